@@ -11,7 +11,7 @@ impl HttpClientContext for MyClient {
     fn new_request(&self, _method: Method, _url: &str) -> Request {
         todo!()
     }
-    async fn run_request(&self, _request: Request) -> Result<Response, Self::Error> {
+    async fn run_request(&mut self, _request: Request) -> Result<Response, Self::Error> {
         todo!()
     }
 }
@@ -23,12 +23,18 @@ pub struct MyCoolEndpoint {
     #[required]
     pub required: String,
     pub not_required: Vec<String>,
+    #[default = 3]
+    pub times: u32,
+    pub comment: Option<String>,
 }
 
 fn main() {
-    let builder = MyClient
-        .coolness(String::from("YEAH IM COOL ;)"))
-        .not_required(vec![String::from("absolutely"), String::from("insanely")]);
-
-    println!("{:#?}", builder.1);
+    let mut client = MyClient;
+    // `execute` only exists once `required` is set
+    let _request = client
+        .coolness()
+        .not_required(vec![String::from("absolutely"), String::from("insanely")])
+        .comment("YEAH IM COOL ;)")
+        .required("very")
+        .execute();
 }
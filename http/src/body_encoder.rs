@@ -22,6 +22,12 @@ impl BodyEncoder {
             None => Self::Chunked(ChunkedEncoder::new(body)),
         }
     }
+
+    /// Send the body as is, even if its length is unknown and the connection has to be closed to
+    /// end it.
+    pub(crate) fn unframed(body: Body) -> Self {
+        Self::Fixed(body)
+    }
 }
 
 impl Read for BodyEncoder {
@@ -3,10 +3,47 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use futures::io::{self, AsyncRead as Read};
+use futures::io::{self, AsyncBufRead as BufRead, AsyncRead as Read};
 use futures::ready;
 use http_types::trailers::{Sender, Trailers};
 
+/// The longest trailer section accepted.
+const MAX_TRAILERS_LENGTH: usize = 8192;
+
+/// A reader of buffered bytes up to a delimiter, which leaves the bytes after it unread.
+pub(crate) trait ReadUntil {
+    /// Append the buffered bytes up to and including `byte`, and at most `limit` bytes, to `buf`,
+    /// filling the buffer first if it is empty. Returns the number of bytes appended, which is `0`
+    /// at the end of the stream or if `limit` is `0`.
+    fn poll_read_until(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        byte: u8,
+        buf: &mut Vec<u8>,
+        limit: usize,
+    ) -> Poll<io::Result<usize>>;
+}
+
+impl<R: BufRead + ?Sized> ReadUntil for R {
+    fn poll_read_until(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        byte: u8,
+        buf: &mut Vec<u8>,
+        limit: usize,
+    ) -> Poll<io::Result<usize>> {
+        let available = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let end = match available.iter().position(|&b| b == byte) {
+            Some(idx) => idx + 1,
+            None => available.len(),
+        };
+        let len = end.min(limit);
+        buf.extend_from_slice(&available[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(len))
+    }
+}
+
 /// Decodes a chunked body according to
 /// https://tools.ietf.org/html/rfc7230#section-4.1
 #[derive(Debug)]
@@ -45,7 +82,7 @@ enum State {
     /// Expecting the \n at the end of a chunk body
     ChunkBodyExpectLf,
     /// Parsing trailers.
-    Trailers(usize, Box<[u8; 8192]>),
+    Trailers(Vec<u8>),
     /// Sending trailers over the channel.
     TrailerSending(Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>>),
     /// All is said and done.
@@ -60,7 +97,7 @@ impl fmt::Debug for State {
             State::ChunkBody => write!(f, "State::ChunkBody"),
             State::ChunkBodyExpectCr => write!(f, "State::ChunkBodyExpectCr"),
             State::ChunkBodyExpectLf => write!(f, "State::ChunkBodyExpectLf"),
-            State::Trailers(len, _) => write!(f, "State::Trailers({}, _)", len),
+            State::Trailers(buf) => write!(f, "State::Trailers({}, _)", buf.len()),
             State::TrailerSending(_) => write!(f, "State::TrailerSending"),
             State::Done => write!(f, "State::Done"),
         }
@@ -119,7 +156,7 @@ fn overflow() -> io::Error {
     io::Error::new(io::ErrorKind::InvalidData, "Chunk size overflowed 64 bits")
 }
 
-impl<R: Read + Unpin> Read for ChunkedDecoder<R> {
+impl<R: Read + ReadUntil + Unpin> Read for ChunkedDecoder<R> {
     #[allow(missing_doc_code_examples)]
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -154,7 +191,7 @@ impl<R: Read + Unpin> Read for ChunkedDecoder<R> {
                 State::ChunkSizeExpectLf => {
                     ready!(this.expect_byte(cx, b'\n', "LF"))?;
                     if this.chunk_size == 0 {
-                        this.state = State::Trailers(0, Box::new([0u8; 8192]));
+                        this.state = State::Trailers(Vec::new());
                     } else {
                         this.state = State::ChunkBody;
                     }
@@ -182,29 +219,40 @@ impl<R: Read + Unpin> Read for ChunkedDecoder<R> {
                     ready!(this.expect_byte(cx, b'\n', "LF"))?;
                     this.state = State::ChunkSize;
                 }
-                State::Trailers(ref mut len, ref mut buf) => {
-                    let bytes_read =
-                        ready!(Pin::new(&mut this.inner).poll_read(cx, &mut buf[*len..]))?;
-                    *len += bytes_read;
-                    let len = *len;
-                    if len == 0 {
-                        this.send_trailers(Trailers::new());
-                        continue;
+                State::Trailers(ref mut buf) => {
+                    // Read the trailers a line at a time, to leave the bytes following the body,
+                    // like a pipelined request, in the stream.
+                    if buf.len() == MAX_TRAILERS_LENGTH {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Trailers are too long",
+                        )));
                     }
+                    let limit = MAX_TRAILERS_LENGTH - buf.len();
+                    let bytes_read =
+                        ready!(Pin::new(&mut this.inner).poll_read_until(cx, b'\n', buf, limit))?;
                     if bytes_read == 0 {
+                        if buf.is_empty() {
+                            this.send_trailers(Trailers::new());
+                            continue;
+                        }
                         return eof();
                     }
+                    if buf != b"\r\n" && !buf.ends_with(b"\r\n\r\n") {
+                        continue;
+                    }
+                    let len = buf.len();
+
                     let mut headers = [httparse::EMPTY_HEADER; 16];
                     let parse_result = httparse::parse_headers(&buf[..len], &mut headers)
                         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
                     use httparse::Status;
                     match parse_result {
                         Status::Partial => {
-                            if len == buf.len() {
-                                return eof();
-                            } else {
-                                return Poll::Pending;
-                            }
+                            return Poll::Ready(Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "Malformed trailers",
+                            )))
                         }
                         Status::Complete((offset, headers)) => {
                             if offset != len {
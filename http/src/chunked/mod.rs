@@ -1,5 +1,5 @@
 mod decoder;
 mod encoder;
 
-pub(crate) use decoder::ChunkedDecoder;
+pub(crate) use decoder::{ChunkedDecoder, ReadUntil};
 pub(crate) use encoder::ChunkedEncoder;
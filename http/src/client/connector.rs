@@ -0,0 +1,374 @@
+//! Opening the connections requests are sent over.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_std::net::TcpStream;
+use async_tls::{client::TlsStream, TlsConnector};
+use futures::io::{AsyncRead as Read, AsyncWrite as Write};
+use http_types::url::Host;
+use http_types::{StatusCode, Url};
+
+use super::happy_eyeballs::{self, socket_addrs, CONNECTION_ATTEMPT_DELAY};
+use super::resolve::{Resolver, SystemResolver};
+
+#[cfg(unix)]
+use async_std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// Opens the connection a request to a URL is sent over.
+///
+/// Implement it to send requests through a proxy, over in-memory streams in tests, or to choose
+/// the address of a host yourself, and pass it to [`connect_with`](super::connect_with).
+///
+/// ```
+/// use acril_http::client::Connector;
+/// use async_std::net::TcpStream;
+/// use http_types::Url;
+///
+/// /// Sends every request to a local server.
+/// struct Local(u16);
+///
+/// impl Connector for Local {
+///     type Stream = TcpStream;
+///
+///     async fn connect(&self, _url: &Url) -> http_types::Result<TcpStream> {
+///         Ok(TcpStream::connect(("127.0.0.1", self.0)).await?)
+///     }
+/// }
+/// ```
+pub trait Connector {
+    /// The connection.
+    type Stream: Read + Write + Send + Sync + Unpin + 'static;
+
+    /// Open a connection to the server of `url`.
+    fn connect(&self, url: &Url) -> impl Future<Output = http_types::Result<Self::Stream>> + Send;
+}
+
+impl<C: Connector + Sync> Connector for &C {
+    type Stream = C::Stream;
+
+    fn connect(&self, url: &Url) -> impl Future<Output = http_types::Result<Self::Stream>> + Send {
+        (**self).connect(url)
+    }
+}
+
+/// The connector of [`connect`](super::connect): TCP, with TLS for `https:` URLs, and Unix domain
+/// sockets for `unix:` URLs.
+///
+/// Host names are resolved by the resolver `R`, and the addresses are raced following
+/// [RFC 8305](https://www.rfc-editor.org/rfc/rfc8305), starting a new attempt every
+/// `attempt_delay` until one connects:
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use acril_http::client::{CachingResolver, DefaultConnector, StaticResolver, SystemResolver};
+///
+/// let resolver = StaticResolver::new()
+///     .with_host("api.staging", ["10.0.0.7".parse().unwrap()])
+///     .with_fallback(CachingResolver::new(SystemResolver, Duration::from_secs(60)));
+/// let connector = DefaultConnector::new().with_resolver(resolver);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DefaultConnector<R = SystemResolver> {
+    resolver: R,
+    attempt_delay: Duration,
+}
+
+impl DefaultConnector {
+    /// A connector resolving hosts with the resolver of the operating system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<R: Default> Default for DefaultConnector<R> {
+    fn default() -> Self {
+        Self {
+            resolver: R::default(),
+            attempt_delay: CONNECTION_ATTEMPT_DELAY,
+        }
+    }
+}
+
+impl<R> DefaultConnector<R> {
+    /// Resolve hosts with `resolver`.
+    pub fn with_resolver<T: Resolver>(self, resolver: T) -> DefaultConnector<T> {
+        DefaultConnector {
+            resolver,
+            attempt_delay: self.attempt_delay,
+        }
+    }
+
+    /// Set the delay before trying the next address of a host while connecting to the previous
+    /// ones. Defaults to 250ms.
+    pub fn with_attempt_delay(mut self, attempt_delay: Duration) -> Self {
+        self.attempt_delay = attempt_delay;
+        self
+    }
+
+    /// The resolver of host names.
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+}
+
+impl<R: Resolver + Sync> Connector for DefaultConnector<R> {
+    type Stream = DefaultStream;
+
+    async fn connect(&self, url: &Url) -> http_types::Result<DefaultStream> {
+        #[cfg(unix)]
+        if let Some(socket) = super::unix_socket_path(url) {
+            return Ok(DefaultStream::Unix(UnixStream::connect(socket).await?));
+        }
+
+        let host = url.host().ok_or_else(|| {
+            http_types::Error::from_str(StatusCode::UnprocessableEntity, "No host in request URL")
+        })?;
+        let port = url.port_or_known_default().ok_or_else(|| {
+            http_types::Error::from_str(StatusCode::UnprocessableEntity, "No port in request URL")
+        })?;
+        let ips = match host {
+            Host::Domain(domain) => self.resolver.resolve(domain).await?,
+            Host::Ipv4(ip) => vec![ip.into()],
+            Host::Ipv6(ip) => vec![ip.into()],
+        };
+        let stream = happy_eyeballs::connect(socket_addrs(ips, port), self.attempt_delay).await?;
+
+        if url.scheme() == "https" {
+            let domain = url.host_str().unwrap_or_default();
+            let stream = TlsConnector::default().connect(domain, stream).await?;
+            Ok(DefaultStream::Tls(Box::new(stream)))
+        } else {
+            Ok(DefaultStream::Tcp(stream))
+        }
+    }
+}
+
+/// A connection opened by [`DefaultConnector`].
+#[derive(Debug)]
+pub enum DefaultStream {
+    /// A plain TCP connection.
+    Tcp(TcpStream),
+    /// A TLS connection over TCP.
+    Tls(Box<TlsStream<TcpStream>>),
+    /// A Unix domain socket.
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+macro_rules! delegate {
+    ($self:ident, $stream:ident => $call:expr) => {
+        match $self.get_mut() {
+            DefaultStream::Tcp($stream) => $call,
+            DefaultStream::Tls($stream) => $call,
+            #[cfg(unix)]
+            DefaultStream::Unix($stream) => $call,
+        }
+    };
+}
+
+impl Read for DefaultStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        delegate!(self, stream => Pin::new(stream).poll_read(cx, buf))
+    }
+}
+
+impl Write for DefaultStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        delegate!(self, stream => Pin::new(stream).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        delegate!(self, stream => Pin::new(stream).poll_flush(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        delegate!(self, stream => Pin::new(stream).poll_close(cx))
+    }
+}
+
+/// A connector sending every request to the server listening on a Unix domain socket, whatever
+/// the URL of the request.
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixConnector {
+    socket: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixConnector {
+    /// Connect to the socket at `socket`.
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Connector for UnixConnector {
+    type Stream = UnixStream;
+
+    async fn connect(&self, _url: &Url) -> http_types::Result<UnixStream> {
+        Ok(UnixStream::connect(&self.socket).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::io::Cursor;
+    use http_types::{Method, Request};
+
+    use super::*;
+
+    /// A connection answering with `response`, recording the request written to it.
+    struct Pipe {
+        response: Cursor<&'static [u8]>,
+        request: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Read for Pipe {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.response).poll_read(cx, buf)
+        }
+    }
+
+    impl Write for Pipe {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.request.lock().unwrap().extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    struct PipeConnector {
+        response: &'static [u8],
+        request: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl PipeConnector {
+        fn new(response: &'static [u8]) -> Self {
+            Self {
+                response,
+                request: Default::default(),
+            }
+        }
+    }
+
+    impl Connector for PipeConnector {
+        type Stream = Pipe;
+
+        async fn connect(&self, _url: &Url) -> http_types::Result<Pipe> {
+            Ok(Pipe {
+                response: Cursor::new(self.response),
+                request: self.request.clone(),
+            })
+        }
+    }
+
+    #[test]
+    fn sends_requests_over_the_connector() {
+        let connector = PipeConnector::new(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+        let url = Url::parse("http://example.com/items").unwrap();
+        let mut res = futures::executor::block_on(crate::client::connect_with(
+            &connector,
+            Request::new(Method::Get, url),
+        ))
+        .unwrap();
+
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(
+            futures::executor::block_on(res.body_string()).unwrap(),
+            "hello"
+        );
+        let request = String::from_utf8(connector.request.lock().unwrap().clone()).unwrap();
+        assert!(request.starts_with("GET /items HTTP/1.1\r\n"));
+        assert!(request.contains("host: example.com\r\n"));
+    }
+
+    #[test]
+    fn limits_response_bodies() {
+        use crate::limit::BodyTooLarge;
+
+        let send = |response| {
+            let connector = PipeConnector::new(response);
+            let url = Url::parse("http://example.com/").unwrap();
+            futures::executor::block_on(async {
+                let req = Request::new(Method::Get, url);
+                let mut res = crate::client::connect_with_limit(&connector, req, Some(4)).await?;
+                res.body_string().await
+            })
+        };
+
+        assert_eq!(
+            send(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nabcd").unwrap(),
+            "abcd"
+        );
+        let error = send(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nabcde").unwrap_err();
+        assert_eq!(error.status(), StatusCode::PayloadTooLarge);
+        assert_eq!(BodyTooLarge::find(&error), Some(&BodyTooLarge { limit: 4 }));
+
+        let error = send(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n",
+        )
+        .unwrap_err();
+        assert_eq!(BodyTooLarge::find(&error), Some(&BodyTooLarge { limit: 4 }));
+    }
+
+    #[test]
+    fn resolves_hosts_with_the_resolver() {
+        use crate::client::StaticResolver;
+        use async_std::net::TcpListener;
+
+        async_std::task::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let resolver = StaticResolver::new().with_host(
+                "api.test",
+                ["::1".parse().unwrap(), "127.0.0.1".parse().unwrap()],
+            );
+            let connector = DefaultConnector::new().with_resolver(resolver);
+
+            // nothing listens on the IPv6 loopback, so the IPv4 address is used
+            let url = Url::parse(&format!("http://api.test:{}/", port)).unwrap();
+            let stream = connector.connect(&url).await.unwrap();
+            let (_, peer) = listener.accept().await.unwrap();
+            assert!(matches!(stream, DefaultStream::Tcp(_)));
+            assert!(peer.is_ipv4());
+
+            let url = Url::parse("http://unknown.test/").unwrap();
+            assert!(connector.connect(&url).await.is_err());
+        });
+    }
+}
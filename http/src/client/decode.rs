@@ -10,6 +10,7 @@ use std::convert::TryFrom;
 
 use crate::chunked::ChunkedDecoder;
 use crate::date::fmt_http_date;
+use crate::limit::{BodyTooLarge, Limited};
 use crate::{MAX_HEADERS, MAX_HEAD_LENGTH};
 
 const CR: u8 = b'\r';
@@ -17,6 +18,17 @@ const LF: u8 = b'\n';
 
 /// Decode an HTTP response on the client.
 pub async fn decode<R>(reader: R) -> http_types::Result<Response>
+where
+    R: Read + Unpin + Send + Sync + 'static,
+{
+    decode_with_limit(reader, None).await
+}
+
+/// Decode an HTTP response on the client, with a body of at most `limit` bytes.
+///
+/// A response with a longer `Content-Length` is rejected with [`BodyTooLarge`] before its body is
+/// read, and reading a longer chunked body fails with it once it has been read past the limit.
+pub async fn decode_with_limit<R>(reader: R, limit: Option<u64>) -> http_types::Result<Response>
 where
     R: Read + Unpin + Send + Sync + 'static,
 {
@@ -85,7 +97,8 @@ where
     if let Some(encoding) = transfer_encoding {
         if encoding.last().as_str() == "chunked" {
             let trailers_sender = res.send_trailers();
-            let reader = BufReader::new(ChunkedDecoder::new(reader, trailers_sender));
+            let reader = ChunkedDecoder::new(reader, trailers_sender);
+            let reader = BufReader::new(Limited::new(reader, limit));
             res.set_body(Body::from_reader(reader, None));
 
             // Return the response.
@@ -96,6 +109,7 @@ where
     // Check for Content-Length.
     if let Some(len) = content_length {
         let len = len.last().as_str().parse::<usize>()?;
+        BodyTooLarge::check(Some(len as u64), limit)?;
         res.set_body(Body::from_reader(reader.take(len as u64), Some(len)));
     }
 
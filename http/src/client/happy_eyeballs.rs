@@ -0,0 +1,171 @@
+//! Connecting to the first reachable address of a host, racing IPv6 and IPv4 as described by
+//! [RFC 8305](https://www.rfc-editor.org/rfc/rfc8305).
+
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use async_io::Timer;
+use async_std::net::TcpStream;
+use futures::future::{self, Either};
+use futures::stream::{FuturesUnordered, StreamExt};
+
+/// The delay before starting the next connection attempt while the previous ones are pending,
+/// recommended by RFC 8305.
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Connect to the first of `addrs` to accept a connection.
+///
+/// The addresses are tried alternating between IPv6 and IPv4, starting with IPv6, and a new
+/// attempt is started when the previous one fails or after `delay`, whichever comes first. The
+/// first connection established wins and the other attempts are dropped.
+pub async fn connect(addrs: Vec<SocketAddr>, delay: Duration) -> io::Result<TcpStream> {
+    race(addrs, delay, TcpStream::connect).await
+}
+
+/// Race `attempt` over `addrs` as [`connect`] does.
+pub(crate) async fn race<S, F, Fut>(
+    addrs: Vec<SocketAddr>,
+    delay: Duration,
+    mut attempt: F,
+) -> io::Result<S>
+where
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+{
+    let mut addrs = interleave(addrs).into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error = None;
+
+    loop {
+        if attempts.is_empty() {
+            match addrs.next() {
+                Some(addr) => attempts.push(attempt(addr)),
+                None => {
+                    return Err(last_error.unwrap_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to")
+                    }))
+                }
+            }
+        }
+
+        let next_attempt = if addrs.len() > 0 {
+            Either::Left(Timer::after(delay))
+        } else {
+            Either::Right(future::pending())
+        };
+        match future::select(attempts.next(), next_attempt).await {
+            Either::Left((Some(Ok(stream)), _)) => return Ok(stream),
+            Either::Left((Some(Err(e)), _)) => {
+                last_error = Some(e);
+                if let Some(addr) = addrs.next() {
+                    attempts.push(attempt(addr));
+                }
+            }
+            Either::Left((None, _)) => {}
+            Either::Right(_) => {
+                if let Some(addr) = addrs.next() {
+                    attempts.push(attempt(addr));
+                }
+            }
+        }
+    }
+}
+
+/// Order `addrs` alternating between IPv6 and IPv4 addresses, starting with IPv6, keeping the
+/// order of the addresses of each family.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(SocketAddr::is_ipv6);
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+
+    let mut ordered = Vec::with_capacity(v6.len() + v4.len());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// The socket addresses of `ips` on `port`.
+pub(crate) fn socket_addrs(ips: Vec<IpAddr>, port: u16) -> Vec<SocketAddr> {
+    ips.into_iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn interleaves_families() {
+        let addrs = vec![
+            addr("10.0.0.1:80"),
+            addr("10.0.0.2:80"),
+            addr("10.0.0.3:80"),
+            addr("[::1]:80"),
+        ];
+        assert_eq!(
+            interleave(addrs),
+            [
+                addr("[::1]:80"),
+                addr("10.0.0.1:80"),
+                addr("10.0.0.2:80"),
+                addr("10.0.0.3:80"),
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_ipv4_after_the_delay() {
+        let start = Instant::now();
+        let addrs = vec![addr("10.0.0.1:80"), addr("[2001:db8::1]:80")];
+        // the IPv6 attempt never completes
+        let winner = futures::executor::block_on(race(
+            addrs,
+            Duration::from_millis(50),
+            |addr| async move {
+                if addr.is_ipv6() {
+                    future::pending::<()>().await;
+                }
+                Ok(addr)
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(winner, addr("10.0.0.1:80"));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn moves_on_after_failures() {
+        let addrs = vec![addr("[2001:db8::1]:80"), addr("10.0.0.1:80")];
+        let start = Instant::now();
+        let winner =
+            futures::executor::block_on(race(addrs, Duration::from_secs(60), |addr| async move {
+                if addr.is_ipv6() {
+                    return Err(io::Error::from(io::ErrorKind::ConnectionRefused));
+                }
+                Ok(addr)
+            }))
+            .unwrap();
+        assert_eq!(winner, addr("10.0.0.1:80"));
+        assert!(start.elapsed() < Duration::from_secs(60));
+
+        let error = futures::executor::block_on(race(
+            vec![addr("10.0.0.1:80")],
+            Duration::ZERO,
+            |_| async { Err::<(), _>(io::Error::from(io::ErrorKind::ConnectionRefused)) },
+        ))
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    }
+}
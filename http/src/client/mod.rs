@@ -1,23 +1,40 @@
 //! Process HTTP connections on the client.
 
-use http_types::{Request, Response, StatusCode};
+use http_types::{Request, Response};
 
+#[cfg(not(target_arch = "wasm32"))]
+mod connector;
 #[cfg(not(target_arch = "wasm32"))]
 mod decode;
 #[cfg(not(target_arch = "wasm32"))]
 mod encode;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod happy_eyeballs;
+#[cfg(not(target_arch = "wasm32"))]
+mod resolve;
+#[cfg(unix)]
+mod unix;
 
+#[cfg(unix)]
+pub use connector::UnixConnector;
 #[cfg(not(target_arch = "wasm32"))]
-pub use decode::decode;
+pub use connector::{Connector, DefaultConnector, DefaultStream};
+#[cfg(not(target_arch = "wasm32"))]
+pub use decode::{decode, decode_with_limit};
 #[cfg(not(target_arch = "wasm32"))]
 pub use encode::Encoder;
-use async_tls::TlsConnector;
-use async_std::net::TcpStream;
-
-use futures::io::{AsyncRead as Read, AsyncWrite as Write, self};
+use futures::io::{self, AsyncRead as Read, AsyncWrite as Write};
+#[cfg(not(target_arch = "wasm32"))]
+pub use resolve::{CachingResolver, Resolver, StaticResolver, SystemResolver};
+#[cfg(unix)]
+pub use unix::{connect_unix, unix_socket_path, unix_url, UNIX_SCHEME};
 
 #[cfg(not(target_arch = "wasm32"))]
-async fn native_connect<RW>(mut stream: RW, req: Request) -> http_types::Result<Response>
+async fn native_connect<RW>(
+    mut stream: RW,
+    req: Request,
+    limit: Option<u64>,
+) -> http_types::Result<Response>
 where
     RW: Read + Write + Send + Sync + Unpin + 'static,
 {
@@ -26,13 +43,13 @@ where
 
     io::copy(&mut req, &mut stream).await?;
 
-    let res = decode(stream).await?;
+    let res = decode_with_limit(stream, limit).await?;
     log::trace!("< {:?}", &res);
 
     Ok(res)
 }
 
-/// Opens an HTTP/1.1 connection to a remote host.
+/// Opens an HTTP/1.1 connection to a remote host, or to the Unix domain socket of a `unix:` URL.
 pub async fn connect(req: Request) -> http_types::Result<Response> {
     #[cfg(target_arch = "wasm32")]
     {
@@ -57,60 +74,31 @@ pub async fn connect(req: Request) -> http_types::Result<Response> {
     }
 
     #[cfg(not(target_arch = "wasm32"))]
-    if req.url().scheme() == "https" {
-        let stream = TcpStream::connect(format!(
-            "{}:{}",
-            req.url().host_str().ok_or_else(|| {
-                http_types::Error::from_str(
-                    StatusCode::UnprocessableEntity,
-                    "No host in request URL",
-                )
-            })?,
-            req.url().port_or_known_default().ok_or_else(|| {
-                http_types::Error::from_str(
-                    StatusCode::UnprocessableEntity,
-                    "No port in request URL",
-                )
-            })?
-        ))
-        .await?;
-        native_connect(
-            TlsConnector::default()
-                .connect(
-                    req.host().ok_or_else(|| {
-                        http_types::Error::from_str(
-                            StatusCode::UnprocessableEntity,
-                            "No host in request URL",
-                            )
-                    })?,
-                    stream,
-                )
-                .await?,
-            req,
-        )
-        .await
-    } else {
-        native_connect(
-            TcpStream::connect(format!(
-                "{}:{}",
-                req.url().host_str().ok_or_else(|| {
-                    http_types::Error::from_str(
-                        StatusCode::UnprocessableEntity,
-                        "No host in request URL",
-                    )
-                })?,
-                req.url().port_or_known_default().ok_or_else(|| {
-                    http_types::Error::from_str(
-                        StatusCode::UnprocessableEntity,
-                        "No port in request URL",
-                    )
-                })?
-            ))
-            .await?,
-            req,
-        )
-        .await
-    }
+    connect_with(&DefaultConnector::new(), req).await
+}
+
+/// Send `req` over a connection opened by `connector`.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn connect_with<C: Connector>(
+    connector: &C,
+    req: Request,
+) -> http_types::Result<Response> {
+    connect_with_limit(connector, req, None).await
+}
+
+/// Send `req` over a connection opened by `connector`, with a response body of at most `limit`
+/// bytes, as [`decode_with_limit`] reads it.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn connect_with_limit<C: Connector>(
+    connector: &C,
+    mut req: Request,
+    limit: Option<u64>,
+) -> http_types::Result<Response> {
+    let stream = connector.connect(req.url()).await?;
+    #[cfg(unix)]
+    unix::set_host(&mut req);
+
+    native_connect(stream, req, limit).await
 }
 
 #[cfg(target_arch = "wasm32")]
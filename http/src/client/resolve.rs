@@ -0,0 +1,190 @@
+//! Resolving host names to the addresses [`DefaultConnector`](super::DefaultConnector) connects to.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_std::net::ToSocketAddrs;
+
+/// Resolves host names to IP addresses.
+pub trait Resolver {
+    /// The addresses of `host`, in order of preference.
+    fn resolve(&self, host: &str) -> impl Future<Output = io::Result<Vec<IpAddr>>> + Send;
+}
+
+impl<R: Resolver + Sync> Resolver for &R {
+    fn resolve(&self, host: &str) -> impl Future<Output = io::Result<Vec<IpAddr>>> + Send {
+        (**self).resolve(host)
+    }
+}
+
+/// The resolver of the operating system.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        let addrs = (host, 0).to_socket_addrs().await?;
+        Ok(addrs.map(|addr| addr.ip()).collect())
+    }
+}
+
+/// A resolver with fixed addresses for some hosts, falling back to another resolver for the
+/// others.
+///
+/// Without a fallback, it fails to resolve unknown hosts, and serves as a stub resolver in tests:
+///
+/// ```
+/// use acril_http::client::{Resolver, StaticResolver};
+///
+/// let resolver = StaticResolver::new().with_host("api.test", ["127.0.0.1".parse().unwrap()]);
+/// # futures::executor::block_on(async {
+/// assert_eq!(resolver.resolve("api.test").await?, ["127.0.0.1".parse::<std::net::IpAddr>()?]);
+/// assert!(resolver.resolve("example.com").await.is_err());
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StaticResolver<R = SystemResolver> {
+    hosts: HashMap<String, Vec<IpAddr>>,
+    fallback: Option<R>,
+}
+
+impl StaticResolver {
+    /// A resolver without hosts nor fallback.
+    pub fn new() -> Self {
+        Self {
+            hosts: HashMap::new(),
+            fallback: None,
+        }
+    }
+}
+
+impl<R> StaticResolver<R> {
+    /// Resolve `host` to `addrs`.
+    pub fn with_host(mut self, host: &str, addrs: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.hosts
+            .insert(host.to_ascii_lowercase(), addrs.into_iter().collect());
+        self
+    }
+
+    /// Resolve the hosts without fixed addresses with `fallback`.
+    pub fn with_fallback<F>(self, fallback: F) -> StaticResolver<F> {
+        StaticResolver {
+            hosts: self.hosts,
+            fallback: Some(fallback),
+        }
+    }
+}
+
+impl<R: Resolver + Sync> Resolver for StaticResolver<R> {
+    async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        if let Some(addrs) = self.hosts.get(&host.to_ascii_lowercase()) {
+            return Ok(addrs.clone());
+        }
+
+        match &self.fallback {
+            Some(fallback) => fallback.resolve(host).await,
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no addresses for host {}", host),
+            )),
+        }
+    }
+}
+
+/// The addresses of hosts, with the instant they expire.
+type Cache = HashMap<String, (Instant, Vec<IpAddr>)>;
+
+/// A resolver caching the addresses resolved by another resolver for `ttl`. Failures are not
+/// cached, and clones share their cache.
+#[derive(Debug, Clone)]
+pub struct CachingResolver<R = SystemResolver> {
+    inner: R,
+    ttl: Duration,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl<R: Resolver> CachingResolver<R> {
+    /// Cache the addresses resolved by `inner` for `ttl`.
+    pub fn new(inner: R, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: Default::default(),
+        }
+    }
+
+    /// Forget all cached addresses.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+impl<R: Resolver + Sync> Resolver for CachingResolver<R> {
+    async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        let host = host.to_ascii_lowercase();
+        if let Some((expires, addrs)) = self.cache.lock().unwrap().get(&host) {
+            if Instant::now() < *expires {
+                return Ok(addrs.clone());
+            }
+        }
+
+        let addrs = self.inner.resolve(&host).await?;
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(host, (Instant::now() + self.ttl, addrs.clone()));
+        Ok(addrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    /// A resolver counting its lookups.
+    #[derive(Default)]
+    struct Counting(AtomicUsize);
+
+    impl Resolver for Counting {
+        async fn resolve(&self, _host: &str) -> io::Result<Vec<IpAddr>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![IpAddr::from([10, 0, 0, 1])])
+        }
+    }
+
+    #[test]
+    fn overrides_hosts() {
+        let resolver = StaticResolver::new()
+            .with_host("API.test", [IpAddr::from([127, 0, 0, 1])])
+            .with_fallback(Counting::default());
+
+        let addrs = block_on(resolver.resolve("api.TEST")).unwrap();
+        assert_eq!(addrs, [IpAddr::from([127, 0, 0, 1])]);
+        let addrs = block_on(resolver.resolve("other.test")).unwrap();
+        assert_eq!(addrs, [IpAddr::from([10, 0, 0, 1])]);
+    }
+
+    #[test]
+    fn caches_for_the_ttl() {
+        let cached = CachingResolver::new(Counting::default(), Duration::from_secs(60));
+        block_on(cached.resolve("api.test")).unwrap();
+        block_on(cached.resolve("api.test")).unwrap();
+        assert_eq!(cached.inner.0.load(Ordering::SeqCst), 1);
+        block_on(cached.resolve("other.test")).unwrap();
+        assert_eq!(cached.inner.0.load(Ordering::SeqCst), 2);
+
+        let expired = CachingResolver::new(Counting::default(), Duration::ZERO);
+        block_on(expired.resolve("api.test")).unwrap();
+        block_on(expired.resolve("api.test")).unwrap();
+        assert_eq!(expired.inner.0.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,110 @@
+//! Connections to servers listening on Unix domain sockets.
+//!
+//! The socket of a `unix:` URL is its host, with the path of the socket percent-encoded, so a
+//! request to the Docker API is sent to `unix://%2Fvar%2Frun%2Fdocker.sock/containers/json`. Use
+//! [`unix_url`] to build these URLs, which also work as base URLs for relative paths.
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use http_types::headers::HOST;
+use http_types::{Request, Response, Url};
+
+use super::{connect_with, UnixConnector};
+
+/// The scheme of URLs of Unix domain sockets.
+pub const UNIX_SCHEME: &str = "unix";
+
+/// The `Host` header of requests sent over Unix domain sockets, which have no host name.
+const UNIX_HOST: &str = "localhost";
+
+/// The `unix:` URL of `path` on the server listening on the socket at `socket`.
+///
+/// ```
+/// let url = acril_http::client::unix_url("/var/run/docker.sock", "/containers/json").unwrap();
+/// assert_eq!(url.as_str(), "unix://%2Fvar%2Frun%2Fdocker.sock/containers/json");
+/// ```
+pub fn unix_url(socket: impl AsRef<Path>, path: &str) -> http_types::Result<Url> {
+    let mut host = String::new();
+    for &byte in socket.as_ref().as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                host.push(byte as char)
+            }
+            _ => host.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    let mut url = Url::parse(&format!("{}://{}/", UNIX_SCHEME, host))?;
+    url = url.join(path)?;
+    Ok(url)
+}
+
+/// The path of the socket of the `unix:` URL `url`, or `None` if it is not a `unix:` URL.
+pub fn unix_socket_path(url: &Url) -> Option<PathBuf> {
+    if url.scheme() != UNIX_SCHEME {
+        return None;
+    }
+
+    let host = url.host_str()?.as_bytes();
+    let mut path = Vec::with_capacity(host.len());
+    let mut i = 0;
+    while i < host.len() {
+        let escaped = host
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (host[i], escaped) {
+            (b'%', Some(byte)) => {
+                path.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                path.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    Some(PathBuf::from(OsStr::from_bytes(&path)))
+}
+
+/// Send `req` to the server listening on the Unix domain socket at `socket`, whatever the host
+/// of its URL.
+pub async fn connect_unix(socket: impl AsRef<Path>, req: Request) -> http_types::Result<Response> {
+    connect_with(&UnixConnector::new(socket.as_ref()), req).await
+}
+
+/// Set the `Host` header of a request to a `unix:` URL, whose host is the path of the socket.
+pub(super) fn set_host(req: &mut Request) {
+    if req.url().scheme() == UNIX_SCHEME && req.header(HOST).is_none() {
+        req.insert_header(HOST, UNIX_HOST);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_paths_round_trip() {
+        let url = unix_url("/tmp/my app.sock", "/v1/items?limit=2").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "unix://%2Ftmp%2Fmy%20app.sock/v1/items?limit=2"
+        );
+        assert_eq!(url.path(), "/v1/items");
+        assert_eq!(
+            unix_socket_path(&url).unwrap(),
+            Path::new("/tmp/my app.sock")
+        );
+
+        let relative = url.join("/v2/items").unwrap();
+        assert_eq!(unix_socket_path(&relative), unix_socket_path(&url));
+        assert_eq!(
+            unix_socket_path(&Url::parse("http://example.com/").unwrap()),
+            None
+        );
+    }
+}
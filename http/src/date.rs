@@ -38,15 +38,14 @@ pub struct HttpDate {
 /// Supports the preferred IMF-fixdate and the legacy RFC 805 and
 /// ascdate formats. Two digit years are mapped to dates between
 /// 1970 and 2069.
-#[allow(dead_code)]
-pub(crate) fn parse_http_date(s: &str) -> http_types::Result<SystemTime> {
+pub fn parse_http_date(s: &str) -> http_types::Result<SystemTime> {
     s.parse::<HttpDate>().map(|d| d.into())
 }
 
 /// Format a date to be used in a HTTP header field.
 ///
 /// Dates are formatted as IMF-fixdate: `Fri, 15 May 2015 15:34:21 GMT`.
-pub(crate) fn fmt_http_date(d: SystemTime) -> String {
+pub fn fmt_http_date(d: SystemTime) -> String {
     format!("{}", HttpDate::from(d))
 }
 
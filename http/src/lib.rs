@@ -112,12 +112,15 @@ mod date;
 mod read_notifier;
 
 pub mod client;
+pub mod limit;
+pub mod multipart;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod server;
 
 #[cfg(not(target_arch = "wasm32"))]
 use body_encoder::BodyEncoder;
 pub use client::connect;
+pub use date::{fmt_http_date, parse_http_date, HttpDate};
 use futures::io::Cursor;
 pub use futures::io::{AsyncRead as Read, AsyncWrite as Write};
 #[cfg(not(target_arch = "wasm32"))]
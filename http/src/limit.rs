@@ -0,0 +1,159 @@
+//! Limits on the size of bodies.
+
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::io::{self, AsyncRead as Read};
+use http_types::StatusCode;
+
+/// The error of a body longer than its limit.
+///
+/// Bodies whose length is known in advance fail with it right away, as an [`http_types::Error`]
+/// with the status `413 Payload Too Large`. Others fail with it as an [`io::Error`] once they have
+/// been read past the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyTooLarge {
+    /// The maximum size of the body, in bytes.
+    pub limit: u64,
+}
+
+impl BodyTooLarge {
+    /// The `BodyTooLarge` error that caused `error`, directly or through an `io::Error`.
+    pub fn find(error: &http_types::Error) -> Option<&Self> {
+        error.downcast_ref::<Self>().or_else(|| {
+            error
+                .downcast_ref::<io::Error>()?
+                .get_ref()?
+                .downcast_ref::<Self>()
+        })
+    }
+
+    /// Check `length`, the length of a body if it is known, against `limit`.
+    pub fn check(length: Option<u64>, limit: Option<u64>) -> http_types::Result<()> {
+        match (length, limit) {
+            (Some(length), Some(limit)) if length > limit => Err(http_types::Error::new(
+                StatusCode::PayloadTooLarge,
+                BodyTooLarge { limit },
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "body exceeds the limit of {} bytes", self.limit)
+    }
+}
+
+impl Error for BodyTooLarge {}
+
+/// A reader failing with [`BodyTooLarge`] once more than `limit` bytes have been read from it.
+#[pin_project::pin_project]
+#[derive(Debug)]
+pub struct Limited<R> {
+    #[pin]
+    inner: R,
+    limit: Option<u64>,
+    read: u64,
+    exceeded: bool,
+}
+
+impl<R: Read> Limited<R> {
+    /// Limit `inner` to `limit` bytes, or leave it unlimited if `limit` is `None`.
+    pub fn new(inner: R, limit: Option<u64>) -> Self {
+        Self {
+            inner,
+            limit,
+            read: 0,
+            exceeded: false,
+        }
+    }
+
+    /// Limit `inner`, a body of `length` bytes if its length is known, to `limit` bytes. The
+    /// reader fails without reading from `inner` if `length` is known to exceed the limit.
+    pub fn with_length(inner: R, length: Option<u64>, limit: Option<u64>) -> Self {
+        let mut limited = Self::new(inner, limit);
+        limited.exceeded = BodyTooLarge::check(length, limit).is_err();
+        limited
+    }
+
+    /// Whether more than `limit` bytes have been read.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    /// The limited reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let Some(limit) = *this.limit else {
+            return this.inner.poll_read(cx, buf);
+        };
+        if *this.exceeded {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                BodyTooLarge { limit },
+            )));
+        }
+
+        // read one byte past the limit, to tell a body of exactly `limit` bytes from a longer one
+        let remaining = limit - *this.read + 1;
+        let len = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let read = futures::ready!(this.inner.poll_read(cx, &mut buf[..len]))?;
+        *this.read += read as u64;
+        if *this.read > limit {
+            *this.exceeded = true;
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                BodyTooLarge { limit },
+            )));
+        }
+
+        Poll::Ready(Ok(read))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, io::AsyncReadExt};
+
+    use super::*;
+
+    #[test]
+    fn fails_past_the_limit() {
+        let mut exact = Limited::new(&b"abcd"[..], Some(4));
+        let mut body = Vec::new();
+        block_on(exact.read_to_end(&mut body)).unwrap();
+        assert_eq!(body, b"abcd");
+        assert!(!exact.exceeded());
+
+        let mut longer = Limited::new(&b"abcde"[..], Some(4));
+        let error = block_on(longer.read_to_end(&mut Vec::new())).unwrap_err();
+        assert!(longer.exceeded());
+        let error = http_types::Error::from(error);
+        assert_eq!(BodyTooLarge::find(&error), Some(&BodyTooLarge { limit: 4 }));
+
+        let error = BodyTooLarge::check(Some(5), Some(4)).unwrap_err();
+        assert_eq!(error.status(), StatusCode::PayloadTooLarge);
+        assert_eq!(BodyTooLarge::find(&error), Some(&BodyTooLarge { limit: 4 }));
+        assert!(BodyTooLarge::check(Some(5), None).is_ok());
+
+        let mut known = Limited::with_length(&b"abcde"[..], Some(5), Some(4));
+        assert!(known.exceeded());
+        assert!(block_on(known.read(&mut [0; 8])).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Streaming `multipart/form-data` encoding, as described in
+//! [RFC 7578](https://www.rfc-editor.org/rfc/rfc7578).
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+
+use futures::io::{AsyncRead as Read, BufReader, Cursor};
+use futures::{io, ready};
+use http_types::{mime, Body, Mime};
+
+/// A part of a [`Multipart`] body.
+pub struct Part {
+    name: String,
+    file_name: Option<String>,
+    mime: Option<Mime>,
+    body: Body,
+}
+
+impl fmt::Debug for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Part")
+            .field("name", &self.name)
+            .field("file_name", &self.file_name)
+            .field("mime", &self.mime)
+            .field("len", &self.body.len())
+            .finish()
+    }
+}
+
+impl Part {
+    /// Create a part named `name` from `body`, sent with the mime type of the body.
+    pub fn new(name: impl Into<String>, body: impl Into<Body>) -> Self {
+        let body = body.into();
+        Self {
+            name: name.into(),
+            file_name: None,
+            mime: Some(body.mime().clone()),
+            body,
+        }
+    }
+
+    /// Create a text part, sent without a `Content-Type` (which means `text/plain`).
+    pub fn text(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            mime: None,
+            ..Self::new(name, value.into())
+        }
+    }
+
+    /// Create an `application/octet-stream` part containing `bytes`.
+    pub fn bytes(name: impl Into<String>, bytes: impl Into<Vec<u8>>) -> Self {
+        Self::new(name, bytes.into())
+    }
+
+    /// Create an `application/octet-stream` part streamed from `reader`. If the length of the
+    /// part is not known, the length of the whole body will not be known either.
+    pub fn reader(
+        name: impl Into<String>,
+        reader: impl Read + Unpin + Send + Sync + 'static,
+        len: Option<usize>,
+    ) -> Self {
+        Self::new(name, Body::from_reader(BufReader::new(reader), len))
+    }
+
+    /// Create a part streamed from the file at `path`. The file name is set to the name of the
+    /// file, and the mime type is guessed from its extension.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn file(
+        name: impl Into<String>,
+        path: impl AsRef<std::path::Path>,
+    ) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = async_std::fs::File::open(path).await?;
+        let len = file.metadata().await?.len() as usize;
+
+        let mut part = Self::reader(name, file, Some(len));
+        if let Some(file_name) = path.file_name() {
+            part.file_name = Some(file_name.to_string_lossy().into_owned());
+        }
+        if let Some(mime) = path
+            .extension()
+            .and_then(|ext| Mime::from_extension(ext.to_string_lossy()))
+        {
+            part.mime = Some(mime);
+        }
+        Ok(part)
+    }
+
+    /// Set the file name sent in the `Content-Disposition` of this part.
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Set the `Content-Type` of this part.
+    pub fn mime(mut self, mime: impl Into<Mime>) -> Self {
+        self.mime = Some(mime.into());
+        self
+    }
+
+    /// The name of this part.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The headers of this part, including the blank line ending them.
+    fn head(&self) -> String {
+        let mut head = format!(
+            "Content-Disposition: form-data; name=\"{}\"",
+            escape(&self.name)
+        );
+        if let Some(file_name) = &self.file_name {
+            head.push_str(&format!("; filename=\"{}\"", escape(file_name)));
+        }
+        head.push_str("\r\n");
+        if let Some(mime) = &self.mime {
+            head.push_str(&format!("Content-Type: {}\r\n", mime));
+        }
+        head.push_str("\r\n");
+        head
+    }
+}
+
+/// Escape a quoted parameter of `Content-Disposition` the way browsers do.
+fn escape(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A `multipart/form-data` body, whose parts are streamed as the body is read.
+///
+/// ```
+/// use acril_http::multipart::{Multipart, Part};
+///
+/// let body = Multipart::new()
+///     .text("title", "holiday")
+///     .part(Part::bytes("photo", vec![0xff, 0xd8]).file_name("beach.jpg").mime(http_types::mime::JPEG))
+///     .into_body();
+/// assert_eq!(body.mime().essence(), "multipart/form-data");
+/// ```
+#[derive(Debug)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Multipart {
+    /// Create an empty body with a randomly generated boundary.
+    pub fn new() -> Self {
+        Self::with_boundary(generate_boundary())
+    }
+
+    /// Create an empty body with the given boundary, which must not occur in any of the parts.
+    pub fn with_boundary(boundary: impl Into<String>) -> Self {
+        Self {
+            boundary: boundary.into(),
+            parts: Vec::new(),
+        }
+    }
+
+    /// The boundary separating the parts.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Append a text part.
+    pub fn text(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.part(Part::text(name, value))
+    }
+
+    /// Append a part.
+    pub fn part(mut self, part: Part) -> Self {
+        self.push(part);
+        self
+    }
+
+    /// Append a part in place.
+    pub fn push(&mut self, part: Part) {
+        self.parts.push(part);
+    }
+
+    /// The mime type of the body, including the boundary.
+    pub fn mime(&self) -> Mime {
+        Mime::from_str(&format!(
+            "{}; boundary={}",
+            mime::MULTIPART_FORM,
+            self.boundary
+        ))
+        .expect("boundaries are valid parameter values")
+    }
+
+    /// Turn this into a [`Body`], whose length is known if the lengths of all parts are.
+    pub fn into_body(self) -> Body {
+        let mime = self.mime();
+
+        let mut len = Some(self.closing().len());
+        for (idx, part) in self.parts.iter().enumerate() {
+            len = len
+                .zip(part.body.len())
+                .map(|(len, body)| len + self.delimiter(idx).len() + part.head().len() + body);
+        }
+
+        let encoder = Encoder {
+            state: State::Done,
+            next_body: None,
+            empty: self.parts.is_empty(),
+            parts: self.parts.into_iter().enumerate(),
+            boundary: self.boundary,
+            closed: false,
+        };
+
+        let mut body = Body::from_reader(BufReader::new(encoder), len);
+        body.set_mime(mime);
+        body
+    }
+
+    /// The delimiter preceding the part at `idx`.
+    fn delimiter(&self, idx: usize) -> String {
+        delimiter(&self.boundary, idx)
+    }
+
+    /// The delimiter closing the body.
+    fn closing(&self) -> String {
+        closing(&self.boundary, self.parts.is_empty())
+    }
+}
+
+fn delimiter(boundary: &str, idx: usize) -> String {
+    let crlf = if idx == 0 { "" } else { "\r\n" };
+    format!("{}--{}\r\n", crlf, boundary)
+}
+
+fn closing(boundary: &str, empty: bool) -> String {
+    let crlf = if empty { "" } else { "\r\n" };
+    format!("{}--{}--\r\n", crlf, boundary)
+}
+
+fn generate_boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    let high = hasher.finish();
+    hasher.write_u64(high);
+    format!("acril-boundary-{:016x}{:016x}", high, hasher.finish())
+}
+
+#[derive(Debug)]
+enum State {
+    Head(Cursor<Vec<u8>>),
+    Body(Body),
+    Done,
+}
+
+/// Reads the parts, framed by their delimiters and headers.
+#[derive(Debug)]
+struct Encoder {
+    state: State,
+    next_body: Option<Body>,
+    empty: bool,
+    parts: std::iter::Enumerate<std::vec::IntoIter<Part>>,
+    boundary: String,
+    closed: bool,
+}
+
+impl Encoder {
+    /// Move to the body following the current head, or to the head of the next part.
+    fn advance(&mut self) {
+        if let Some(body) = self.next_body.take() {
+            self.state = State::Body(body);
+        } else if let Some((idx, part)) = self.parts.next() {
+            let head = delimiter(&self.boundary, idx) + &part.head();
+            self.state = State::Head(Cursor::new(head.into_bytes()));
+            self.next_body = Some(part.body);
+        } else if !self.closed {
+            self.closed = true;
+            let closing = closing(&self.boundary, self.empty);
+            self.state = State::Head(Cursor::new(closing.into_bytes()));
+        } else {
+            self.state = State::Done;
+        }
+    }
+}
+
+impl Read for Encoder {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        loop {
+            let read = match &mut this.state {
+                State::Head(head) => ready!(Pin::new(head).poll_read(cx, buf))?,
+                State::Body(body) => ready!(Pin::new(body).poll_read(cx, buf))?,
+                State::Done if this.closed => return Poll::Ready(Ok(0)),
+                State::Done => 0,
+            };
+            if read > 0 || buf.is_empty() {
+                return Poll::Ready(Ok(read));
+            }
+            this.advance();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn encodes_parts() {
+        let file = Part::reader("file", &b"file\r\ncontents"[..], Some(14))
+            .file_name("a \"quoted\".txt")
+            .mime(mime::PLAIN);
+        let body = Multipart::with_boundary("XYZ")
+            .text("title", "hello")
+            .part(file)
+            .into_body();
+
+        assert_eq!(body.mime().to_string(), "multipart/form-data;boundary=XYZ");
+        let len = body.len();
+        let encoded = block_on(body.into_string()).unwrap();
+        assert_eq!(
+            encoded,
+            "--XYZ\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\
+             \r\n\
+             hello\r\n\
+             --XYZ\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"a %22quoted%22.txt\"\r\n\
+             Content-Type: text/plain;charset=utf-8\r\n\
+             \r\n\
+             file\r\ncontents\r\n\
+             --XYZ--\r\n"
+        );
+        assert_eq!(len, Some(encoded.len()));
+    }
+
+    #[test]
+    fn unknown_length() {
+        let reader = Body::from_reader(BufReader::new(&b"abc"[..]), None);
+        let body = Multipart::with_boundary("XYZ")
+            .part(Part::new("stream", reader))
+            .into_body();
+        assert_eq!(body.len(), None);
+        assert!(block_on(body.into_string())
+            .unwrap()
+            .ends_with("abc\r\n--XYZ--\r\n"));
+    }
+
+    #[test]
+    fn empty() {
+        let body = Multipart::with_boundary("XYZ").into_body();
+        assert_eq!(body.len(), Some(9));
+        assert_eq!(block_on(body.into_string()).unwrap(), "--XYZ--\r\n");
+    }
+
+    #[test]
+    fn unique_boundaries() {
+        assert_ne!(Multipart::new().boundary(), Multipart::new().boundary());
+    }
+}
@@ -1,6 +1,7 @@
-use crate::chunked::ChunkedDecoder;
-use async_dup::{Arc, Mutex};
-use futures::io::{AsyncRead as Read, BufReader, Take};
+use crate::chunked::{ChunkedDecoder, ReadUntil};
+use crate::limit::Limited;
+use async_dup::{Arc, Mutex, MutexGuard};
+use futures::io::{AsyncRead as Read, AsyncWrite as Write, BufReader, Take};
 use std::{
     fmt::Debug,
     io,
@@ -8,12 +9,107 @@ use std::{
     task::{Context, Poll},
 };
 
+/// The buffered reader of a connection, kept across its requests so the bytes of pipelined
+/// requests it has buffered are not lost.
+#[derive(Debug)]
+pub struct SharedReader<IO>(Arc<Mutex<BufReader<IO>>>);
+
+impl<IO: Read> SharedReader<IO> {
+    pub(crate) fn new(io: IO) -> Self {
+        Self(Arc::new(Mutex::new(BufReader::new(io))))
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, BufReader<IO>> {
+        self.0.lock()
+    }
+}
+
+impl<IO> Clone for SharedReader<IO> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<IO: Read + Unpin> Read for SharedReader<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<IO: Read + Unpin> ReadUntil for SharedReader<IO> {
+    fn poll_read_until(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        byte: u8,
+        buf: &mut Vec<u8>,
+        limit: usize,
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.lock()).poll_read_until(cx, byte, buf, limit)
+    }
+}
+
+/// The connection handed to an upgrade. Reads start with the bytes the server has already
+/// buffered, like data the client sent right after its upgrade request, and writes go to `io`.
+#[derive(Debug)]
+pub(crate) struct Upgraded<IO> {
+    reader: SharedReader<IO>,
+    io: IO,
+}
+
+impl<IO> Upgraded<IO> {
+    pub(crate) fn new(reader: SharedReader<IO>, io: IO) -> Self {
+        Self { reader, io }
+    }
+}
+
+impl<IO: Read + Unpin> Read for Upgraded<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.reader).poll_read(cx, buf)
+    }
+}
+
+impl<IO: Write + Unpin> Write for Upgraded<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_close(cx)
+    }
+}
+
 pub enum BodyReader<IO: Read + Unpin> {
-    Chunked(Arc<Mutex<ChunkedDecoder<BufReader<IO>>>>),
-    Fixed(Arc<Mutex<Take<BufReader<IO>>>>),
+    Chunked(Arc<Mutex<Limited<ChunkedDecoder<SharedReader<IO>>>>>),
+    Fixed(Arc<Mutex<Take<SharedReader<IO>>>>),
     None,
 }
 
+impl<IO: Read + Unpin> BodyReader<IO> {
+    /// Whether the body has been read past the limit it was decoded with.
+    pub(crate) fn limit_exceeded(&self) -> bool {
+        match self {
+            BodyReader::Chunked(r) => r.lock().exceeded(),
+            BodyReader::Fixed(_) | BodyReader::None => false,
+        }
+    }
+}
+
 impl<IO: Read + Unpin> Debug for BodyReader<IO> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -7,35 +7,82 @@ use futures::io::{AsyncRead as Read, AsyncWrite as Write, BufReader};
 use futures::prelude::*;
 use http_types::content::ContentLength;
 use http_types::headers::{EXPECT, TRANSFER_ENCODING};
-use http_types::{ensure, ensure_eq, format_err};
-use http_types::{Body, Method, Request, Url};
+use http_types::{bail, ensure, format_err};
+use http_types::{Body, Method, Request, Url, Version};
 
-use super::body_reader::BodyReader;
+use super::body_reader::{BodyReader, SharedReader};
 use crate::chunked::ChunkedDecoder;
+use crate::limit::{BodyTooLarge, Limited};
 use crate::read_notifier::ReadNotifier;
 use crate::{MAX_HEADERS, MAX_HEAD_LENGTH};
 
 const LF: u8 = b'\n';
 
+/// The number returned from httparse when the request is HTTP 1.0
+const HTTP_1_0_VERSION: u8 = 0;
 /// The number returned from httparse when the request is HTTP 1.1
 const HTTP_1_1_VERSION: u8 = 1;
 
+/// The host of HTTP 1.0 requests without a `Host` header.
+const DEFAULT_HOST: &str = "localhost";
+
 const CONTINUE_HEADER_VALUE: &str = "100-continue";
 const CONTINUE_RESPONSE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
 
 /// Decode an HTTP request on the server.
-pub async fn decode<IO>(mut io: IO) -> http_types::Result<Option<(Request, BodyReader<IO>)>>
+///
+/// Each call reads the request through a new buffer, which is dropped with the bytes it read past
+/// the request, like those of a pipelined request. To decode several requests from a connection,
+/// use [`Server`](super::Server), which keeps one buffer per connection.
+pub async fn decode<IO>(io: IO) -> http_types::Result<Option<(Request, BodyReader<IO>)>>
+where
+    IO: Read + Write + Clone + Send + Sync + Unpin + 'static,
+{
+    decode_with_limit(io, None).await
+}
+
+/// Decode an HTTP request on the server, with a body of at most `limit` bytes.
+///
+/// A request with a longer `Content-Length` is rejected with a `413 Payload Too Large` error
+/// before its body is read, and a longer chunked body fails with [`BodyTooLarge`] once it has
+/// been read past the limit.
+///
+/// As with [`decode`], bytes read past the request are dropped.
+pub async fn decode_with_limit<IO>(
+    io: IO,
+    limit: Option<u64>,
+) -> http_types::Result<Option<(Request, BodyReader<IO>)>>
+where
+    IO: Read + Write + Clone + Send + Sync + Unpin + 'static,
+{
+    let reader = SharedReader::new(io.clone());
+    let decoded = decode_from(io, reader, limit).await?;
+    if let Some((req, _)) = &decoded {
+        BodyTooLarge::check(req.len().map(|len| len as u64), limit)?;
+    }
+    Ok(decoded)
+}
+
+/// Decode an HTTP request on the connection `io`, reading it from `reader`.
+///
+/// A chunked body is limited to `limit` bytes, but a longer `Content-Length` is left for the
+/// caller to reject, knowing the version of the request.
+pub(crate) async fn decode_from<IO>(
+    mut io: IO,
+    reader: SharedReader<IO>,
+    limit: Option<u64>,
+) -> http_types::Result<Option<(Request, BodyReader<IO>)>>
 where
     IO: Read + Write + Clone + Send + Sync + Unpin + 'static,
 {
-    let mut reader = BufReader::new(io.clone());
     let mut buf = Vec::new();
     let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
     let mut httparse_req = httparse::Request::new(&mut headers);
+    let mut head_reader = reader.lock();
 
     // Keep reading bytes from the stream until we hit the end of the stream.
     loop {
-        let bytes_read = reader.read_until(LF, &mut buf).await?;
+        let bytes_read = head_reader.read_until(LF, &mut buf).await?;
         // No more bytes are yielded from the stream.
         if bytes_read == 0 {
             return Ok(None);
@@ -54,6 +101,8 @@ where
         }
     }
 
+    drop(head_reader);
+
     // Convert our header buf into an httparse instance, and validate.
     let status = httparse_req.parse(&buf)?;
 
@@ -66,18 +115,17 @@ where
     let version = httparse_req.version;
     let version = version.ok_or_else(|| format_err!("No version found"))?;
 
-    ensure_eq!(
-        version,
-        HTTP_1_1_VERSION,
-        "Unsupported HTTP version 1.{}",
-        version
-    );
+    let version = match version {
+        HTTP_1_0_VERSION => Version::Http1_0,
+        HTTP_1_1_VERSION => Version::Http1_1,
+        _ => bail!("Unsupported HTTP version 1.{}", version),
+    };
 
     let url = url_from_httparse_req(&httparse_req)?;
 
     let mut req = Request::new(Method::from_str(method)?, url);
 
-    req.set_version(Some(http_types::Version::Http1_1));
+    req.set_version(Some(version));
 
     for header in httparse_req.headers.iter() {
         req.append_header(header.name, std::str::from_utf8(header.value)?);
@@ -96,13 +144,27 @@ where
         "Unexpected Content-Length header"
     );
 
+    // HTTP/1.0 has no Transfer-Encoding, so a request with one has faulty framing.
+    //
+    // https://www.rfc-editor.org/rfc/rfc9112#section-6.1
+    http_types::ensure_status!(
+        version != Version::Http1_0 || transfer_encoding.is_none(),
+        400,
+        "Unexpected Transfer-Encoding header in an HTTP/1.0 request"
+    );
+
     // Establish a channel to wait for the body to be read. This
     // allows us to avoid sending 100-continue in situations that
     // respond without reading the body, saving clients from uploading
     // their body.
     let (body_read_sender, body_read_receiver) = async_channel::bounded(1);
 
-    if Some(CONTINUE_HEADER_VALUE) == req.header(EXPECT).map(|h| h.as_str()) {
+    // HTTP/1.0 clients do not know 100-continue, so their expectation is ignored.
+    //
+    // https://www.rfc-editor.org/rfc/rfc9110#section-10.1.1
+    if version == Version::Http1_1
+        && Some(CONTINUE_HEADER_VALUE) == req.header(EXPECT).map(|h| h.as_str())
+    {
         async_global_executor::spawn(async move {
             // If the client expects a 100-continue header, spawn a
             // task to wait for the first read attempt on the body.
@@ -122,7 +184,7 @@ where
         .unwrap_or(false)
     {
         let trailer_sender = req.send_trailers();
-        let reader = ChunkedDecoder::new(reader, trailer_sender);
+        let reader = Limited::new(ChunkedDecoder::new(reader, trailer_sender), limit);
         let reader = Arc::new(Mutex::new(reader));
         let reader_clone = reader.clone();
         let reader = ReadNotifier::new(reader, body_read_sender);
@@ -149,10 +211,14 @@ fn url_from_httparse_req(req: &httparse::Request<'_, '_>) -> http_types::Result<
         .headers
         .iter()
         .find(|x| x.name.eq_ignore_ascii_case("host"))
-        .ok_or_else(|| format_err!("Mandatory Host header missing"))?
-        .value;
+        .map(|header| header.value);
 
-    let host = std::str::from_utf8(host)?;
+    // the Host header was introduced by HTTP 1.1
+    let host = match host {
+        Some(host) => std::str::from_utf8(host)?,
+        None if req.version == Some(HTTP_1_0_VERSION) => DEFAULT_HOST,
+        None => bail!("Mandatory Host header missing"),
+    };
 
     if path.starts_with("http://") || path.starts_with("https://") {
         Ok(Url::parse(path)?)
@@ -257,6 +323,17 @@ mod tests {
         )
     }
 
+    #[test]
+    fn url_for_http_1_0_without_host() {
+        httparse_req("GET /health HTTP/1.0\r\n", |req| {
+            let url = url_from_httparse_req(&req).unwrap();
+            assert_eq!(url.as_str(), "http://localhost/health");
+        });
+        httparse_req("GET /health HTTP/1.1\r\n", |req| {
+            assert!(url_from_httparse_req(&req).is_err());
+        });
+    }
+
     #[test]
     fn url_for_query() {
         httparse_req(
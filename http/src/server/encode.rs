@@ -7,7 +7,7 @@ use std::time::SystemTime;
 
 use futures::io::{self, AsyncRead as Read, Cursor};
 use http_types::headers::{CONTENT_LENGTH, DATE, TRANSFER_ENCODING};
-use http_types::{Method, Response};
+use http_types::{Method, Response, Version};
 
 use crate::body_encoder::BodyEncoder;
 use crate::date::fmt_http_date;
@@ -37,6 +37,8 @@ impl Read for Encoder {
 
                     if self.method == Method::Head {
                         EncoderState::End
+                    } else if self.is_http_1_0() {
+                        EncoderState::Body(BodyEncoder::unframed(self.response.take_body()))
                     } else {
                         EncoderState::Body(BodyEncoder::new(self.response.take_body()))
                     }
@@ -63,12 +65,17 @@ impl Encoder {
         }
     }
 
+    /// Whether the response is sent to an HTTP 1.0 client, which cannot decode chunked bodies.
+    fn is_http_1_0(&self) -> bool {
+        self.response.version() == Some(Version::Http1_0)
+    }
+
     fn finalize_headers(&mut self) {
         // If the body isn't streaming, we can set the content-length ahead of time. Else we need to
-        // send all items in chunks.
+        // send all items in chunks, or for HTTP 1.0 end the body by closing the connection.
         if let Some(len) = self.response.len() {
             self.response.insert_header(CONTENT_LENGTH, len.to_string());
-        } else {
+        } else if !self.is_http_1_0() {
             self.response.insert_header(TRANSFER_ENCODING, "chunked");
         }
 
@@ -83,7 +90,12 @@ impl Encoder {
         let mut head = Vec::with_capacity(128);
         let reason = self.response.status().canonical_reason();
         let status = self.response.status();
-        write!(head, "HTTP/1.1 {} {}\r\n", status, reason)?;
+        let version = if self.is_http_1_0() {
+            "HTTP/1.0"
+        } else {
+            "HTTP/1.1"
+        };
+        write!(head, "{} {} {}\r\n", version, status, reason)?;
 
         self.finalize_headers();
         let mut headers = self.response.iter().collect::<Vec<_>>();
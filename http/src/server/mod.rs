@@ -3,13 +3,17 @@
 use futures::io::{self, AsyncRead as Read, AsyncWrite as Write};
 use http_types::headers::{CONNECTION, UPGRADE};
 use http_types::upgrade::Connection;
-use http_types::{Request, Response, StatusCode};
+use http_types::{Method, Request, Response, StatusCode, Version};
 use std::{future::Future, time::Duration};
+
+use crate::limit::BodyTooLarge;
+use body_reader::{SharedReader, Upgraded};
+
 mod body_reader;
 mod decode;
 mod encode;
 
-pub use decode::decode;
+pub use decode::{decode, decode_with_limit};
 pub use encode::Encoder;
 
 /// Configure the server.
@@ -17,20 +21,69 @@ pub use encode::Encoder;
 pub struct ServerOptions {
     /// Timeout to handle headers. Defaults to 60s.
     headers_timeout: Option<Duration>,
+    /// Maximum size of request bodies, in bytes. Defaults to no limit.
+    max_body_size: Option<u64>,
+}
+
+impl ServerOptions {
+    /// Set the timeout to handle headers, or disable it with `None`.
+    pub fn with_headers_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.headers_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum size of request bodies, or remove the limit with `None`.
+    ///
+    /// Requests with a longer body are answered with `413 Payload Too Large` and the connection
+    /// is closed, instead of the response of the handler if it read past the limit.
+    pub fn with_max_body_size(mut self, max_body_size: Option<u64>) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
 }
 
 impl Default for ServerOptions {
     fn default() -> Self {
         Self {
             headers_timeout: Some(Duration::from_secs(60)),
+            max_body_size: None,
         }
     }
 }
 
 /// struct for server
+///
+/// A server handles the requests of one connection, over any stream which can be cloned, like a
+/// TCP stream or a Unix domain socket:
+///
+/// ```no_run
+/// use acril_http::server::{ConnectionStatus, Server};
+/// use async_std::os::unix::net::UnixListener;
+/// use async_std::prelude::*;
+/// use http_types::{Response, StatusCode};
+///
+/// # async_std::task::block_on(async {
+/// let listener = UnixListener::bind("/tmp/app.sock").await?;
+/// let mut incoming = listener.incoming();
+/// while let Some(stream) = incoming.next().await {
+///     let mut server = Server::new(stream?);
+///     while server
+///         .accept_one(|_req| async { Ok::<_, http_types::Error>(Response::new(StatusCode::Ok)) })
+///         .await?
+///         == ConnectionStatus::KeepAlive
+///     {}
+/// }
+/// # Ok::<_, http_types::Error>(())
+/// # });
+/// ```
+///
+/// Requests pipelined by the client, sent before the response to the previous one, are read from
+/// the same buffer and answered in order by the following calls to `accept_one`. Likewise, the
+/// connection handed to an upgrade first reads the bytes buffered after the upgrade request.
 #[derive(Debug)]
 pub struct Server<RW> {
     io: RW,
+    reader: SharedReader<RW>,
     opts: ServerOptions,
 }
 
@@ -51,6 +104,7 @@ where
     /// builds a new server
     pub fn new(io: RW) -> Self {
         Self {
+            reader: SharedReader::new(io.clone()),
             io,
             opts: Default::default(),
         }
@@ -72,44 +126,73 @@ where
         callback: F,
     ) -> Result<ConnectionStatus, Error> {
         // Decode a new request, timing out if this takes longer than the timeout duration.
-        let fut = decode(self.io.clone());
+        let fut = decode::decode_from(
+            self.io.clone(),
+            self.reader.clone(),
+            self.opts.max_body_size,
+        );
 
-        let (req, mut body) = if let Some(timeout_duration) = self.opts.headers_timeout {
+        let decoded = if let Some(timeout_duration) = self.opts.headers_timeout {
             match async_std::future::timeout(timeout_duration, fut).await {
-                Ok(Ok(Some(r))) => r,
-                Err(_) | Ok(Ok(None)) => return Ok(ConnectionStatus::Close), /* EOF or timeout */
-                Ok(Err(e)) => return Err(e.into()),
+                Ok(decoded) => decoded,
+                Err(_) => return Ok(ConnectionStatus::Close), /* timeout */
             }
         } else {
-            match fut.await? {
-                Some(r) => r,
-                None => return Ok(ConnectionStatus::Close), /* EOF */
-            }
+            fut.await
+        };
+        let (req, mut body) = match decoded {
+            Ok(Some(r)) => r,
+            Ok(None) => return Ok(ConnectionStatus::Close), /* EOF */
+            Err(e) => return Err(e.into()),
         };
 
+        let version = req.version().unwrap_or(Version::Http1_1);
+        let length = req.len().map(|len| len as u64);
+        if BodyTooLarge::check(length, self.opts.max_body_size).is_err() {
+            return self.reject_too_large(req.method(), version).await;
+        }
+
         let has_upgrade_header = req.header(UPGRADE).is_some();
         let connection_header_as_str = req
             .header(CONNECTION)
             .map(|connection| connection.as_str())
             .unwrap_or("");
 
-        let connection_header_is_upgrade = connection_header_as_str
-            .split(',')
-            .any(|s| s.trim().eq_ignore_ascii_case("upgrade"));
-        let mut close_connection = connection_header_as_str.eq_ignore_ascii_case("close");
+        let connection_header_is_upgrade = has_token(connection_header_as_str, "upgrade");
+
+        // HTTP 1.0 connections are closed after each response unless the client asks otherwise,
+        // and HTTP 1.1 connections are kept alive unless the client asks otherwise.
+        let mut close_connection = if version == Version::Http1_0 {
+            !has_token(connection_header_as_str, "keep-alive")
+        } else {
+            has_token(connection_header_as_str, "close")
+        };
 
         let upgrade_requested = has_upgrade_header && connection_header_is_upgrade;
 
         let method = req.method();
 
         // Pass the request to the endpoint and encode the response.
-        let mut res = (callback)(req).await?;
+        let res = (callback)(req).await;
+        if body.limit_exceeded() {
+            return self.reject_too_large(method, version).await;
+        }
+        let mut res = res?;
+        res.set_version(Some(version));
 
         close_connection |= res
             .header(CONNECTION)
-            .map(|c| c.as_str().eq_ignore_ascii_case("close"))
+            .map(|c| has_token(c.as_str(), "close"))
             .unwrap_or(false);
 
+        if version == Version::Http1_0 {
+            // without chunked encoding, a body of unknown length ends when the connection closes
+            close_connection |= res.len().is_none() && method != Method::Head;
+            if !close_connection {
+                res.insert_header(CONNECTION, "keep-alive");
+            }
+        }
+
         let upgrade_provided = res.status() == StatusCode::SwitchingProtocols && res.has_upgrade();
 
         let upgrade_sender = if upgrade_requested && upgrade_provided {
@@ -123,14 +206,19 @@ where
         let bytes_written = io::copy(&mut encoder, &mut self.io).await?;
         log::trace!("wrote {} response bytes", bytes_written);
 
-        let body_bytes_discarded = io::copy(&mut body, &mut io::sink()).await?;
+        let body_bytes_discarded = match io::copy(&mut body, &mut io::sink()).await {
+            // the rest of the body cannot be skipped, so the next request cannot be found
+            Err(_) if body.limit_exceeded() => return Ok(ConnectionStatus::Close),
+            discarded => discarded?,
+        };
         log::trace!(
             "discarded {} unread request body bytes",
             body_bytes_discarded
         );
 
         if let Some(upgrade_sender) = upgrade_sender {
-            upgrade_sender.send(Connection::new(self.io.clone())).await;
+            let upgraded = Upgraded::new(self.reader.clone(), self.io.clone());
+            upgrade_sender.send(Connection::new(upgraded)).await;
             Ok(ConnectionStatus::Close)
         } else if close_connection {
             Ok(ConnectionStatus::Close)
@@ -138,4 +226,322 @@ where
             Ok(ConnectionStatus::KeepAlive)
         }
     }
+
+    /// Respond `413 Payload Too Large` to a request with a body longer than the limit, and close
+    /// the connection.
+    async fn reject_too_large<Error: From<std::io::Error>>(
+        &mut self,
+        method: Method,
+        version: Version,
+    ) -> Result<ConnectionStatus, Error> {
+        let mut res = Response::new(StatusCode::PayloadTooLarge);
+        res.set_version(Some(version));
+        res.insert_header(CONNECTION, "close");
+        io::copy(&mut Encoder::new(res, method), &mut self.io).await?;
+        Ok(ConnectionStatus::Close)
+    }
+}
+
+/// Whether the comma-separated list `header` contains `token`.
+fn has_token(header: &str, token: &str) -> bool {
+    header
+        .split(',')
+        .any(|s| s.trim().eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+
+    use futures::executor::block_on;
+    use futures::io::{AsyncReadExt, Cursor};
+
+    use super::*;
+
+    /// A connection reading `input` and recording what is written to it.
+    #[derive(Clone)]
+    struct TestIo {
+        input: Arc<Mutex<Cursor<Vec<u8>>>>,
+        output: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl TestIo {
+        fn new(input: &str) -> Self {
+            Self {
+                input: Arc::new(Mutex::new(Cursor::new(input.as_bytes().to_vec()))),
+                output: Default::default(),
+            }
+        }
+
+        fn output(&self) -> String {
+            String::from_utf8(self.output.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Read for TestIo {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut *self.input.lock().unwrap()).poll_read(cx, buf)
+        }
+    }
+
+    impl Write for TestIo {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.output.lock().unwrap().extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn accept_limited(io: &TestIo) -> ConnectionStatus {
+        let opts = ServerOptions::default().with_max_body_size(Some(4));
+        let mut server = Server::new(io.clone()).with_opts(opts);
+        block_on(server.accept_one(|mut req: Request| async move {
+            let mut body = String::new();
+            req.read_to_string(&mut body).await?;
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(body);
+            Ok::<_, http_types::Error>(res)
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_limit() {
+        let io = TestIo::new("POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 4\r\n\r\nabcd");
+        assert_eq!(accept_limited(&io), ConnectionStatus::KeepAlive);
+        assert!(io.output().starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(io.output().ends_with("\r\n\r\nabcd"));
+
+        let io = TestIo::new("POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\n\r\nabcde");
+        assert_eq!(accept_limited(&io), ConnectionStatus::Close);
+        assert!(io
+            .output()
+            .starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+        let io = TestIo::new(
+            "POST / HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n",
+        );
+        assert_eq!(accept_limited(&io), ConnectionStatus::Close);
+        assert!(io
+            .output()
+            .starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    }
+
+    #[test]
+    fn applies_http_1_0_framing_rules() {
+        // the 413 has the version of the request
+        let io = TestIo::new("POST / HTTP/1.0\r\nContent-Length: 5\r\n\r\nabcde");
+        assert_eq!(accept_limited(&io), ConnectionStatus::Close);
+        assert!(io
+            .output()
+            .starts_with("HTTP/1.0 413 Payload Too Large\r\n"));
+
+        // 100-continue is ignored
+        let io =
+            TestIo::new("POST / HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 2\r\n\r\nab");
+        assert_eq!(accept_limited(&io), ConnectionStatus::Close);
+        assert!(io.output().starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(!io.output().contains("100 Continue"));
+
+        // chunked bodies are faulty framing
+        let io = TestIo::new(
+            "POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n0\r\n\r\n",
+        );
+        let mut server = Server::new(io.clone());
+        let error = block_on(server.accept_one(|_req| async {
+            Ok::<_, http_types::Error>(Response::new(StatusCode::Ok))
+        }))
+        .unwrap_err();
+        assert_eq!(error.status(), StatusCode::BadRequest);
+        assert_eq!(io.output(), "");
+    }
+
+    /// Accept a request on `io`, responding with `body`, streamed if `streamed`.
+    fn accept_with_body(io: &TestIo, body: &'static str, streamed: bool) -> ConnectionStatus {
+        let mut server = Server::new(io.clone());
+        block_on(server.accept_one(|_req| async move {
+            let mut res = Response::new(StatusCode::Ok);
+            if streamed {
+                let reader = futures::io::BufReader::new(body.as_bytes());
+                res.set_body(http_types::Body::from_reader(reader, None));
+            } else {
+                res.set_body(body);
+            }
+            Ok::<_, http_types::Error>(res)
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn upgrades_keep_buffered_bytes() {
+        let io = TestIo::new(
+            "GET / HTTP/1.1\r\nHost: test\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\nsent early",
+        );
+        let receiver = Arc::new(Mutex::new(None));
+        let mut server = Server::new(io.clone());
+
+        let status = block_on(server.accept_one(|_req| {
+            let receiver = receiver.clone();
+            async move {
+                let mut res = Response::new(StatusCode::SwitchingProtocols);
+                res.insert_header(UPGRADE, "echo");
+                res.insert_header(CONNECTION, "Upgrade");
+                *receiver.lock().unwrap() = Some(res.recv_upgrade().await);
+                Ok::<_, http_types::Error>(res)
+            }
+        }))
+        .unwrap();
+        assert_eq!(status, ConnectionStatus::Close);
+        assert!(io
+            .output()
+            .starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+
+        let receiver = receiver.lock().unwrap().take().unwrap();
+        let mut connection = block_on(receiver).unwrap();
+        let mut early = String::new();
+        block_on(connection.read_to_string(&mut early)).unwrap();
+        assert_eq!(early, "sent early");
+
+        block_on(futures::io::AsyncWriteExt::write_all(
+            &mut connection,
+            b"echo",
+        ))
+        .unwrap();
+        assert!(io.output().ends_with("\r\n\r\necho"));
+    }
+
+    #[test]
+    fn closes_http_1_0_connections_by_default() {
+        let io = TestIo::new("GET / HTTP/1.0\r\n\r\n");
+        assert_eq!(
+            accept_with_body(&io, "hello", false),
+            ConnectionStatus::Close
+        );
+        let output = io.output();
+        assert!(output.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(output.contains("content-length: 5\r\n"));
+        assert!(!output.contains("connection:"));
+
+        let io = TestIo::new("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n");
+        assert_eq!(
+            accept_with_body(&io, "hello", false),
+            ConnectionStatus::KeepAlive
+        );
+        assert!(io.output().contains("connection: keep-alive\r\n"));
+    }
+
+    #[test]
+    fn never_chunks_http_1_0_responses() {
+        let io = TestIo::new("GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n");
+        assert_eq!(
+            accept_with_body(&io, "hello", true),
+            ConnectionStatus::Close
+        );
+        let output = io.output();
+        assert!(output.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(!output.contains("transfer-encoding"));
+        assert!(!output.contains("connection:"));
+        assert!(output.ends_with("\r\n\r\nhello"));
+
+        let io = TestIo::new("GET / HTTP/1.1\r\nHost: test\r\n\r\n");
+        assert_eq!(
+            accept_with_body(&io, "hello", true),
+            ConnectionStatus::KeepAlive
+        );
+        assert!(io.output().starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(io.output().contains("transfer-encoding: chunked\r\n"));
+    }
+
+    #[test]
+    fn answers_pipelined_requests_in_order() {
+        let io = TestIo::new(concat!(
+            "POST /first HTTP/1.1\r\nHost: test\r\nContent-Length: 3\r\n\r\nabc",
+            "POST /second HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "2\r\nde\r\n0\r\nX-Checksum: 1\r\n\r\n",
+            "GET /third HTTP/1.1\r\nHost: test\r\n\r\n",
+        ));
+        let mut server = Server::new(io.clone());
+        let mut accept = || {
+            block_on(server.accept_one(|mut req: Request| async move {
+                let body = req.body_string().await?;
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body(format!("{} {}", req.url().path(), body));
+                Ok::<_, http_types::Error>(res)
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(accept(), ConnectionStatus::KeepAlive);
+        assert_eq!(accept(), ConnectionStatus::KeepAlive);
+        assert_eq!(accept(), ConnectionStatus::KeepAlive);
+        assert_eq!(accept(), ConnectionStatus::Close);
+
+        let output = io.output();
+        let bodies: Vec<_> = output
+            .split("HTTP/1.1 200 OK\r\n")
+            .skip(1)
+            .map(|res| res.split("\r\n\r\n").nth(1).unwrap())
+            .collect();
+        assert_eq!(bodies, ["/first abc", "/second de", "/third "]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serves_over_unix_sockets() {
+        use async_std::os::unix::net::UnixListener;
+        use http_types::Method;
+
+        let socket = std::env::temp_dir().join(format!("acril-http-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+
+        async_std::task::block_on(async {
+            let listener = UnixListener::bind(&socket).await.unwrap();
+            let server = async_std::task::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut server = Server::new(stream);
+                let mut requests = 0;
+                while server
+                    .accept_one(|req: Request| async move {
+                        let mut res = Response::new(StatusCode::Ok);
+                        res.set_body(req.url().path().to_owned());
+                        Ok::<_, http_types::Error>(res)
+                    })
+                    .await
+                    .unwrap()
+                    == ConnectionStatus::KeepAlive
+                {
+                    requests += 1;
+                }
+                requests
+            });
+
+            let url = crate::client::unix_url(&socket, "/containers/json").unwrap();
+            let mut res = crate::client::connect(Request::new(Method::Get, url))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::Ok);
+            assert_eq!(res.body_string().await.unwrap(), "/containers/json");
+            drop(res);
+
+            assert_eq!(server.await, 1);
+        });
+        std::fs::remove_file(&socket).unwrap();
+    }
 }
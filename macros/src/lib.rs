@@ -1,9 +1,12 @@
+mod openapi;
+
 use proc_macro::TokenStream as TS;
-use proc_macro2::{Ident, Literal, Span, TokenStream};
+use proc_macro2::{Ident, Span, TokenStream};
 use quote::ToTokens;
 use syn::{
-    parenthesized, parse2, parse_quote, spanned::Spanned, token, Data, DeriveInput, Expr, ExprLit,
-    Field, Fields, Item, ItemStruct, Lit, Meta, MetaList, MetaNameValue, Result, Token, Type,
+    parenthesized, parse2, parse_quote, punctuated::Punctuated, spanned::Spanned, token, Attribute,
+    Data, DataEnum, DeriveInput, Expr, ExprLit, Field, Fields, GenericParam, Generics, Item, Lit,
+    LitStr, Member, Meta, MetaList, MetaNameValue, Result, Token, Type,
 };
 
 #[proc_macro]
@@ -23,20 +26,56 @@ fn _endpoint_error(args: TokenStream) -> Result<TokenStream> {
     })
 }
 
-#[proc_macro_derive(ClientEndpoint, attributes(endpoint, required))]
+/// Implement `ClientEndpoint` for a struct or enum described by `#[endpoint(...)]`.
+///
+/// By default, the whole endpoint is sent the way its input mode says. Fields can instead be
+/// placed one by one:
+///
+/// - `#[path_param]`: only used in the path, like `id` in `"/pets/{id}"`, percent-encoded so that
+///   its value is a single segment.
+/// - `#[query]`: in the query string.
+/// - `#[header("Name")]`: as the value of a header.
+/// - `#[body]`: in the body, or as the whole body with `#[body(whole)]`.
+///
+/// Path parameters use `#[path_param]` rather than `#[path]`, because a derive helper named
+/// `path` is ambiguous with the built-in `#[path = "..."]` attribute of modules, and cannot be
+/// used on fields.
+#[proc_macro_derive(
+    ClientEndpoint,
+    attributes(endpoint, required, file, serde, path_param, query, header, body)
+)]
 pub fn endpoint(item: TS) -> TS {
     _endpoint(item.into())
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
 
-#[derive(Default, Clone, Copy)]
+/// Generate endpoints, their models and a `#[with_builder]` facade on a client from an OpenAPI 3
+/// document.
+#[proc_macro]
+pub fn openapi(args: TS) -> TS {
+    openapi::openapi(args.into())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+#[derive(Default, Clone)]
 enum MetaMode {
     Json,
     Query,
     Display,
     #[default]
     Empty,
+    Form,
+    Multipart,
+    Bytes,
+    Body,
+    NdJson,
+    Sse,
+    /// `with = path::to::fn`: encoded by `async fn(&Endpoint, &mut Request) -> Result<(), E>`, or
+    /// decoded by `async fn(&mut Response) -> Result<Output, E>`, where `E` converts to the error
+    /// of the endpoint.
+    With(syn::Path),
 }
 
 mod kw {
@@ -44,6 +83,14 @@ mod kw {
     syn::custom_keyword!(query);
     syn::custom_keyword!(display);
     syn::custom_keyword!(empty);
+    syn::custom_keyword!(form);
+    syn::custom_keyword!(multipart);
+    syn::custom_keyword!(bytes);
+    syn::custom_keyword!(body);
+    syn::custom_keyword!(ndjson);
+    syn::custom_keyword!(sse);
+    syn::custom_keyword!(with);
+    syn::custom_keyword!(whole);
 }
 
 impl syn::parse::Parse for MetaMode {
@@ -62,6 +109,28 @@ impl syn::parse::Parse for MetaMode {
         } else if lo.peek(kw::empty) {
             input.parse::<kw::empty>()?;
             Self::Empty
+        } else if lo.peek(kw::form) {
+            input.parse::<kw::form>()?;
+            Self::Form
+        } else if lo.peek(kw::multipart) {
+            input.parse::<kw::multipart>()?;
+            Self::Multipart
+        } else if lo.peek(kw::bytes) {
+            input.parse::<kw::bytes>()?;
+            Self::Bytes
+        } else if lo.peek(kw::body) {
+            input.parse::<kw::body>()?;
+            Self::Body
+        } else if lo.peek(kw::ndjson) {
+            input.parse::<kw::ndjson>()?;
+            Self::NdJson
+        } else if lo.peek(kw::sse) {
+            input.parse::<kw::sse>()?;
+            Self::Sse
+        } else if lo.peek(kw::with) {
+            input.parse::<kw::with>()?;
+            input.parse::<Token![=]>()?;
+            Self::With(input.parse()?)
         } else {
             return Err(lo.error());
         };
@@ -70,26 +139,355 @@ impl syn::parse::Parse for MetaMode {
     }
 }
 
+impl MetaMode {
+    fn is_output_only(&self) -> bool {
+        matches!(self, Self::Bytes | Self::Body | Self::NdJson | Self::Sse)
+    }
+
+    fn is_input_only(&self) -> bool {
+        matches!(self, Self::Multipart)
+    }
+
+    /// Code setting up `request` from `value`, a place expression of a serializable value with
+    /// `fields`.
+    fn encode(&self, fields: &Fields, value: &TokenStream) -> Result<TokenStream> {
+        Ok(match self {
+            MetaMode::Json => {
+                quote::quote! { request.set_body(http_types::Body::from_json(&#value)?); }
+            }
+            MetaMode::Query => quote::quote! {
+                acril::serde::Serialize::serialize(
+                    &#value,
+                    acril::serde_urlencoded::Serializer::new(&mut request.url_mut().query_pairs_mut()),
+                )?;
+
+                if let Some("") = request.url().query() {
+                    request.url_mut().set_query(None);
+                }
+            },
+            MetaMode::Display => quote::quote! {
+                request.set_body(self.to_string());
+            },
+            MetaMode::Empty => quote::quote!(),
+            MetaMode::Form => quote::quote! {
+                request.set_body(http_types::Body::from_form(&#value)?);
+            },
+            MetaMode::Multipart => {
+                let Fields::Named(named) = fields else {
+                    return Err(syn::Error::new_spanned(
+                        fields,
+                        "multipart endpoints must have named fields",
+                    ));
+                };
+                let parts = named.named.iter().filter_map(|field| {
+                    let ident = field.ident.as_ref()?;
+                    let (name, skipped) = field_name(field);
+                    let file = field.attrs.iter().any(|a| a.path().is_ident("file"));
+                    // file fields are usually skipped by serde, which cannot serialize them
+                    if skipped && !file {
+                        return None;
+                    }
+
+                    Some(if file {
+                        quote::quote! {
+                            if let Some(part) = acril::http::multipart::FilePart::to_part(&#value.#ident, #name).await? {
+                                multipart.push(part);
+                            }
+                        }
+                    } else {
+                        quote::quote! {
+                            acril::http::multipart::append_field(&mut multipart, #name, &#value.#ident)?;
+                        }
+                    })
+                });
+
+                quote::quote! {
+                    let mut multipart = acril::http::multipart::Multipart::new();
+                    #(#parts)*
+                    request.set_body(multipart.into_body());
+                }
+            }
+            MetaMode::With(encode) => quote::quote! {
+                #encode(self, &mut request).await?;
+            },
+            MetaMode::Bytes | MetaMode::Body | MetaMode::NdJson | MetaMode::Sse => {
+                unreachable!("output-only modes are rejected while parsing")
+            }
+        })
+    }
+
+    /// An expression producing the output from `response`.
+    fn decode(&self) -> TokenStream {
+        match self {
+            MetaMode::Json => quote::quote!(response.body_json().await?),
+            MetaMode::Display => quote::quote!(response.body_string().await?),
+            MetaMode::Query => quote::quote! {
+                acril::serde_urlencoded::from_str(&response.body_string().await?).map_err(|e| {
+                    http_types::Error::new(http_types::StatusCode::UnprocessableEntity, e)
+                })?
+            },
+            MetaMode::Empty => quote::quote!(()),
+            MetaMode::Form => quote::quote!(response.body_form().await?),
+            MetaMode::Multipart => unreachable!("input-only modes are rejected while parsing"),
+            MetaMode::Bytes => quote::quote!(response.body_bytes().await?),
+            MetaMode::Body => quote::quote!(response.take_body()),
+            MetaMode::NdJson => {
+                quote::quote!(acril::http::stream::NdJson::new(response.take_body()))
+            }
+            MetaMode::Sse => {
+                quote::quote!(acril::http::stream::EventStream::new(response.take_body()))
+            }
+            MetaMode::With(decode) => quote::quote!(#decode(&mut response).await?),
+        }
+    }
+}
+
 struct EndpointMeta {
     pub method: Ident,
     pub mode: (MetaMode, MetaMode),
     pub path: Expr,
     pub client: Type,
     pub output: Type,
+    pub api_error: Option<Type>,
+    /// The error of the endpoint, instead of `__Endpoint_Error` or `EndpointError`.
+    pub error: Option<Type>,
+    pub paginate: Option<Paginate>,
+    pub with_meta: bool,
+    /// The fields of the output set from headers of the response.
+    pub headers: Vec<(Ident, LitStr)>,
+    /// The maximum size of the body of the response, in bytes.
+    pub limit: Option<Expr>,
 }
 
-impl syn::parse::Parse for EndpointMeta {
+/// How the next page of a paginated endpoint is requested.
+enum PageStrategy {
+    /// Set the field to the token at the path in the output.
+    Cursor {
+        field: Ident,
+        next: Punctuated<Member, Token![.]>,
+    },
+    /// Increment the field by one.
+    Page(Ident),
+    /// Increment the field by the number of items received.
+    Offset(Ident),
+    /// Follow the `next` link in the `Link` header.
+    Link,
+}
+
+/// The `paginate(...)` option of an endpoint.
+struct Paginate {
+    strategy: PageStrategy,
+    item: Type,
+    items: Punctuated<Member, Token![.]>,
+    /// The field holding the number of items requested per page, for `page` and `offset`.
+    size: Option<Ident>,
+}
+
+impl syn::parse::Parse for Paginate {
     fn parse(input: syn::parse::ParseStream) -> Result<Self> {
+        let span = input.span();
+        let (mut cursor, mut next, mut page, mut offset, mut link) =
+            (None, None, None, None, false);
+        let (mut item, mut items, mut size) = (None, Punctuated::new(), None);
+
+        while !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            match key.to_string().as_str() {
+                "link" => link = true,
+                "cursor" | "page" | "offset" | "next" | "item" | "items" | "size" => {
+                    input.parse::<Token![=]>()?;
+                    match key.to_string().as_str() {
+                        "cursor" => cursor = Some(input.parse()?),
+                        "page" => page = Some(input.parse()?),
+                        "offset" => offset = Some(input.parse()?),
+                        "next" => next = Some(Punctuated::parse_separated_nonempty(input)?),
+                        "item" => item = Some(input.parse()?),
+                        "size" => size = Some(input.parse()?),
+                        _ => items = Punctuated::parse_separated_nonempty(input)?,
+                    }
+                }
+                _ => return Err(syn::Error::new_spanned(key, "unknown pagination option")),
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        let strategy = match (cursor, next, page, offset, link) {
+            (Some(field), Some(next), None, None, false) => PageStrategy::Cursor { field, next },
+            (Some(_), None, None, None, false) => {
+                return Err(syn::Error::new(
+                    span,
+                    "cursor pagination needs the path of the next cursor in the output: `next = ...`",
+                ))
+            }
+            (None, None, Some(field), None, false) => PageStrategy::Page(field),
+            (None, None, None, Some(field), false) => PageStrategy::Offset(field),
+            (None, None, None, None, true) => PageStrategy::Link,
+            _ => {
+                return Err(syn::Error::new(
+                    span,
+                    "expected exactly one of `cursor = field, next = path`, `page = field`, `offset = field` or `link`",
+                ))
+            }
+        };
+
+        if size.is_some() && !matches!(strategy, PageStrategy::Page(_) | PageStrategy::Offset(_)) {
+            return Err(syn::Error::new(
+                span,
+                "`size = field` is only used by `page` and `offset` pagination",
+            ));
+        }
+
         Ok(Self {
+            strategy,
+            size,
+            item: item
+                .ok_or_else(|| syn::Error::new(span, "missing the item type: `item = Type`"))?,
+            items,
+        })
+    }
+}
+
+impl EndpointMeta {
+    /// Parse the `, key = value` and `, paginate(...)` options following the output type.
+    fn parse_options(&mut self, input: syn::parse::ParseStream) -> Result<()> {
+        while input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+
+            let key = input.parse::<Ident>()?;
+            if key == "paginate" {
+                let args;
+                parenthesized!(args in input);
+                self.paginate = Some(args.parse()?);
+                continue;
+            }
+            if key == "headers" {
+                let args;
+                parenthesized!(args in input);
+                while !args.is_empty() {
+                    let field = args.parse()?;
+                    args.parse::<Token![=]>()?;
+                    self.headers.push((field, args.parse()?));
+                    if !args.is_empty() {
+                        args.parse::<Token![,]>()?;
+                    }
+                }
+                continue;
+            }
+            if key == "with_meta" {
+                self.with_meta = true;
+                continue;
+            }
+
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "api_error" => self.api_error = Some(input.parse()?),
+                "error" => self.error = Some(input.parse()?),
+                "limit" => self.limit = Some(input.parse()?),
+                _ => return Err(syn::Error::new_spanned(key, "unknown endpoint option")),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Code limiting the body of `response` to the `limit = N` option, if it is set.
+    fn limit_body(&self) -> TokenStream {
+        match &self.limit {
+            Some(limit) => quote::quote!(acril::http::limit::limit_body(&mut response, #limit);),
+            None => quote::quote!(),
+        }
+    }
+
+    /// The `Error` type of the endpoint.
+    fn error(&self) -> TokenStream {
+        if let Some(error) = &self.error {
+            return error.to_token_stream();
+        }
+
+        let client = &self.client;
+        match &self.api_error {
+            Some(api_error) => quote::quote! {
+                acril::http::client::EndpointError<
+                    #api_error,
+                    <#client as acril::http::client::HttpClientContext>::Error,
+                >
+            },
+            None => quote::quote!(__Endpoint_Error),
+        }
+    }
+
+    /// The `Output` type of the endpoint.
+    fn output_type(&self) -> TokenStream {
+        let output = &self.output;
+        if self.with_meta {
+            quote::quote!(acril::http::client::WithMeta<#output>)
+        } else {
+            output.to_token_stream()
+        }
+    }
+
+    /// An expression producing the output from `response`, with the fields set from headers.
+    fn decode(&self) -> TokenStream {
+        let mut decode = self.mode.1.decode();
+        if !self.headers.is_empty() {
+            let fields = self.headers.iter().map(|(field, name)| {
+                quote::quote! {
+                    output.#field = acril::http::client::header_field(&response, #name)?;
+                }
+            });
+            let output = &self.output;
+            decode = quote::quote! {{
+                let mut output: #output = #decode;
+                #(#fields)*
+                output
+            }};
+        }
+        if self.with_meta {
+            decode = quote::quote! {
+                acril::http::client::WithMeta {
+                    status: response.status(),
+                    headers: AsRef::<http_types::headers::Headers>::as_ref(&response).clone(),
+                    output: #decode,
+                }
+            };
+        }
+        decode
+    }
+}
+
+impl syn::parse::Parse for EndpointMeta {
+    fn parse(input: syn::parse::ParseStream) -> Result<Self> {
+        let mut meta = Self {
             method: input.parse()?,
             mode: if input.peek(token::Paren) {
                 let real;
                 parenthesized!(real in input);
+                let span = real.span();
                 let inp = real.parse::<MetaMode>()?;
+                if inp.is_output_only() {
+                    return Err(syn::Error::new(
+                        span,
+                        "this mode can only be used for the output of an endpoint",
+                    ));
+                }
                 let out = if real.peek(Token![,]) {
                     real.parse::<Token![,]>()?;
 
-                    real.parse::<MetaMode>()?
+                    let span = real.span();
+                    let out = real.parse::<MetaMode>()?;
+                    if out.is_input_only() {
+                        return Err(syn::Error::new(
+                            span,
+                            "this mode can only be used for the input of an endpoint",
+                        ));
+                    }
+                    out
                 } else {
                     if let MetaMode::Display = inp {
                         MetaMode::Display
@@ -113,27 +511,121 @@ impl syn::parse::Parse for EndpointMeta {
             } else {
                 parse_quote!(())
             },
-        })
+            api_error: None,
+            error: None,
+            paginate: None,
+            with_meta: false,
+            headers: Vec::new(),
+            limit: None,
+        };
+        meta.parse_options(input)?;
+
+        Ok(meta)
     }
 }
 
-fn _endpoint(item: TokenStream) -> Result<TokenStream> {
-    let input: DeriveInput = parse2(item)?;
+/// The tokens of the `#[endpoint(...)]` attribute in `attrs`.
+fn endpoint_attr(attrs: &[Attribute]) -> Option<TokenStream> {
+    attrs.iter().find_map(|x| match &x.meta {
+        Meta::List(MetaList { path, tokens, .. }) if path.is_ident("endpoint") => {
+            Some(tokens.clone())
+        }
+        _ => None,
+    })
+}
 
-    let meta = parse2::<EndpointMeta>(
-        input
-            .attrs
-            .iter()
-            .find_map(|x| match &x.meta {
-                Meta::List(MetaList { path, tokens, .. }) if path.is_ident("endpoint") => {
-                    Some(tokens.clone())
-                }
-                _ => None,
+/// An expression evaluating to the URL of the endpoint, with the fields in scope. The
+/// `#[path_param]` fields among `fields` are percent-encoded as single path segments.
+fn url_code(path: &Expr, fields: &Fields, bindings: &[Ident]) -> TokenStream {
+    let params = fields
+        .iter()
+        .zip(bindings)
+        .filter(|(field, _)| matches!(Placement::of(field), Ok(Some(Placement::Path))))
+        .map(|(_, binding)| binding)
+        .collect::<Vec<_>>();
+
+    match path {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(path),
+            ..
+        }) => {
+            let (path, segments) = encode_path(path, &params);
+            quote::quote!({
+                #segments
+                format!(#path)
+            })
+        }
+        // the parameters are formatted by the expression, so they can only be encoded with `{}`
+        _ => {
+            let path = match path {
+                Expr::Paren(_) => path.to_token_stream(),
+                _ => quote::quote!(format!(#path)),
+            };
+            quote::quote!({
+                #(let #params = acril::http::client::PathSegment(#params);)*
+                #path
             })
-            .unwrap(),
-    )?;
+        }
+    }
+}
+
+/// Rewrite the placeholders of the `params` in the format string `path` to their percent-encoded
+/// values: `{id:04}` becomes `{__segment0}`, bound to `id` formatted with `{:04}` and encoded.
+/// Returns the new format string and the bindings of the segments.
+fn encode_path(path: &LitStr, params: &[&Ident]) -> (LitStr, TokenStream) {
+    let value = path.value();
+    let (mut rest, mut out, mut segments) = (value.as_str(), String::new(), Vec::new());
 
-    let ((setup, desetup), url) = match input.data {
+    while let Some(idx) = rest.find(['{', '}']) {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        // escaped braces, and unbalanced ones `format!` reports
+        let end = match rest.find('}') {
+            _ if rest.starts_with("{{") || rest.starts_with("}}") => 1,
+            Some(end) if rest.starts_with('{') => end,
+            _ => 0,
+        };
+        let placeholder = &rest[..=end];
+        rest = &rest[end + 1..];
+
+        let inner = placeholder.trim_start_matches('{').trim_end_matches('}');
+        let (name, spec) = inner.split_once(':').unwrap_or((inner, ""));
+        match params.iter().find(|param| **param == name.trim()) {
+            Some(param) if end > 1 => {
+                let segment = Ident::new(&format!("__segment{}", segments.len()), path.span());
+                let format = LitStr::new(&format!("{{:{spec}}}"), path.span());
+                segments.push(quote::quote! {
+                    let #segment = acril::http::client::PathSegment(format!(#format, #param));
+                });
+                out.push_str(&format!("{{{segment}}}"));
+            }
+            _ => out.push_str(placeholder),
+        }
+    }
+    out.push_str(rest);
+
+    (LitStr::new(&out, path.span()), quote::quote!(#(#segments)*))
+}
+
+/// The names the fields are bound to in patterns: their identifiers, or `_0`, `_1`, ... for tuple
+/// fields.
+fn field_bindings(fields: &Fields) -> Vec<Ident> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            field
+                .ident
+                .clone()
+                .unwrap_or_else(|| Ident::new(&format!("_{idx}"), Span::call_site()))
+        })
+        .collect()
+}
+
+fn _endpoint(item: TokenStream) -> Result<TokenStream> {
+    let input: DeriveInput = parse2(item)?;
+
+    let s = match &input.data {
         // wontfix
         Data::Union(_) => {
             return Err(syn::Error::new(
@@ -141,332 +633,1227 @@ fn _endpoint(item: TokenStream) -> Result<TokenStream> {
                 "unions are not supported as endpoints",
             ))
         }
-        // todo
-        Data::Enum(_) => {
-            return Err(syn::Error::new_spanned(
-                input,
-                "enums are currently not supported as endpoints",
-            ))
-        }
-        Data::Struct(s) => match s.fields {
-            Fields::Unit => (
-                (
-                    quote::quote!(),
-                    match meta.mode.1 {
-                        MetaMode::Json => quote::quote!(response.body_json().await?),
-                        MetaMode::Display => quote::quote!(response.body_string().await?),
-                        MetaMode::Query => todo!(),
-                        MetaMode::Empty => quote::quote!(()),
-                    },
-                ),
-                meta.path.to_token_stream(),
-            ),
-            Fields::Named(named) => (
-                (
-                    match meta.mode.0 {
-                        MetaMode::Json => {
-                            quote::quote! { request.set_body(http_types::Body::from_json(self)?); }
-                        }
-                        MetaMode::Query => quote::quote! {
-                            self.serialize(acril::serde_urlencoded::Serializer::new(&mut request.url_mut().query_pairs_mut()))?;
-
-                            if let Some("") = request.url().query() {
-                                request.url_mut().set_query(None);
-                            }
-                        },
-                        MetaMode::Display => quote::quote! {
-                            request.set_body(self.to_string());
-                        },
-                        MetaMode::Empty => quote::quote!(),
-                    },
-                    match meta.mode.1 {
-                        MetaMode::Json => quote::quote!(response.body_json().await?),
-                        MetaMode::Display => quote::quote!(response.body_string().await?),
-                        MetaMode::Query => todo!(),
-                        MetaMode::Empty => quote::quote!(()),
-                    },
-                ),
-                {
-                    let fields = named.named.into_iter().filter_map(|x| x.ident);
-                    let murl = if !matches!(&meta.path, Expr::Paren(_)) {
-                        let m = meta.path;
-                        quote::quote!(format!(#m))
-                    } else {
-                        meta.path.into_token_stream()
-                    };
+        Data::Enum(e) => return endpoint_enum(&input, e),
+        Data::Struct(s) => s,
+    };
 
-                    quote::quote! {{
-                        let Self { #(#fields,)* } = self;
-                        #murl
-                    }}
-                },
-            ),
-            Fields::Unnamed(tup) => (
-                (
-                    match meta.mode.0 {
-                        MetaMode::Json => {
-                            quote::quote! { request.set_body(http_types::Body::from_json(self)?) }
-                        }
-                        MetaMode::Query => quote::quote! {
-                            self.serialize(acril::serde_urlencoded::Serializer::new(&mut request.url_mut().query_pairs_mut()))?;
+    let meta =
+        parse2::<EndpointMeta>(endpoint_attr(&input.attrs).ok_or_else(|| {
+            syn::Error::new(input.span(), "missing the #[endpoint(...)] attribute")
+        })?)?;
 
-                            if let Some("") = request.url().query() {
-                                request.url_mut().set_query(None);
-                            }
+    let desetup = meta.decode();
+    // `query_setup` adds the query, which the URLs of `link` pagination already contain
+    let (query_setup, setup, url) = match &s.fields {
+        Fields::Unit => {
+            let (query_setup, setup) = split_query(&meta.mode.0, quote::quote!());
+            (query_setup, setup, meta.path.to_token_stream())
+        }
+        fields => {
+            let bindings = field_bindings(fields);
+            let pattern = match fields {
+                Fields::Named(_) => quote::quote!(Self { #(#bindings,)* }),
+                _ => quote::quote!(Self(#(#bindings,)*)),
+            };
+            let url = url_code(&meta.path, fields, &bindings);
+            let (query_setup, setup) =
+                match placed_setup(fields, &bindings, &meta.mode.0, &meta.path, &input.generics)? {
+                    Some((query_setup, setup)) => (
+                        quote::quote! {
+                            let #pattern = self;
+                            #query_setup
                         },
-                        MetaMode::Display => quote::quote! {
-                            request.set_body(self.to_string());
+                        quote::quote! {
+                            let #pattern = self;
+                            #setup
                         },
-                        MetaMode::Empty => quote::quote!(),
-                    },
-                    match meta.mode.1 {
-                        MetaMode::Json => quote::quote!(response.body_json().await?),
-                        MetaMode::Display => quote::quote!(response.body_string().await?),
-                        MetaMode::Query => todo!(),
-                        MetaMode::Empty => quote::quote!(()),
-                    },
-                ),
-                {
-                    let fields = tup
-                        .unnamed
-                        .into_iter()
-                        .enumerate()
-                        .map(|(idx, _)| Ident::new(&format!("_{idx}"), Span::call_site()));
-                    let murl = if !matches!(&meta.path, Expr::Paren(_)) {
-                        let m = meta.path;
-                        quote::quote!(format!(#m))
-                    } else {
-                        meta.path.into_token_stream()
-                    };
+                    ),
+                    None => split_query(
+                        &meta.mode.0,
+                        meta.mode.0.encode(fields, &quote::quote!((*self)))?,
+                    ),
+                };
 
-                    quote::quote! {{
-                                            let Self(#(#fields,)*) = self;
-                    #murl
-                                        }}
-                },
-            ),
-        },
+            (
+                query_setup,
+                setup,
+                quote::quote! {{
+                    let #pattern = self;
+                    #url
+                }},
+            )
+        }
     };
 
-    let ident = input.ident;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let error = meta.error();
+    let output = meta.output_type();
+    let limit_body = meta.limit_body();
+    // the output of paginated endpoints without its metadata
+    let inner = if meta.with_meta {
+        quote::quote!(output.output)
+    } else {
+        quote::quote!(output)
+    };
     let EndpointMeta {
         client,
         method,
-        output,
+        api_error,
+        error: custom_error,
+        paginate,
         ..
     } = meta;
+    let custom_error = custom_error.is_some();
+
+    let run = request_code(
+        quote::quote!(client.new_request(Method::#method, &{#url})),
+        &quote::quote!(#query_setup #setup),
+        &desetup,
+        api_error.as_ref(),
+        custom_error,
+        limit_body.clone(),
+    );
+
+    let paginated = paginate.map(|Paginate { strategy, item, items, size }| {
+        let items = items.iter();
+        let items = quote::quote!(#inner #(.#items)*);
+        let (cursor, fetch) = match strategy {
+            PageStrategy::Link => {
+                let setup = quote::quote! {
+                    if cursor.is_none() {
+                        #query_setup
+                    }
+                    #setup
+                };
+                let code = request_code(
+                    quote::quote! {
+                        match cursor {
+                            Some(url) => client.new_request(Method::#method, url),
+                            None => client.new_request(Method::#method, &{#url}),
+                        }
+                    },
+                    &setup,
+                    &desetup,
+                    api_error.as_ref(),
+                    custom_error,
+                    quote::quote! {
+                        #limit_body
+                        next = acril::http::paginate::next_link(&response, &url);
+                    },
+                );
+
+                (
+                    quote::quote!(String),
+                    quote::quote! {
+                        let mut next = None;
+                        let output: Result<Self::Output, Self::Error> = { #code };
+                        let output = output?;
+
+                        Ok(acril::http::paginate::Page {
+                            items: IntoIterator::into_iter(#items).collect(),
+                            next,
+                        })
+                    },
+                )
+            }
+            strategy => {
+                // without the page size, the walk ends with an empty page
+                let size = size.map(|size| {
+                    quote::quote! {
+                        || acril::http::paginate::is_last_page(items.len(), endpoint.#size.clone())
+                    }
+                });
+                let next = match strategy {
+                    PageStrategy::Cursor { field, next } => {
+                        let next = next.iter();
+                        quote::quote! {
+                            let next = #inner #(.#next)*;
+                            let items: Vec<Self::Item> = IntoIterator::into_iter(#items).collect();
+                            let next = next.map(|cursor| {
+                                let mut endpoint = endpoint.clone();
+                                endpoint.#field = From::from(cursor);
+                                endpoint
+                            });
+                        }
+                    }
+                    PageStrategy::Page(field) => quote::quote! {
+                        let items: Vec<Self::Item> = IntoIterator::into_iter(#items).collect();
+                        let last = items.is_empty() #size;
+                        let next = (!last).then(|| {
+                            let mut endpoint = endpoint.clone();
+                            endpoint.#field += 1;
+                            endpoint
+                        });
+                    },
+                    PageStrategy::Offset(field) => quote::quote! {
+                        let items: Vec<Self::Item> = IntoIterator::into_iter(#items).collect();
+                        let last = items.is_empty() #size;
+                        let next = (!last).then(|| {
+                            let mut endpoint = endpoint.clone();
+                            acril::http::paginate::advance(&mut endpoint.#field, items.len());
+                            endpoint
+                        });
+                    },
+                    PageStrategy::Link => unreachable!(),
+                };
+
+                (
+                    quote::quote!(Self),
+                    quote::quote! {
+                        let endpoint = cursor.unwrap_or(self);
+                        let output = endpoint.run(client).await?;
+                        #next
+
+                        Ok(acril::http::paginate::Page { items, next })
+                    },
+                )
+            }
+        };
+
+        quote::quote! {
+            impl #impl_generics acril::http::paginate::PaginatedEndpoint for #ident #ty_generics #where_clause {
+                type Item = #item;
+                type Cursor = #cursor;
+
+                #[allow(unused, clippy::needless_question_mark)]
+                async fn fetch_page(
+                    &self,
+                    client: &mut Self::Context,
+                    cursor: Option<&Self::Cursor>,
+                ) -> Result<acril::http::paginate::Page<Self::Item, Self::Cursor>, Self::Error> {
+                    #fetch
+                }
+            }
+        }
+    });
 
     Ok(quote::quote! {
-        impl Service for #ident {
+        impl #impl_generics Service for #ident #ty_generics #where_clause {
             type Context = #client;
-            type Error = __Endpoint_Error;
+            type Error = #error;
         }
 
-        impl ClientEndpoint for #ident {
+        impl #impl_generics ClientEndpoint for #ident #ty_generics #where_clause {
             type Output = #output;
 
-            #[allow(unused)]
+            #[allow(unused, clippy::needless_question_mark)]
             async fn run(&self, client: &mut Self::Context) -> Result<Self::Output, Self::Error> {
-                let mut request = client.new_request(Method::#method, &{#url});
-                #setup
-
-                let mut response = client.run_request(request).await?;
-                Ok(#desetup)
+                #run
             }
         }
+
+        #paginated
     })
 }
 
-#[proc_macro_attribute]
-pub fn with_builder(args: TS, item: TS) -> TS {
-    _with_builder(args.into(), item.into())
-        .unwrap_or_else(|e| e.to_compile_error())
-        .into()
-}
+/// Derive `ClientEndpoint` for an enum, whose variants each have their own `#[endpoint(...)]`.
+fn endpoint_enum(input: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-fn _with_builder(args: TokenStream, item: TokenStream) -> Result<TokenStream> {
-    let mut item = parse2::<Item>(item)?;
-    let value = {
-        let (stru, ident, struct_fields, meta) = match &item {
-            Item::Struct(s) => (
-                s,
-                s.ident.clone(),
-                &s.fields,
-                parse2::<EndpointMeta>(
-                    s.attrs
-                        .iter()
-                        .find_map(|x| {
-                            if x.path().is_ident("endpoint") {
-                                Some(x.meta.require_list().ok()?.tokens.clone())
-                            } else {
-                                None
-                            }
-                        })
-                        .expect("no endpoint attribute"),
-                )?,
-            ),
-            _ => unreachable!(),
+    let mut variants = Vec::new();
+    for variant in &data.variants {
+        let meta = parse2::<EndpointMeta>(endpoint_attr(&variant.attrs).ok_or_else(|| {
+            syn::Error::new_spanned(
+                variant,
+                "every variant of an endpoint enum needs an #[endpoint(...)] attribute",
+            )
+        })?)?;
+        if meta.paginate.is_some() {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "enum variants cannot be paginated",
+            ));
+        }
+        variants.push((variant, meta));
+    }
+
+    let Some((_, first)) = variants.first() else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "an endpoint enum needs at least one variant",
+        ));
+    };
+    let client = &first.client;
+    let error = first.error();
+    let same = |a: &dyn ToTokens, b: &dyn ToTokens| {
+        a.to_token_stream().to_string() == b.to_token_stream().to_string()
+    };
+    for (variant, meta) in &variants {
+        if !same(&meta.client, client) || !same(&meta.error(), &error) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "all variants of an endpoint enum need the same client and error type",
+            ));
+        }
+    }
+
+    // variants with different outputs return a generated enum of them
+    let output_enum = variants
+        .iter()
+        .any(|(_, meta)| !same(&meta.output_type(), &first.output_type()))
+        .then(|| Ident::new(&format!("{ident}Output"), ident.span()));
+
+    let arms = variants.iter().map(|(variant, meta)| {
+        let name = &variant.ident;
+        let bindings = field_bindings(&variant.fields);
+        let pattern = match &variant.fields {
+            Fields::Named(_) => quote::quote!(Self::#name { #(#bindings,)* }),
+            Fields::Unnamed(_) => quote::quote!(Self::#name(#(#bindings,)*)),
+            Fields::Unit => quote::quote!(Self::#name),
         };
-        let client = meta.client;
-        let output = meta.output;
-        let bld = Ident::new(&format!("{ident}Builder"), ident.span());
-        let doc = stru.attrs.iter().find_map(|x| {
-                    if x.path().is_ident("doc") {
-                        if let Meta::NameValue(MetaNameValue { value: Expr::Lit(ExprLit { lit: Lit::Str(st), .. }), .. }) = &x.meta {
-                            let value = Literal::string(&format!("{}\nThis function returns a builder, so you can configure the request and send it with [`{}::execute`].", st.value(), bld.to_string()));
-                            Some(quote::quote!(#[doc = #value]))
-                        } else {
-                            None
+
+        let (setup, url) = if let Fields::Unit = variant.fields {
+            (quote::quote!(), meta.path.to_token_stream())
+        } else {
+            let setup = match placed_setup(
+                &variant.fields,
+                &bindings,
+                &meta.mode.0,
+                &meta.path,
+                &input.generics,
+            )? {
+                Some((query_setup, setup)) => quote::quote!(#query_setup #setup),
+                None => {
+                    let body = quote::quote!(__body);
+                    let encode = meta.mode.0.encode(&variant.fields, &body)?;
+                    let body = match &meta.mode.0 {
+                        MetaMode::Json | MetaMode::Query | MetaMode::Form | MetaMode::Multipart => {
+                            ref_struct(
+                                "Body",
+                                &variant.fields,
+                                &bindings,
+                                &meta.mode.0,
+                                &input.generics,
+                            )
                         }
-                    } else {
-                        None
+                        _ => quote::quote!(),
+                    };
+                    quote::quote!(#body #encode)
+                }
+            };
+
+            (setup, url_code(&meta.path, &variant.fields, &bindings))
+        };
+
+        let desetup = meta.decode();
+        let desetup = match &output_enum {
+            Some(output) => quote::quote!(#output::#name(#desetup)),
+            None => desetup,
+        };
+
+        let method = &meta.method;
+        let code = request_code(
+            quote::quote!(client.new_request(Method::#method, &{#url})),
+            &setup,
+            &desetup,
+            meta.api_error.as_ref(),
+            meta.error.is_some(),
+            meta.limit_body(),
+        );
+
+        Ok(quote::quote!(#pattern => { #code }))
+    });
+    let arms = arms.collect::<Result<Vec<_>>>()?;
+
+    let (output, output_def) = match output_enum {
+        Some(output) => {
+            let vis = &input.vis;
+            let doc = format!("The output of [`{ident}`], one variant for each of its variants.");
+            let outputs = variants.iter().map(|(variant, meta)| {
+                let name = &variant.ident;
+                let output = meta.output_type();
+                quote::quote!(#name(#output))
+            });
+            // the outputs may not use all the generics of the endpoint
+            let marker = phantom(&input.generics).map(|phantom| {
+                quote::quote! {
+                    #[doc(hidden)]
+                    __Marker(#phantom, ::core::convert::Infallible),
+                }
+            });
+            let generics = &input.generics;
+
+            (
+                quote::quote!(#output #ty_generics),
+                quote::quote! {
+                    #[doc = #doc]
+                    #vis enum #output #generics #where_clause {
+                        #(#outputs,)*
+                        #marker
                     }
-                });
-        let doc_unit = stru.attrs.iter().find_map(|x| {
-            if x.path().is_ident("doc") {
-                if let Meta::NameValue(MetaNameValue { value, .. }) = &x.meta {
-                    Some(quote::quote!(#[doc = #value]))
+                },
+            )
+        }
+        None => (first.output_type(), quote::quote!()),
+    };
+
+    Ok(quote::quote! {
+        #output_def
+
+        impl #impl_generics Service for #ident #ty_generics #where_clause {
+            type Context = #client;
+            type Error = #error;
+        }
+
+        impl #impl_generics ClientEndpoint for #ident #ty_generics #where_clause {
+            type Output = #output;
+
+            #[allow(unused, clippy::needless_question_mark)]
+            async fn run(&self, client: &mut Self::Context) -> Result<Self::Output, Self::Error> {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    })
+}
+
+/// Define `__name`, a struct of references to `fields` bound to `bindings`, serializable the same
+/// way as the fields of a struct would be in `mode`. It has the `generics` of the endpoint, as the
+/// fields may use them.
+fn ref_struct(
+    name: &str,
+    fields: &Fields,
+    bindings: &[Ident],
+    mode: &MetaMode,
+    generics: &Generics,
+) -> TokenStream {
+    let ident = Ident::new(&format!("__{name}"), Span::call_site());
+    let var = Ident::new(&format!("__{}", name.to_lowercase()), Span::call_site());
+    // multipart bodies are not serialized as a whole, so their fields may not be serializable
+    let serialize = !matches!(mode, MetaMode::Multipart);
+    let members = fields.iter().map(|field| {
+        let ty = &field.ty;
+        let ident = &field.ident;
+        let attrs = field
+            .attrs
+            .iter()
+            .filter(|a| serialize && a.path().is_ident("serde"));
+        match ident {
+            Some(ident) => quote::quote!(#(#attrs)* #ident: &'__a #ty),
+            None => quote::quote!(#(#attrs)* &'__a #ty),
+        }
+    });
+    let derive = serialize.then(|| {
+        quote::quote! {
+            #[derive(acril::serde::Serialize)]
+            #[serde(crate = "acril::serde")]
+        }
+    });
+
+    // the type of the struct, which cannot be inferred for the generics its fields do not use
+    let args = generic_args(generics);
+    let ty = quote::quote!(#ident<'_, #(#args),*>);
+    let mut generics = generics.clone();
+    generics.params.insert(0, parse_quote!('__a));
+    let where_clause = &generics.where_clause;
+    // the generics of the endpoint the fields do not use
+    let skip = serialize.then(|| quote::quote!(#[serde(skip)]));
+    let marker = phantom(&generics);
+    let marker_value = marker
+        .as_ref()
+        .map(|_| quote::quote!(::core::marker::PhantomData));
+
+    if let Fields::Unnamed(_) = fields {
+        let marker = marker.map(|marker| quote::quote!(#skip #marker));
+        quote::quote! {
+            #derive
+            struct #ident #generics (#(#members,)* #marker) #where_clause;
+            let #var: #ty = #ident(#(#bindings,)* #marker_value);
+        }
+    } else {
+        let marker = marker.map(|marker| quote::quote!(#skip __marker: #marker));
+        let marker_value = marker_value.map(|value| quote::quote!(__marker: #value));
+        quote::quote! {
+            #derive
+            struct #ident #generics #where_clause { #(#members,)* #marker }
+            let #var: #ty = #ident { #(#bindings,)* #marker_value };
+        }
+    }
+}
+
+/// The parameters of `generics` as arguments.
+fn generic_args(generics: &Generics) -> Vec<TokenStream> {
+    generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(param) => param.lifetime.to_token_stream(),
+            GenericParam::Type(param) => param.ident.to_token_stream(),
+            GenericParam::Const(param) => param.ident.to_token_stream(),
+        })
+        .collect()
+}
+
+/// A `PhantomData` using the lifetimes and type parameters of `generics`, for the types generated
+/// with them which may not use all of them.
+fn phantom(generics: &Generics) -> Option<TokenStream> {
+    let params = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Lifetime(param) => {
+                let lifetime = &param.lifetime;
+                Some(quote::quote!(&#lifetime ()))
+            }
+            GenericParam::Type(param) => Some(param.ident.to_token_stream()),
+            GenericParam::Const(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    (!params.is_empty()).then(|| quote::quote!(::core::marker::PhantomData<fn() -> (#(#params,)*)>))
+}
+
+/// Split `setup`, the code encoding an endpoint in `mode`, into the code adding the query and the
+/// rest.
+fn split_query(mode: &MetaMode, setup: TokenStream) -> (TokenStream, TokenStream) {
+    match mode {
+        MetaMode::Query => (setup, quote::quote!()),
+        _ => (quote::quote!(), setup),
+    }
+}
+
+/// Where a field is sent, set by its placement attribute.
+enum Placement {
+    /// `#[path_param]`: only in the path of the endpoint.
+    Path,
+    /// `#[query]`: in the query string.
+    Query,
+    /// `#[header("Name")]`: as the value of the header.
+    Header(LitStr),
+    /// `#[body]`: in the body, encoded as the input mode of the endpoint (JSON if it has none).
+    /// With `#[body(whole)]`, the field is the whole body instead of one of its fields.
+    Body { whole: bool },
+}
+
+impl Placement {
+    /// The placement attribute of `field`, if it has one.
+    fn of(field: &Field) -> Result<Option<Self>> {
+        let mut placement = None;
+
+        for attr in &field.attrs {
+            let path = attr.path();
+            let this = if path.is_ident("path_param") || path.is_ident("query") {
+                attr.meta.require_path_only()?;
+                if path.is_ident("path_param") {
+                    Self::Path
                 } else {
-                    None
+                    Self::Query
+                }
+            } else if path.is_ident("body") {
+                let whole = match &attr.meta {
+                    Meta::Path(_) => false,
+                    Meta::List(list) if list.parse_args::<kw::whole>().is_ok() => true,
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            attr,
+                            "expected #[body] or #[body(whole)]",
+                        ))
+                    }
+                };
+                Self::Body { whole }
+            } else if path.is_ident("header") {
+                let Meta::List(list) = &attr.meta else {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "expected the name of the header: #[header(\"X-Name\")]",
+                    ));
+                };
+                let name = list.parse_args::<LitStr>()?;
+                let is_token = |b: u8| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b);
+                if name.value().is_empty() || !name.value().bytes().all(is_token) {
+                    return Err(syn::Error::new_spanned(name, "invalid header name"));
                 }
+                Self::Header(name)
             } else {
-                None
+                continue;
+            };
+
+            if placement.is_some() {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "conflicting placements: a field can only have one of #[path_param], #[query], #[header(...)] and #[body]",
+                ));
             }
-        });
-        let fields = match struct_fields {
-            Fields::Unit => {
-                return Ok(if let Ok(method) = parse2::<Ident>(args) {
-                    quote::quote! {
-                        #item
+            placement = Some(this);
+        }
 
-                        impl #client {
-                            #doc_unit
-                            pub async fn #method(&self) -> Result<#output, __Endpoint_Error> {
-                                #ident.run(self).await
-                            }
-                        }
+        Ok(placement)
+    }
+}
+
+/// The code adding the query and the rest of the code setting up `request` from `fields`, bound to
+/// `bindings`, if any field has a placement attribute, in an endpoint with `generics`. Fields
+/// without one are sent the way `mode` sends a whole endpoint, or only used in the path if it
+/// sends nothing, its `Display` or what a `with` function encodes.
+fn placed_setup(
+    fields: &Fields,
+    bindings: &[Ident],
+    mode: &MetaMode,
+    path: &Expr,
+    generics: &Generics,
+) -> Result<Option<(TokenStream, TokenStream)>> {
+    let placements = fields
+        .iter()
+        .map(Placement::of)
+        .collect::<Result<Vec<_>>>()?;
+    if placements.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+
+    let (mut query, mut body, mut headers) = (Vec::new(), Vec::new(), Vec::new());
+    let mut whole = None;
+    for ((field, binding), placement) in fields.iter().zip(bindings).zip(placements) {
+        match placement {
+            Some(Placement::Path) => {
+                if let Expr::Lit(ExprLit {
+                    lit: Lit::Str(path),
+                    ..
+                }) = path
+                {
+                    let path = path.value();
+                    if !path.contains(&format!("{{{binding}}}"))
+                        && !path.contains(&format!("{{{binding}:"))
+                    {
+                        return Err(syn::Error::new_spanned(
+                            field,
+                            format!("`{binding}` is a #[path_param] field, but the path does not use it"),
+                        ));
                     }
-                } else {
-                    TokenStream::new()
-                })
+                }
             }
-            Fields::Named(named) => &named.named,
-            Fields::Unnamed(unnamed) => {
+            Some(Placement::Query) => query.push((field, binding)),
+            Some(Placement::Header(name)) => headers.push(quote::quote! {
+                if let Some(value) = acril::http::client::header_value(#binding)? {
+                    request.insert_header(#name, value);
+                }
+            }),
+            Some(Placement::Body { .. }) if matches!(mode, MetaMode::Display | MetaMode::With(_)) => {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "display and `with` endpoints encode the whole endpoint, they cannot have #[body] fields",
+                ))
+            }
+            Some(Placement::Body { whole: false }) => body.push((field, binding)),
+            Some(Placement::Body { whole: true }) if whole.is_some() => {
                 return Err(syn::Error::new_spanned(
-                    unnamed,
-                    "#[with_builder] does not support tuple structs",
+                    field,
+                    "an endpoint can only have one #[body(whole)] field",
                 ))
             }
+            Some(Placement::Body { whole: true }) => whole = Some((field, binding)),
+            None => match mode {
+                MetaMode::Query => query.push((field, binding)),
+                MetaMode::Json | MetaMode::Form | MetaMode::Multipart => {
+                    body.push((field, binding))
+                }
+                _ => {}
+            },
+        }
+    }
+
+    // the fields sent in the query or the body, of the same kind as `fields`
+    let subset = |placed: &[(&Field, &Ident)]| -> (Fields, Vec<Ident>) {
+        let named: Punctuated<Field, Token![,]> =
+            placed.iter().map(|(field, _)| (*field).clone()).collect();
+        let bindings = placed
+            .iter()
+            .map(|(_, binding)| (*binding).clone())
+            .collect();
+        let fields = match fields {
+            Fields::Named(_) => Fields::Named(syn::FieldsNamed {
+                brace_token: Default::default(),
+                named,
+            }),
+            _ => Fields::Unnamed(syn::FieldsUnnamed {
+                paren_token: Default::default(),
+                unnamed: named,
+            }),
         };
+        (fields, bindings)
+    };
+
+    let query = if query.is_empty() {
+        quote::quote!()
+    } else {
+        let (fields, bindings) = subset(&query);
+        let query = ref_struct("Query", &fields, &bindings, &MetaMode::Query, generics);
+        let encode = MetaMode::Query.encode(&fields, &quote::quote!(__query))?;
+        quote::quote!(#query #encode)
+    };
+
+    let body =
+        if let MetaMode::Display | MetaMode::With(_) = mode {
+            mode.encode(fields, &quote::quote!())?
+        } else if let Some((field, binding)) = whole {
+            if !body.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "a #[body(whole)] field must be the only field sent in the body",
+                ));
+            }
+            match mode {
+                MetaMode::Form => MetaMode::Form,
+                MetaMode::Multipart => return Err(syn::Error::new_spanned(
+                    field,
+                    "multipart bodies are made of fields, they cannot be a #[body(whole)] field",
+                )),
+                _ => MetaMode::Json,
+            }
+            .encode(&Fields::Unit, &quote::quote!((*#binding)))?
+        } else if body.is_empty() {
+            quote::quote!()
+        } else {
+            let mode = match mode {
+                MetaMode::Form | MetaMode::Multipart => mode,
+                _ => &MetaMode::Json,
+            };
+            let (fields, bindings) = subset(&body);
+            let body = ref_struct("Body", &fields, &bindings, mode, generics);
+            let encode = mode.encode(&fields, &quote::quote!(__body))?;
+            quote::quote!(#body #encode)
+        };
+
+    Ok(Some((query, quote::quote!(#(#headers)* #body))))
+}
+
+/// Code sending the request created by `request` and set up by `setup`, evaluating to the
+/// output decoded by `desetup`. `url` is the URL of the request, and `response` the response
+/// for `after_response`. Errors are converted with `?` to the error of the endpoint, and so are
+/// `EndpointError`s if it is set with `error = Type`.
+fn request_code(
+    request: TokenStream,
+    setup: &TokenStream,
+    desetup: &TokenStream,
+    api_error: Option<&Type>,
+    error: bool,
+    after_response: TokenStream,
+) -> TokenStream {
+    if let Some(api_error) = api_error {
+        let code = quote::quote! {
+            use acril::http::client::{ApiError, EndpointError};
+
+            let request = async {
+                let mut request = #request;
+                #setup
+                Ok::<_, http_types::Error>(request)
+            }
+            .await
+            .map_err(EndpointError::Encode)?;
+            let url = request.url().clone();
+
+            let mut response = client
+                .run_request(request)
+                .await
+                .map_err(EndpointError::Transport)?;
+            #after_response
+
+            let status = response.status();
+            if status.is_client_error() || status.is_server_error() {
+                let headers = AsRef::<http_types::headers::Headers>::as_ref(&response).clone();
+                let body = response.body_json::<#api_error>().await.map_err(|mut e| {
+                    e.set_status(status);
+                    EndpointError::Decode(e)
+                })?;
+
+                return Err(EndpointError::Api(ApiError { status, headers, body }));
+            }
+
+            async { Ok::<_, http_types::Error>(#desetup) }
+                .await
+                .map_err(EndpointError::Decode)
+        };
+
+        if error {
+            quote::quote! {
+                let result: Result<
+                    _,
+                    acril::http::client::EndpointError<
+                        #api_error,
+                        <Self::Context as acril::http::client::HttpClientContext>::Error,
+                    >,
+                > = async { #code }.await;
+                result.map_err(From::from)
+            }
+        } else {
+            code
+        }
+    } else {
+        // encoding and decoding errors are `http_types::Error`s, like those of `EndpointError`
+        quote::quote! {
+            let request = async {
+                let mut request = #request;
+                #setup
+                Ok::<_, http_types::Error>(request)
+            }
+            .await?;
+            let url = request.url().clone();
+
+            let mut response = client.run_request(request).await?;
+            #after_response
+            // `?` converts to the error of the endpoint, which may be `http_types::Error` itself
+            Ok(async { Ok::<_, http_types::Error>(#desetup) }.await?)
+        }
+    }
+}
+
+/// The name `field` is serialized with, and whether serde skips it.
+fn field_name(field: &Field) -> (String, bool) {
+    let mut name = field
+        .ident
+        .as_ref()
+        .map(|ident| ident.to_string())
+        .unwrap_or_default();
+    if let Some(raw) = name.strip_prefix("r#") {
+        name = raw.to_owned();
+    }
+    let mut skipped = false;
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let Ok(metas) = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+        else {
+            continue;
+        };
+
+        for meta in metas {
+            match meta {
+                Meta::Path(path) if path.is_ident("skip") || path.is_ident("skip_serializing") => {
+                    skipped = true
+                }
+                Meta::NameValue(MetaNameValue {
+                    path,
+                    value:
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(rename),
+                            ..
+                        }),
+                    ..
+                }) if path.is_ident("rename") => name = rename.value(),
+                _ => {}
+            }
+        }
+    }
+
+    (name, skipped)
+}
 
-        let field_methods = fields.iter().map(
-            |Field {
-                 ident, ty, attrs, ..
-             }| {
-                let doc = attrs.iter().find_map(|x| {
-                    if x.path().is_ident("doc") {
-                        if let Meta::NameValue(MetaNameValue { value, .. }) = &x.meta {
-                            Some(quote::quote!(#[doc = #value]))
-                        } else {
-                            None
+/// Generate a typestate builder for an endpoint, and a method returning it on the client if a
+/// name is given. `#[required]` fields must be set before the builder can be executed, the others
+/// start from their `#[default = expr]` or `Default::default()`, and `Option<T>` fields are set
+/// with a `T`. It must come before `#[derive(ClientEndpoint)]`, which does not know `#[default]`.
+///
+/// When all fields are `#[required]`, or there are none, the method on the client takes the fields
+/// as arguments and sends the endpoint: `client.method(a, b).await`. The builder is then still
+/// available with `XBuilder::new(&mut client)`. Client methods take `&mut self`, which running an
+/// endpoint needs.
+///
+/// With `#[with_builder(method, blocking)]`, the builder can also be executed with
+/// `execute_blocking`, and the direct method has a `method_blocking` version.
+#[proc_macro_attribute]
+pub fn with_builder(args: TS, item: TS) -> TS {
+    _with_builder(args.into(), item.into())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// The arguments of `#[with_builder(method, blocking)]`: the name of the method on the client, and
+/// whether to generate blocking methods too.
+struct BuilderArgs {
+    method: Option<Ident>,
+    blocking: bool,
+}
+
+impl syn::parse::Parse for BuilderArgs {
+    fn parse(input: syn::parse::ParseStream) -> Result<Self> {
+        let method: Option<Ident> = input.parse()?;
+        let mut blocking = false;
+        if method.is_some() && input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            if key != "blocking" {
+                return Err(syn::Error::new_spanned(key, "expected `blocking`"));
+            }
+            blocking = true;
+            input.parse::<Option<Token![,]>>()?;
+        }
+
+        Ok(Self { method, blocking })
+    }
+}
+
+/// A field of a `#[with_builder]` endpoint.
+struct BuilderField {
+    binding: Ident,
+    ty: Type,
+    /// The `T` of an `Option<T>` field, which setters and defaults take.
+    inner: Option<Type>,
+    /// The type parameter tracking whether a `#[required]` field is set.
+    state: Option<Ident>,
+    default: Option<Expr>,
+    doc: Vec<Attribute>,
+}
+
+impl BuilderField {
+    fn new(field: &mut Field, binding: Ident) -> Result<Self> {
+        let required = field.attrs.iter().any(|a| a.path().is_ident("required"));
+        let mut default = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("default")) {
+            if required {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "#[required] fields cannot have a default",
+                ));
+            }
+            if default.is_some() {
+                return Err(syn::Error::new_spanned(attr, "duplicate default"));
+            }
+            default = Some(attr.meta.require_name_value()?.value.clone());
+        }
+        // the derive does not know `#[default = ...]`
+        field.attrs.retain(|a| !a.path().is_ident("default"));
+
+        let state = required.then(|| {
+            let name = binding.to_string();
+            let name = openapi::pascal(name.trim_start_matches("r#"));
+            Ident::new(&format!("__{name}"), binding.span())
+        });
+
+        Ok(Self {
+            inner: option_inner(&field.ty).cloned(),
+            ty: field.ty.clone(),
+            state,
+            default,
+            doc: field
+                .attrs
+                .iter()
+                .filter(|a| a.path().is_ident("doc"))
+                .cloned()
+                .collect(),
+            binding,
+        })
+    }
+
+    /// Converts `value`, the `impl Into<T>` of a setter, to the type of the field.
+    fn convert(&self, value: impl ToTokens) -> TokenStream {
+        let ty = &self.ty;
+        match &self.inner {
+            Some(inner) => {
+                quote::quote!(::core::option::Option::Some(Into::<#inner>::into(#value)))
+            }
+            None => quote::quote!(Into::<#ty>::into(#value)),
+        }
+    }
+
+    /// The initial value of an optional field: its default, wrapped in `Some` for `Option<T>`
+    /// fields, or `Default::default()`.
+    fn initial(&self) -> TokenStream {
+        match (&self.default, &self.inner) {
+            (Some(default), Some(_)) => quote::quote!(::core::option::Option::Some(#default)),
+            (Some(default), None) => default.to_token_stream(),
+            (None, _) => quote::quote!(::core::default::Default::default()),
+        }
+    }
+}
+
+/// The `T` of `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if path.qself.is_some() || segment.ident != "Option" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.iter().collect::<Vec<_>>()[..] {
+        [syn::GenericArgument::Type(inner)] => Some(inner),
+        _ => None,
+    }
+}
+
+fn _with_builder(args: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    let mut item = match parse2::<Item>(item)? {
+        Item::Struct(s) => s,
+        item => {
+            return Err(syn::Error::new_spanned(
+                item,
+                "#[with_builder] only supports structs",
+            ))
+        }
+    };
+    let BuilderArgs { method, blocking } = parse2(args)?;
+    let meta = parse2::<EndpointMeta>(endpoint_attr(&item.attrs).ok_or_else(|| {
+        syn::Error::new_spanned(&item.ident, "missing the #[endpoint(...)] attribute")
+    })?)?;
+
+    let output = meta.output_type();
+    let client = &meta.client;
+    let ident = &item.ident;
+    let vis = &item.vis;
+    let bld = Ident::new(&format!("{ident}Builder"), ident.span());
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let error = quote::quote!(<#ident #ty_generics as Service>::Error);
+    // the generic arguments of the endpoint, passed on by the builder
+    let args = generic_args(&item.generics);
+    let doc = item
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let bindings = field_bindings(&item.fields);
+    let fields = item
+        .fields
+        .iter_mut()
+        .zip(bindings)
+        .map(|(field, binding)| BuilderField::new(field, binding))
+        .collect::<Result<Vec<_>>>()?;
+
+    if fields.is_empty() {
+        let turbofish = ty_generics.as_turbofish();
+        return Ok(match method {
+            Some(method) => {
+                let blocking = blocking.then(|| {
+                    let blocking = Ident::new(&format!("{method}_blocking"), method.span());
+                    let doc = format!("The blocking version of [`Self::{method}`].");
+                    quote::quote! {
+                        #[doc = #doc]
+                        #vis fn #blocking #impl_generics (&mut self) -> Result<#output, #error>
+                        #where_clause
+                        {
+                            acril::http::blocking::block_on(self.#method())
                         }
-                    } else {
-                        None
                     }
                 });
 
                 quote::quote! {
-                #doc
-                                pub fn #ident(mut self, #ident: impl Into<#ty>) -> Self {
-                        self.1.#ident = Into::<#ty>::into(#ident);
-                        self
-                                }
-                            }
-            },
-        );
+                    #item
 
-        let client_impl = if let Ok(method) = parse2::<Ident>(args) {
-            if fields
-                .iter()
-                .all(|x| x.attrs.iter().any(|a| a.path().is_ident("required")))
-            {
-                let args = fields
-                    .iter()
-                    .map(|Field { ident, ty, .. }| quote::quote!(#ident: #ty));
-                let self_init_fields = fields
-                    .iter()
-                    .map(|Field { ident, .. }| quote::quote!(#ident));
-
-                Some(quote::quote! {
-                impl #client {
-                pub async fn #method(&self, #(#args,)*) -> Result<#output, __Endpoint_Error> {
-                    #ident {#(#self_init_fields,)*}.run(self).await
-                }
-                }
-                            })
-            } else {
-                let args = fields.iter().filter_map(
-                    |Field {
-                         attrs, ident, ty, ..
-                     }| {
-                        if attrs.iter().any(|a| a.path().is_ident("required")) {
-                            Some(quote::quote!(#ident: #ty))
-                        } else {
-                            None
-                        }
-                    },
-                );
-                let self_init_fields = fields.iter().map(
-                    |Field {
-                         attrs, ident, ty, ..
-                     }| {
-                        if attrs.iter().any(|a| a.path().is_ident("required")) {
-                            quote::quote!(#ident)
-                        } else {
-                            quote::quote!(#ident: <#ty as Default>::default())
+                    impl #client {
+                        #(#doc)*
+                        #vis async fn #method #impl_generics (&mut self) -> Result<#output, #error>
+                        #where_clause
+                        {
+                            #ident #turbofish {}.run(self).await
                         }
-                    },
-                );
 
-                Some(quote::quote! {
-                        impl #client {
-                pub fn #method(&mut self, #(#args,)*) -> #bld<'_> {
-                    #bld(self, #ident { #(#self_init_fields,)* })
+                        #blocking
+                    }
                 }
-                        }
-                                })
             }
-        } else {
-            None
+            None => item.into_token_stream(),
+        });
+    }
+
+    let states = fields
+        .iter()
+        .filter_map(|f| f.state.as_ref())
+        .collect::<Vec<_>>();
+    let storage = fields.iter().map(|f| {
+        let binding = &f.binding;
+        match &f.state {
+            Some(state) => quote::quote!(#binding: #state),
+            None => {
+                let ty = &f.ty;
+                quote::quote!(#binding: #ty)
+            }
+        }
+    });
+    let inits = fields.iter().map(|f| {
+        let binding = &f.binding;
+        let value = match &f.state {
+            Some(_) => quote::quote!(acril::http::client::Unset),
+            None => f.initial(),
         };
+        quote::quote!(#binding: #value)
+    });
 
-        quote::quote! {
-            #item
+    // the builder may not use all the generics of the endpoint
+    let marker = phantom(&item.generics).map(|phantom| quote::quote!(__marker: #phantom,));
+    let marker_value = marker
+        .as_ref()
+        .map(|_| quote::quote!(__marker: ::core::marker::PhantomData,));
 
-            #doc
-            pub struct #bld<'a>(&'a mut #client, #ident);
-            impl<'a> #bld<'a> {
-                pub async fn execute(self) -> Result<#output, __Endpoint_Error> {
-                    self.1.run(self.0).await
+    let setters = fields.iter().map(|f| {
+        let binding = &f.binding;
+        let doc = &f.doc;
+        let arg = f.inner.as_ref().unwrap_or(&f.ty);
+        let value = f.convert(binding);
+        let Some(state) = &f.state else {
+            return quote::quote! {
+                #(#doc)*
+                pub fn #binding(mut self, #binding: impl Into<#arg>) -> Self {
+                    self.#binding = #value;
+                    self
                 }
+            };
+        };
 
-                #(#field_methods)*
+        // setting a required field changes its state to its type
+        let ty = &f.ty;
+        let next = states.iter().map(|s| {
+            if *s == state {
+                ty.to_token_stream()
+            } else {
+                s.to_token_stream()
+            }
+        });
+        let moved = fields.iter().map(|other| {
+            let other = &other.binding;
+            if other == binding {
+                quote::quote!(#binding: #value)
+            } else {
+                quote::quote!(#other: self.#other)
+            }
+        });
+        quote::quote! {
+            #(#doc)*
+            pub fn #binding(self, #binding: impl Into<#arg>) -> #bld<'__client, #(#args,)* #(#next),*> {
+                #bld {
+                    __client: self.__client,
+                    #(#moved,)*
+                    #marker_value
+                }
             }
+        }
+    });
 
-            #client_impl
+    let set = fields.iter().filter(|f| f.state.is_some()).map(|f| &f.ty);
+    let values = fields.iter().map(|f| {
+        let binding = &f.binding;
+        quote::quote!(self.#binding)
+    });
+    let endpoint = match &item.fields {
+        Fields::Named(_) => {
+            let bindings = fields.iter().map(|f| &f.binding);
+            quote::quote!(#ident { #(#bindings: #values),* })
         }
+        _ => quote::quote!(#ident(#(#values),*)),
     };
 
-    if let Item::Struct(ItemStruct {
-        fields: Fields::Named(ref mut named),
-        ..
-    }) = item
-    {
-        named
-            .named
-            .iter_mut()
-            .for_each(|x| x.attrs.retain_mut(|x| !x.path().is_ident("with_builder")));
+    let bld_doc = format!(
+        "A builder of [`{ident}`]. Its `#[required]` fields must be set before it can be sent with \
+         [`{bld}::execute`]."
+    );
+    let all_required = fields.iter().all(|f| f.state.is_some());
+    let client_impl = method.map(|method| {
+        if all_required {
+            // with nothing to configure, the method takes the fields and sends the endpoint
+            let params = fields.iter().map(|f| {
+                let (binding, ty) = (&f.binding, &f.ty);
+                quote::quote!(#binding: #ty)
+            });
+            let params = quote::quote!(#(#params),*);
+            let bindings = fields.iter().map(|f| &f.binding).collect::<Vec<_>>();
+            let turbofish = ty_generics.as_turbofish();
+            let endpoint = match &item.fields {
+                Fields::Named(_) => quote::quote!(#ident #turbofish { #(#bindings),* }),
+                _ => quote::quote!(#ident #turbofish (#(#bindings),*)),
+            };
+            let blocking = blocking.then(|| {
+                let blocking = Ident::new(&format!("{method}_blocking"), method.span());
+                let doc = format!("The blocking version of [`Self::{method}`].");
+                quote::quote! {
+                    #[doc = #doc]
+                    #vis fn #blocking #impl_generics (&mut self, #params) -> Result<#output, #error>
+                    #where_clause
+                    {
+                        acril::http::blocking::block_on(self.#method(#(#bindings),*))
+                    }
+                }
+            });
+
+            return quote::quote! {
+                impl #client {
+                    #(#doc)*
+                    #vis async fn #method #impl_generics (&mut self, #params) -> Result<#output, #error>
+                    #where_clause
+                    {
+                        #endpoint.run(self).await
+                    }
+
+                    #blocking
+                }
+            };
+        }
+
+        let method_doc = format!(
+            "This function returns a builder, so you can configure the request and send it with \
+             [`{bld}::execute`]."
+        );
+        let separator = (!doc.is_empty()).then(|| quote::quote!(#[doc = ""]));
+        quote::quote! {
+            impl #client {
+                #(#doc)*
+                #separator
+                #[doc = #method_doc]
+                #vis fn #method #impl_generics (&mut self) -> #bld<'_, #(#args),*> #where_clause {
+                    #bld::new(self)
+                }
+            }
+        }
+    });
+
+    let execute_blocking = blocking.then(|| {
+        quote::quote! {
+            /// The blocking version of [`Self::execute`].
+            pub fn execute_blocking(self) -> Result<#output, #error> {
+                acril::http::blocking::block_on(self.execute())
+            }
+        }
+    });
+
+    let mut generics = item.generics.clone();
+    generics.params.insert(0, parse_quote!('__client));
+    let (base_generics, _, _) = generics.split_for_impl();
+    let base_generics = base_generics.to_token_stream();
+    let mut state_generics = generics.clone();
+    let mut def_generics = generics;
+    for state in &states {
+        state_generics.params.push(parse_quote!(#state));
+        def_generics
+            .params
+            .push(parse_quote!(#state = acril::http::client::Unset));
     }
+    let (state_generics, state_ty_generics, _) = state_generics.split_for_impl();
 
-    Ok(value)
+    Ok(quote::quote! {
+        #item
+
+        #[doc = #bld_doc]
+        #[must_use]
+        #vis struct #bld #def_generics #where_clause {
+            __client: &'__client mut #client,
+            #(#storage,)*
+            #marker
+        }
+
+        impl #base_generics #bld<'__client, #(#args),*> #where_clause {
+            #vis fn new(client: &'__client mut #client) -> Self {
+                Self {
+                    __client: client,
+                    #(#inits,)*
+                    #marker_value
+                }
+            }
+        }
+
+        impl #state_generics #bld #state_ty_generics #where_clause {
+            #(#setters)*
+        }
+
+        impl #base_generics #bld<'__client, #(#args,)* #(#set),*> #where_clause {
+            pub async fn execute(self) -> Result<#output, #error> {
+                #endpoint.run(self.__client).await
+            }
+
+            #execute_blocking
+        }
+
+        #client_impl
+    })
 }
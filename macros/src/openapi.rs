@@ -0,0 +1,790 @@
+//! `openapi!`: endpoints, models and a client facade generated from an OpenAPI 3 document.
+
+use std::collections::HashSet;
+
+use proc_macro2::{Ident, Span, TokenStream};
+use serde_yaml::Value;
+use syn::{
+    parse::{Parse, ParseStream},
+    LitStr, Result, Token, Type,
+};
+
+/// The arguments of `openapi!`: the path of the document, relative to the manifest of the crate,
+/// the client the endpoints are called with, and optionally their error type.
+struct Args {
+    spec: LitStr,
+    client: Type,
+    error: Option<Type>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let spec = input.parse()?;
+        if input.is_empty() {
+            return Err(input.error("missing the client: `openapi!(\"spec.yaml\", client = Type)`"));
+        }
+        input.parse::<Token![,]>()?;
+        let key = input.parse::<Ident>()?;
+        if key != "client" {
+            return Err(syn::Error::new_spanned(key, "expected `client = Type`"));
+        }
+        input.parse::<Token![=]>()?;
+        let client = input.parse()?;
+
+        let mut error = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            if key != "error" {
+                return Err(syn::Error::new_spanned(key, "expected `error = Type`"));
+            }
+            input.parse::<Token![=]>()?;
+            error = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+
+        Ok(Self {
+            spec,
+            client,
+            error,
+        })
+    }
+}
+
+/// The errors of the generator, reported at the path of the document.
+type Gen<T> = std::result::Result<T, String>;
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+pub(crate) fn openapi(args: TokenStream) -> Result<TokenStream> {
+    let Args {
+        spec,
+        client,
+        error: endpoint_error,
+    } = syn::parse2(args)?;
+    let error = |message: String| syn::Error::new(spec.span(), message);
+
+    let root = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| error("CARGO_MANIFEST_DIR is not set".into()))?;
+    let path = std::path::Path::new(&root).join(spec.value());
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| error(format!("cannot read {}: {e}", path.display())))?;
+    // YAML is a superset of JSON, so this parses both
+    let doc: Value =
+        serde_yaml::from_str(&text).map_err(|e| error(format!("invalid OpenAPI document: {e}")))?;
+
+    let mut gen = Generator {
+        doc: &doc,
+        client: &client,
+        error: endpoint_error.as_ref(),
+        models: Vec::new(),
+        names: HashSet::new(),
+    };
+    let endpoints = gen.generate().map_err(error)?;
+    let models = &gen.models;
+    let path = path.to_string_lossy();
+
+    Ok(quote::quote! {
+        // recompile when the document changes
+        const _: &[u8] = include_bytes!(#path);
+
+        #(#models)*
+        #(#endpoints)*
+    })
+}
+
+struct Generator<'a> {
+    doc: &'a Value,
+    client: &'a Type,
+    /// The `error = Type` of the endpoints, if they do not use `__Endpoint_Error`.
+    error: Option<&'a Type>,
+    /// The generated models.
+    models: Vec<TokenStream>,
+    /// The names of the generated types.
+    names: HashSet<String>,
+}
+
+/// A field of a generated struct.
+struct FieldDef {
+    ident: Ident,
+    ty: TokenStream,
+    /// Whether the field is an `Option`.
+    optional: bool,
+    /// Whether the field is `#[required]` by the builder.
+    required: bool,
+    attrs: Vec<TokenStream>,
+}
+
+impl FieldDef {
+    fn to_tokens(&self) -> TokenStream {
+        let Self {
+            ident, ty, attrs, ..
+        } = self;
+        let ty = if self.optional {
+            quote::quote!(Option<#ty>)
+        } else {
+            ty.clone()
+        };
+        let required = self.required.then(|| quote::quote!(#[required]));
+
+        quote::quote!(#(#attrs)* #required pub #ident: #ty)
+    }
+}
+
+impl<'a> Generator<'a> {
+    /// Generate the models of the components, and return the endpoints of the operations.
+    fn generate(&mut self) -> Gen<Vec<TokenStream>> {
+        let version = str_of(self.doc.get("openapi")).unwrap_or_default();
+        if !version.starts_with('3') {
+            return Err(format!(
+                "only OpenAPI 3 documents are supported, found version `{version}`"
+            ));
+        }
+
+        let schemas = self
+            .doc
+            .get("components")
+            .and_then(|components| components.get("schemas"))
+            .and_then(Value::as_mapping);
+        if let Some(schemas) = schemas {
+            // inline models are named after their parents, so they must not take these names
+            self.names
+                .extend(schemas.keys().map(|name| pascal(&key(name))));
+            for (name, schema) in schemas {
+                self.model(&pascal(&key(name)), schema)?;
+            }
+        }
+
+        let mut endpoints = Vec::new();
+        let Some(paths) = self.doc.get("paths").and_then(Value::as_mapping) else {
+            return Ok(endpoints);
+        };
+        for (path, item) in paths {
+            let item = self.resolve(item)?;
+            let shared = seq_of(item.get("parameters"));
+            for method in METHODS {
+                if let Some(operation) = item.get(method) {
+                    endpoints.push(self.operation(&key(path), method, operation, shared)?);
+                }
+            }
+        }
+
+        Ok(endpoints)
+    }
+
+    /// Follow `value` if it is a reference to another part of the document.
+    fn resolve(&self, mut value: &'a Value) -> Gen<&'a Value> {
+        for _ in 0..32 {
+            let Some(reference) = ref_of(value) else {
+                return Ok(value);
+            };
+            let pointer = reference
+                .strip_prefix('#')
+                .ok_or_else(|| format!("external references are not supported: `{reference}`"))?;
+
+            value = self.doc;
+            for token in pointer.split('/').skip(1) {
+                let token = token.replace("~1", "/").replace("~0", "~");
+                value = match value {
+                    Value::Sequence(seq) => token.parse().ok().and_then(|idx: usize| seq.get(idx)),
+                    _ => value.get(token.as_str()),
+                }
+                .ok_or_else(|| format!("unresolved reference `{reference}`"))?;
+            }
+        }
+
+        Err("too many nested references".into())
+    }
+
+    /// A name for a new type based on `name`, unique among the generated types.
+    fn unique(&mut self, name: &str) -> String {
+        let mut unique = name.to_owned();
+        let mut idx = 1;
+        while !self.names.insert(unique.clone()) {
+            idx += 1;
+            unique = format!("{name}{idx}");
+        }
+        unique
+    }
+
+    /// The type of values of `schema`, generating a model named after `hint` for inline objects
+    /// and enums.
+    fn schema_type(&mut self, schema: &'a Value, hint: &str) -> Gen<TokenStream> {
+        if let Some(reference) = ref_of(schema) {
+            if let Some(name) = reference.strip_prefix("#/components/schemas/") {
+                let ident = type_ident(&pascal(name));
+                return Ok(quote::quote!(#ident));
+            }
+            return self.schema_type(self.resolve(schema)?, hint);
+        }
+
+        Ok(match kind(schema) {
+            _ if is_struct(schema) || is_enum(schema) => {
+                let name = self.unique(hint);
+                self.model(&name, schema)?;
+                let ident = type_ident(&name);
+                quote::quote!(#ident)
+            }
+            Some("string") if str_of(schema.get("format")) == Some("binary") => {
+                quote::quote!(Vec<u8>)
+            }
+            Some("string") => quote::quote!(String),
+            Some("integer") => match str_of(schema.get("format")) {
+                Some("int32") => quote::quote!(i32),
+                _ => quote::quote!(i64),
+            },
+            Some("number") => match str_of(schema.get("format")) {
+                Some("float") => quote::quote!(f32),
+                _ => quote::quote!(f64),
+            },
+            Some("boolean") => quote::quote!(bool),
+            Some("array") => {
+                let item = match schema.get("items") {
+                    Some(items) => self.value_type(items, &format!("{hint}Item"))?,
+                    None => quote::quote!(acril::serde_json::Value),
+                };
+                quote::quote!(Vec<#item>)
+            }
+            _ => match schema.get("additionalProperties") {
+                Some(values @ Value::Mapping(_)) => {
+                    let value = self.value_type(values, &format!("{hint}Value"))?;
+                    quote::quote!(std::collections::HashMap<String, #value>)
+                }
+                _ => quote::quote!(acril::serde_json::Value),
+            },
+        })
+    }
+
+    /// The type of the items of arrays and maps of `schema`, an `Option` if they are nullable.
+    fn value_type(&mut self, schema: &'a Value, hint: &str) -> Gen<TokenStream> {
+        let ty = self.schema_type(schema, hint)?;
+        Ok(if nullable(schema) {
+            quote::quote!(Option<#ty>)
+        } else {
+            ty
+        })
+    }
+
+    /// Generate the model `name` from `schema`: a struct for objects, an enum for string enums,
+    /// and a type alias otherwise.
+    fn model(&mut self, name: &str, schema: &'a Value) -> Gen<()> {
+        let ident = type_ident(name);
+        let docs = docs(schema);
+
+        if ref_of(schema).is_some() || !(is_struct(schema) || is_enum(schema)) {
+            let ty = self.schema_type(schema, name)?;
+            self.models.push(quote::quote! {
+                #(#docs)*
+                pub type #ident = #ty;
+            });
+            return Ok(());
+        }
+
+        if is_enum(schema) {
+            let mut taken = HashSet::new();
+            let variants = seq_of(schema.get("enum")).iter().filter_map(|value| {
+                let value = value.as_str()?;
+                let mut variant = pascal(value);
+                if variant.is_empty() {
+                    variant = "Empty".into();
+                }
+                while !taken.insert(variant.clone()) {
+                    variant.push('_');
+                }
+                let variant = type_ident(&variant);
+                Some(quote::quote!(#[serde(rename = #value)] #variant))
+            });
+
+            self.models.push(quote::quote! {
+                #(#docs)*
+                #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, acril::serde::Serialize, acril::serde::Deserialize)]
+                #[serde(crate = "acril::serde")]
+                pub enum #ident {
+                    #(#variants,)*
+                }
+            });
+            return Ok(());
+        }
+
+        let mut properties = Vec::new();
+        let mut required = HashSet::new();
+        self.properties(schema, &mut properties, &mut required)?;
+
+        let mut taken = HashSet::new();
+        let mut fields = Vec::new();
+        for (property, schema) in properties {
+            let mut ty = self.schema_type(schema, &format!("{name}{}", pascal(&property)))?;
+            // a struct cannot contain itself
+            if ident == ty.to_string() {
+                ty = quote::quote!(Box<#ty>);
+            }
+            let optional = !required.contains(&property) || nullable(schema);
+            let field = field_ident(&property, &mut taken);
+
+            let mut attrs = docs_of(schema);
+            attrs.push(rename(&field, &property));
+            if optional {
+                attrs.push(quote::quote! {
+                    #[serde(default, skip_serializing_if = "Option::is_none")]
+                });
+            }
+            fields.push(FieldDef {
+                ident: field,
+                ty,
+                optional,
+                required: false,
+                attrs,
+            });
+        }
+        let fields = fields.iter().map(FieldDef::to_tokens);
+
+        self.models.push(quote::quote! {
+            #(#docs)*
+            #[derive(Clone, Debug, acril::serde::Serialize, acril::serde::Deserialize)]
+            #[serde(crate = "acril::serde")]
+            pub struct #ident {
+                #(#fields,)*
+            }
+        });
+        Ok(())
+    }
+
+    /// Collect the properties of the object `schema` and of the schemas it is made of with
+    /// `allOf`, and the names of the required ones.
+    fn properties(
+        &self,
+        schema: &'a Value,
+        properties: &mut Vec<(String, &'a Value)>,
+        required: &mut HashSet<String>,
+    ) -> Gen<()> {
+        let schema = self.resolve(schema)?;
+        for part in seq_of(schema.get("allOf")) {
+            self.properties(part, properties, required)?;
+        }
+
+        if let Some(own) = schema.get("properties").and_then(Value::as_mapping) {
+            for (name, schema) in own {
+                let name = key(name);
+                if !properties.iter().any(|(other, _)| *other == name) {
+                    properties.push((name, schema));
+                }
+            }
+        }
+        required.extend(
+            seq_of(schema.get("required"))
+                .iter()
+                .filter_map(Value::as_str)
+                .map(String::from),
+        );
+
+        Ok(())
+    }
+
+    /// The endpoint of the operation at `path`, whose path item has the `shared` parameters.
+    fn operation(
+        &mut self,
+        path: &str,
+        method: &str,
+        operation: &'a Value,
+        shared: &'a [Value],
+    ) -> Gen<TokenStream> {
+        let operation_id = str_of(operation.get("operationId"))
+            .map(String::from)
+            .unwrap_or_else(|| format!("{method} {path}"));
+        let name = self.unique(&pascal(&operation_id));
+        let mut docs = docs(operation);
+
+        // the parameters of the operation override those of the path item
+        let mut parameters = Vec::<&Value>::new();
+        for parameter in shared.iter().chain(seq_of(operation.get("parameters"))) {
+            let parameter = self.resolve(parameter)?;
+            let id = parameter_id(parameter);
+            parameters.retain(|other| parameter_id(other) != id);
+            parameters.push(parameter);
+        }
+
+        let mut path = path.to_owned();
+        let mut taken = HashSet::new();
+        let mut fields = Vec::new();
+        for parameter in parameters {
+            let parameter_name = str_of(parameter.get("name"))
+                .ok_or_else(|| format!("a parameter of `{operation_id}` has no name"))?;
+            let location = str_of(parameter.get("in")).unwrap_or_default();
+            let schema = parameter.get("schema");
+            let required = location == "path" || parameter.get("required") == Some(&true.into());
+            let optional = !required || schema.is_some_and(nullable);
+            let ty = match schema {
+                Some(schema) => {
+                    self.schema_type(schema, &format!("{name}{}", pascal(parameter_name)))?
+                }
+                None => quote::quote!(String),
+            };
+            let field = field_ident(parameter_name, &mut taken);
+
+            let mut attrs = docs_of(parameter);
+            match location {
+                "path" => {
+                    path = path.replace(&format!("{{{parameter_name}}}"), &format!("{{{field}}}"));
+                    attrs.push(quote::quote!(#[path_param]));
+                }
+                "query" => {
+                    attrs.push(quote::quote!(#[query]));
+                    attrs.push(rename(&field, parameter_name));
+                    if optional {
+                        attrs
+                            .push(quote::quote!(#[serde(skip_serializing_if = "Option::is_none")]));
+                    }
+                }
+                "header" => attrs.push(quote::quote!(#[header(#parameter_name)])),
+                // cookies are set by the client
+                _ => continue,
+            }
+            fields.push(FieldDef {
+                ident: field,
+                ty,
+                optional,
+                required,
+                attrs,
+            });
+        }
+
+        let mut input = quote::quote!(empty);
+        if let Some(body) = operation.get("requestBody") {
+            let body = self.resolve(body)?;
+            let required = body.get("required") == Some(&true.into());
+            let content = body.get("content").and_then(Value::as_mapping);
+            let media = |wanted: fn(&str) -> bool| {
+                content?
+                    .iter()
+                    .find(|(mime, _)| wanted(&key(mime)))
+                    .map(|(_, media)| media.get("schema"))
+            };
+
+            if let Some(schema) = media(is_json) {
+                let ty = match schema {
+                    Some(schema) => self.schema_type(schema, &format!("{name}Body"))?,
+                    None => quote::quote!(acril::serde_json::Value),
+                };
+                input = quote::quote!(json);
+                fields.push(FieldDef {
+                    ident: field_ident("body", &mut taken),
+                    ty,
+                    optional: !required,
+                    required,
+                    attrs: vec![quote::quote!(#[body(whole)])],
+                });
+            } else if let Some((mode, schema)) = media(|mime| mime == "multipart/form-data")
+                .map(|schema| (quote::quote!(multipart), schema))
+                .or_else(|| {
+                    media(|mime| mime == "application/x-www-form-urlencoded")
+                        .map(|schema| (quote::quote!(form), schema))
+                })
+            {
+                let multipart = mode.to_string() == "multipart";
+                input = mode;
+
+                let mut properties = Vec::new();
+                let mut required_properties = HashSet::new();
+                if let Some(schema) = schema {
+                    self.properties(schema, &mut properties, &mut required_properties)?;
+                }
+                for (property, schema) in properties {
+                    let ty = self.schema_type(schema, &format!("{name}{}", pascal(&property)))?;
+                    let required = required && required_properties.contains(&property);
+                    let field = field_ident(&property, &mut taken);
+
+                    let mut attrs = docs_of(schema);
+                    attrs.push(quote::quote!(#[body]));
+                    attrs.push(rename(&field, &property));
+                    if multipart && ty.to_string() == quote::quote!(Vec<u8>).to_string() {
+                        attrs.push(quote::quote!(#[file]));
+                    } else if !required {
+                        attrs
+                            .push(quote::quote!(#[serde(skip_serializing_if = "Option::is_none")]));
+                    }
+                    fields.push(FieldDef {
+                        ident: field,
+                        ty,
+                        optional: !required || nullable(schema),
+                        required,
+                        attrs,
+                    });
+                }
+            } else {
+                let mimes = content
+                    .into_iter()
+                    .flatten()
+                    .map(|(mime, _)| format!("`{}`", key(mime)))
+                    .collect::<Vec<_>>();
+                let note = format!(
+                    " The request body ({}) is not supported, and is not sent.",
+                    mimes.join(", ")
+                );
+                docs.push(quote::quote!(#[doc = ""]));
+                docs.push(quote::quote!(#[doc = #note]));
+            }
+        }
+
+        let (output_mode, output) = self.output(operation, &name)?;
+
+        let ident = type_ident(&name);
+        let builder = field_ident(&name, &mut HashSet::new());
+        let method = type_ident(&pascal(method));
+        let client = self.client;
+        let error = self.error.map(|error| quote::quote!(, error = #error));
+        let body = if fields.is_empty() {
+            quote::quote!(;)
+        } else {
+            let fields = fields.iter().map(FieldDef::to_tokens);
+            quote::quote!({ #(#fields,)* })
+        };
+
+        Ok(quote::quote! {
+            #(#docs)*
+            #[acril::http::client::with_builder(#builder)]
+            #[derive(Clone, Debug, acril::http::client::ClientEndpoint)]
+            #[endpoint(#method(#input, #output_mode) #path in #client -> #output #error)]
+            pub struct #ident #body
+        })
+    }
+
+    /// The output mode and type of `operation`, from its first success response, or its default
+    /// response.
+    fn output(&mut self, operation: &'a Value, name: &str) -> Gen<(TokenStream, TokenStream)> {
+        let responses = operation.get("responses").and_then(Value::as_mapping);
+        let mut success = responses
+            .into_iter()
+            .flatten()
+            .filter(|(status, _)| key(status).starts_with('2'))
+            .collect::<Vec<_>>();
+        success.sort_by_key(|(status, _)| key(status));
+        let response = success.first().map(|(_, response)| *response).or_else(|| {
+            responses?
+                .iter()
+                .find(|(status, _)| key(status) == "default")
+                .map(|(_, response)| response)
+        });
+
+        let Some(response) = response else {
+            return Ok((quote::quote!(empty), quote::quote!(())));
+        };
+        let response = self.resolve(response)?;
+        let Some((mime, media)) = response
+            .get("content")
+            .and_then(Value::as_mapping)
+            .and_then(|content| {
+                content
+                    .iter()
+                    .find(|(mime, _)| is_json(&key(mime)))
+                    .or_else(|| content.iter().next())
+            })
+        else {
+            return Ok((quote::quote!(empty), quote::quote!(())));
+        };
+
+        let mime = key(mime);
+        Ok(if is_json(&mime) {
+            let ty = match media.get("schema") {
+                Some(schema) => self.schema_type(schema, &format!("{name}Response"))?,
+                None => quote::quote!(acril::serde_json::Value),
+            };
+            (quote::quote!(json), ty)
+        } else if mime.starts_with("text/") {
+            (quote::quote!(display), quote::quote!(String))
+        } else {
+            (quote::quote!(bytes), quote::quote!(Vec<u8>))
+        })
+    }
+}
+
+/// A key of a mapping as a string, such as the status codes of responses, which YAML parses as
+/// numbers.
+fn key(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn str_of(value: Option<&Value>) -> Option<&str> {
+    value?.as_str()
+}
+
+fn seq_of(value: Option<&Value>) -> &[Value] {
+    value
+        .and_then(Value::as_sequence)
+        .map_or(&[], Vec::as_slice)
+}
+
+/// The name and location of a parameter, which identify it.
+fn parameter_id(parameter: &Value) -> (Option<&str>, Option<&str>) {
+    (str_of(parameter.get("name")), str_of(parameter.get("in")))
+}
+
+fn ref_of(value: &Value) -> Option<&String> {
+    match value.get("$ref")? {
+        Value::String(reference) => Some(reference),
+        _ => None,
+    }
+}
+
+/// The type of `schema`, ignoring `null` in OpenAPI 3.1 type lists.
+fn kind(schema: &Value) -> Option<&str> {
+    match schema.get("type")? {
+        Value::String(kind) => Some(kind),
+        Value::Sequence(kinds) => kinds
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|kind| *kind != "null"),
+        _ => None,
+    }
+}
+
+fn nullable(schema: &Value) -> bool {
+    schema.get("nullable") == Some(&true.into())
+        || seq_of(schema.get("type")).iter().any(|kind| kind == "null")
+}
+
+/// Whether `schema` is generated as a struct.
+fn is_struct(schema: &Value) -> bool {
+    schema.get("properties").is_some() || schema.get("allOf").is_some()
+}
+
+/// Whether `schema` is generated as an enum.
+fn is_enum(schema: &Value) -> bool {
+    matches!(kind(schema), Some("string") | None)
+        && !seq_of(schema.get("enum")).is_empty()
+        && seq_of(schema.get("enum")).iter().all(Value::is_string)
+}
+
+fn is_json(mime: &str) -> bool {
+    let essence = mime.split(';').next().unwrap_or_default().trim();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+/// The doc attributes of the title, summary and description of `value`.
+fn docs(value: &Value) -> Vec<TokenStream> {
+    let mut docs = Vec::new();
+    for text in ["title", "summary", "description"]
+        .into_iter()
+        .filter_map(|key| str_of(value.get(key)))
+    {
+        if !docs.is_empty() {
+            docs.push(quote::quote!(#[doc = ""]));
+        }
+        docs.extend(text.trim().lines().map(|line| {
+            let line = format!(" {line}");
+            quote::quote!(#[doc = #line])
+        }));
+    }
+    docs
+}
+
+/// The doc attributes of `value`, following references to other schemas.
+fn docs_of(value: &Value) -> Vec<TokenStream> {
+    if ref_of(value).is_some() {
+        Vec::new()
+    } else {
+        docs(value)
+    }
+}
+
+fn rename(field: &Ident, name: &str) -> TokenStream {
+    if *field == name {
+        quote::quote!()
+    } else {
+        quote::quote!(#[serde(rename = #name)])
+    }
+}
+
+/// An identifier for a field or function named after `name`, unique among `taken`.
+fn field_ident(name: &str, taken: &mut HashSet<String>) -> Ident {
+    let mut field = snake(name);
+    if field.is_empty() || field.starts_with(|c: char| c.is_ascii_digit()) {
+        field.insert(0, '_');
+    }
+    if syn::parse_str::<Ident>(&field).is_err() {
+        field.push('_');
+    }
+    while !taken.insert(field.clone()) {
+        field.push('_');
+    }
+    Ident::new(&field, Span::call_site())
+}
+
+/// An identifier for a type or variant named `name`, in `PascalCase`.
+fn type_ident(name: &str) -> Ident {
+    let mut name = name.to_owned();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, 'V');
+    }
+    if syn::parse_str::<Ident>(&name).is_err() {
+        name.push('_');
+    }
+    Ident::new(&name, Span::call_site())
+}
+
+/// `name` in `snake_case`, splitting words at separators and at changes of case.
+fn snake(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::new();
+    for (idx, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !snake.ends_with('_') {
+                snake.push('_');
+            }
+            continue;
+        }
+
+        let prev = idx.checked_sub(1).map(|idx| chars[idx]);
+        let next = chars.get(idx + 1);
+        let word_start = c.is_ascii_uppercase()
+            && prev.is_some_and(|prev| {
+                prev.is_ascii_lowercase()
+                    || prev.is_ascii_digit()
+                    || (prev.is_ascii_uppercase() && next.is_some_and(char::is_ascii_lowercase))
+            });
+        if word_start && !snake.ends_with('_') {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake.trim_matches('_').to_owned()
+}
+
+/// `name` in `PascalCase`.
+pub(crate) fn pascal(name: &str) -> String {
+    snake(name)
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_case() {
+        assert_eq!(snake("listPets"), "list_pets");
+        assert_eq!(snake("X-Request-ID"), "x_request_id");
+        assert_eq!(snake("HTTPServer2Config"), "http_server2_config");
+        assert_eq!(pascal("get /pets/{petId}"), "GetPetsPetId");
+        assert_eq!(pascal("pet_status"), "PetStatus");
+
+        let mut taken = HashSet::new();
+        assert_eq!(field_ident("type", &mut taken), "type_");
+        assert_eq!(field_ident("Type", &mut taken), "type__");
+        assert_eq!(field_ident("2fa", &mut taken), "_2fa");
+        assert_eq!(type_ident("200"), "V200");
+    }
+}
@@ -2,5 +2,63 @@ pub use http_types::{self, Method, Request, Response, StatusCode};
 
 use crate::Service;
 
+pub mod auth;
+pub mod blocking;
+pub mod cassette;
 pub mod client;
+pub mod cookies;
+#[cfg(feature = "compression")]
+pub mod decompression;
+pub mod limit;
+pub mod mock;
+pub mod multipart;
+pub mod paginate;
+#[cfg(feature = "signing")]
+pub mod signing;
+pub mod stream;
+/// Define the error type of the endpoints of the module which do not set their own with the
+/// `error = Type` option of `#[endpoint(...)]`.
+///
+/// ```ignore
+/// endpoint_error!(http_types::Error);
+///
+/// #[derive(ClientEndpoint)]
+/// #[endpoint(Get "/orders" in MyClient -> Vec<Order>)]
+/// struct ListOrders;
+///
+/// #[derive(ClientEndpoint)]
+/// #[endpoint(Get "/account" in MyClient -> Account, error = MyError)]
+/// struct GetAccount;
+/// ```
+///
+/// Errors are converted to the error of an endpoint with `From`, from `http_types::Error` for the
+/// errors encoding the request and decoding the response, and from the error of the client. With
+/// `api_error = Type`, the error is an [`EndpointError`](client::EndpointError), or converted from
+/// it with `error = Type`.
 pub use acril_macros::endpoint_error;
+/// Generate endpoints from an OpenAPI 3 document, in YAML or JSON, read at compile time from a
+/// path relative to the manifest of the crate.
+///
+/// ```ignore
+/// pub struct PetStore(HttpClient);
+///
+/// impl HttpClientContext for PetStore {
+///     // ...
+/// }
+///
+/// endpoint_error!(http_types::Error);
+/// openapi!("petstore.yaml", client = PetStore);
+/// // or, without `endpoint_error!`
+/// openapi!("petstore.yaml", client = PetStore, error = MyError);
+///
+/// let pets = petstore.list_pets().limit(10).execute().await?;
+/// ```
+///
+/// Each operation becomes an endpoint named after its `operationId`, with its parameters as
+/// `#[path_param]`, `#[query]` and `#[header(...)]` fields, its JSON, form or multipart body, and a
+/// `#[with_builder]` method on the client. The schemas of the components, and the inline objects
+/// and string enums, become models. Cookie parameters and external references are not supported,
+/// and `oneOf` and `anyOf` schemas become `serde_json::Value`.
+///
+/// The generated code expects the same items in scope as hand-written endpoints.
+pub use acril_macros::openapi;
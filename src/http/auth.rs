@@ -0,0 +1,718 @@
+//! An authentication middleware, adding credentials from a [`CredentialProvider`] to every request.
+
+use std::time::{Duration, Instant};
+
+use http_types::{
+    auth::{AuthenticationScheme, Authorization, BasicAuth},
+    Body, Method, Request, Response, StatusCode, Url,
+};
+use serde::Deserialize;
+
+use super::client::Middleware;
+use crate::{Handler, Layer, Service};
+
+/// A source of credentials for [`Auth`].
+pub trait CredentialProvider {
+    /// Add credentials to `request`. `inner` is the middleware wrapped by [`Auth`], which can be
+    /// used to make requests for obtaining the credentials.
+    async fn authorize<M>(&mut self, request: &mut Request, inner: &mut M) -> Result<(), M::Error>
+    where
+        M: Middleware,
+        M::Error: From<http_types::Error>;
+
+    /// Whether [`refresh`](Self::refresh) can ever produce new credentials.
+    ///
+    /// Requests sent through [`Auth`] with a refreshable provider have their bodies buffered, so
+    /// that they can be retried, up to [`MAX_RETRY_BODY`] bytes.
+    fn can_refresh(&self) -> bool {
+        false
+    }
+
+    /// Obtain new credentials after the current ones were rejected with `401 Unauthorized`.
+    ///
+    /// Returns whether the request should be retried.
+    async fn refresh<M>(&mut self, _inner: &mut M) -> Result<bool, M::Error>
+    where
+        M: Middleware,
+        M::Error: From<http_types::Error>,
+    {
+        Ok(false)
+    }
+
+    /// Forget the current credentials after they were rejected with `401 Unauthorized` for a
+    /// request that cannot be retried, so that the next request obtains new ones.
+    fn invalidate(&mut self) {}
+}
+
+/// A static bearer token, sent in the `Authorization` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bearer(pub String);
+
+impl CredentialProvider for Bearer {
+    async fn authorize<M>(&mut self, request: &mut Request, _inner: &mut M) -> Result<(), M::Error>
+    where
+        M: Middleware,
+        M::Error: From<http_types::Error>,
+    {
+        Authorization::new(AuthenticationScheme::Bearer, self.0.clone()).apply(request);
+        Ok(())
+    }
+}
+
+/// A username and password, sent in the `Authorization` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Basic {
+    pub username: String,
+    pub password: String,
+}
+
+impl Basic {
+    /// Create a new instance.
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
+
+impl CredentialProvider for Basic {
+    async fn authorize<M>(&mut self, request: &mut Request, _inner: &mut M) -> Result<(), M::Error>
+    where
+        M: Middleware,
+        M::Error: From<http_types::Error>,
+    {
+        BasicAuth::new(&self.username, &self.password).apply(request);
+        Ok(())
+    }
+}
+
+/// Where an [`ApiKey`] is sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiKeyLocation {
+    /// A request header.
+    Header,
+    /// A query parameter in the request URL.
+    Query,
+}
+
+/// An API key, sent in a header or a query parameter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiKey {
+    pub name: String,
+    pub value: String,
+    pub location: ApiKeyLocation,
+}
+
+impl ApiKey {
+    /// An API key sent in the header `name`.
+    pub fn header(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            location: ApiKeyLocation::Header,
+        }
+    }
+
+    /// An API key sent in the query parameter `name`.
+    pub fn query(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            location: ApiKeyLocation::Query,
+        }
+    }
+}
+
+impl CredentialProvider for ApiKey {
+    async fn authorize<M>(&mut self, request: &mut Request, _inner: &mut M) -> Result<(), M::Error>
+    where
+        M: Middleware,
+        M::Error: From<http_types::Error>,
+    {
+        match self.location {
+            ApiKeyLocation::Header => {
+                request.insert_header(self.name.as_str(), self.value.as_str());
+            }
+            ApiKeyLocation::Query => {
+                request
+                    .url_mut()
+                    .query_pairs_mut()
+                    .append_pair(&self.name, &self.value);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Grant {
+    ClientCredentials,
+    RefreshToken(String),
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct CachedToken {
+    access_token: String,
+    expires_at: Option<Instant>,
+}
+
+/// OAuth 2.0 access tokens, obtained with the client credentials or refresh token grants and sent
+/// as bearer tokens.
+///
+/// Tokens are cached, and requested again once they are about to expire (see
+/// [`refresh_before`](OAuth2::refresh_before)) or get rejected by the server. The client
+/// authenticates to the token endpoint with HTTP basic authentication if it has a secret.
+#[derive(Clone, Debug)]
+pub struct OAuth2 {
+    token_url: Url,
+    client_id: String,
+    client_secret: Option<String>,
+    grant: Grant,
+    scopes: Vec<String>,
+    refresh_before: Duration,
+    token: Option<CachedToken>,
+}
+
+impl OAuth2 {
+    fn new(token_url: Url, client_id: String, client_secret: Option<String>, grant: Grant) -> Self {
+        Self {
+            token_url,
+            client_id,
+            client_secret,
+            grant,
+            scopes: Vec::new(),
+            refresh_before: Duration::from_secs(30),
+            token: None,
+        }
+    }
+
+    /// Use the client credentials grant.
+    pub fn client_credentials(
+        token_url: Url,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            token_url,
+            client_id.into(),
+            Some(client_secret.into()),
+            Grant::ClientCredentials,
+        )
+    }
+
+    /// Use the refresh token grant. If the token endpoint issues a new refresh token, it replaces
+    /// `refresh_token` for subsequent refreshes.
+    pub fn refresh_token(
+        token_url: Url,
+        client_id: impl Into<String>,
+        client_secret: Option<String>,
+        refresh_token: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            token_url,
+            client_id.into(),
+            client_secret,
+            Grant::RefreshToken(refresh_token.into()),
+        )
+    }
+
+    /// Request a scope for the access tokens.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// How long before its expiry a cached token is replaced. Defaults to 30 seconds.
+    pub fn refresh_before(mut self, refresh_before: Duration) -> Self {
+        self.refresh_before = refresh_before;
+        self
+    }
+
+    /// The current refresh token, if using the refresh token grant.
+    pub fn get_refresh_token(&self) -> Option<&str> {
+        match &self.grant {
+            Grant::RefreshToken(token) => Some(token),
+            Grant::ClientCredentials => None,
+        }
+    }
+
+    async fn fetch_token<M>(&mut self, inner: &mut M) -> Result<(), M::Error>
+    where
+        M: Middleware,
+        M::Error: From<http_types::Error>,
+    {
+        let mut form = match &self.grant {
+            Grant::ClientCredentials => vec![("grant_type", "client_credentials".to_owned())],
+            Grant::RefreshToken(token) => vec![
+                ("grant_type", "refresh_token".to_owned()),
+                ("refresh_token", token.clone()),
+            ],
+        };
+        if !self.scopes.is_empty() {
+            form.push(("scope", self.scopes.join(" ")));
+        }
+
+        let mut request = Request::new(Method::Post, self.token_url.clone());
+        match &self.client_secret {
+            Some(secret) => BasicAuth::new(&self.client_id, secret).apply(&mut request),
+            None => form.push(("client_id", self.client_id.clone())),
+        }
+        request.set_body(Body::from_form(&form)?);
+
+        let issued_at = Instant::now();
+        let mut response = inner.call(request, &mut ()).await?;
+        if !response.status().is_success() {
+            let body = response.body_string().await.unwrap_or_default();
+            return Err(http_types::Error::from_str(
+                response.status(),
+                format!("token request failed: {body}"),
+            )
+            .into());
+        }
+
+        let token: TokenResponse = response.body_json().await?;
+        if let (Grant::RefreshToken(current), Some(new)) = (&mut self.grant, token.refresh_token) {
+            *current = new;
+        }
+        self.token = Some(CachedToken {
+            access_token: token.access_token,
+            expires_at: token
+                .expires_in
+                .map(|secs| issued_at + Duration::from_secs(secs)),
+        });
+
+        Ok(())
+    }
+}
+
+impl CredentialProvider for OAuth2 {
+    async fn authorize<M>(&mut self, request: &mut Request, inner: &mut M) -> Result<(), M::Error>
+    where
+        M: Middleware,
+        M::Error: From<http_types::Error>,
+    {
+        let fresh = self
+            .token
+            .as_ref()
+            .is_some_and(|token| match token.expires_at {
+                Some(at) => Instant::now() + self.refresh_before < at,
+                None => true,
+            });
+        if !fresh {
+            self.fetch_token(inner).await?;
+        }
+
+        let token = self.token.as_ref().expect("a token was just fetched");
+        Authorization::new(AuthenticationScheme::Bearer, token.access_token.clone()).apply(request);
+        Ok(())
+    }
+
+    fn can_refresh(&self) -> bool {
+        true
+    }
+
+    async fn refresh<M>(&mut self, inner: &mut M) -> Result<bool, M::Error>
+    where
+        M: Middleware,
+        M::Error: From<http_types::Error>,
+    {
+        self.fetch_token(inner).await?;
+        Ok(true)
+    }
+
+    fn invalidate(&mut self) {
+        self.token = None;
+    }
+}
+
+/// A [`Layer`] wrapping a middleware in [`Auth`], each with a clone of the provider.
+#[derive(Clone, Debug)]
+pub struct AuthLayer<P>(pub P);
+
+impl<M, P: Clone> Layer<M> for AuthLayer<P> {
+    type Service = Auth<M, P>;
+
+    fn wrap(&self, inner: M) -> Self::Service {
+        Auth::new(inner, self.0.clone())
+    }
+}
+
+/// The largest request body buffered in memory to retry the request.
+pub const MAX_RETRY_BODY: usize = 1024 * 1024;
+
+/// A middleware that adds credentials from `P` to requests.
+///
+/// If the server responds with `401 Unauthorized` and the provider can
+/// [`refresh`](CredentialProvider::refresh) its credentials, the request is retried once.
+///
+/// To be retried, a request body is read into memory before the request is sent. Only bodies of
+/// known length up to [`MAX_RETRY_BODY`] bytes are, and requests with streamed or larger bodies
+/// are not retried. Their `401 Unauthorized` responses still
+/// [invalidate](CredentialProvider::invalidate) the credentials, which the next request renews. The retried request has the extensions defined by this crate, like
+/// `NoDecompression`, but not others, which cannot be cloned.
+#[derive(Clone, Debug)]
+pub struct Auth<M, P> {
+    inner: M,
+    provider: P,
+}
+
+impl<M, P> Auth<M, P> {
+    /// Wrap `inner`, authorizing requests with `provider`.
+    pub fn new(inner: M, provider: P) -> Self {
+        Self { inner, provider }
+    }
+
+    /// The wrapped middleware.
+    pub fn get_inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// The credential provider.
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// The credential provider, mutably.
+    pub fn provider_mut(&mut self) -> &mut P {
+        &mut self.provider
+    }
+}
+
+impl<M: Middleware, P> Service for Auth<M, P> {
+    type Context = ();
+    type Error = M::Error;
+}
+
+impl<M, P> Handler<Request> for Auth<M, P>
+where
+    M: Middleware,
+    M::Error: From<http_types::Error>,
+    P: CredentialProvider,
+{
+    type Response = Response;
+
+    async fn call(
+        &mut self,
+        mut request: Request,
+        cx: &mut Self::Context,
+    ) -> Result<Self::Response, Self::Error> {
+        let retry = match request.len() {
+            Some(len) if self.provider.can_refresh() && len <= MAX_RETRY_BODY => {
+                let body = request.take_body();
+                let mime = body.mime().clone();
+                let bytes = body.into_bytes().await?;
+
+                let mut retry = retry_of(&request);
+                let mut body = Body::from_bytes(bytes.clone());
+                body.set_mime(mime.clone());
+                request.set_body(body);
+                let mut body = Body::from_bytes(bytes);
+                body.set_mime(mime);
+                retry.set_body(body);
+
+                Some(retry)
+            }
+            _ => None,
+        };
+
+        self.provider
+            .authorize(&mut request, &mut self.inner)
+            .await?;
+        let response = self.inner.call(request, cx).await?;
+
+        if response.status() != StatusCode::Unauthorized {
+            return Ok(response);
+        }
+        match retry {
+            Some(mut retry) if self.provider.refresh(&mut self.inner).await? => {
+                self.provider.authorize(&mut retry, &mut self.inner).await?;
+                self.inner.call(retry, cx).await
+            }
+            Some(_) => Ok(response),
+            None => {
+                self.provider.invalidate();
+                Ok(response)
+            }
+        }
+    }
+}
+
+/// A copy of `request` without its body, to retry it.
+///
+/// The extensions of a request cannot be cloned, so only those defined by this crate are copied.
+fn retry_of(request: &Request) -> Request {
+    let mut retry = Request::new(request.method(), request.url().clone());
+    for (name, values) in request.iter() {
+        retry.insert_header(name, values);
+    }
+    retry.set_version(request.version());
+    retry.set_peer_addr(request.peer_addr());
+    retry.set_local_addr(request.local_addr());
+
+    #[cfg(feature = "compression")]
+    if let Some(&marker) = request.ext().get::<super::decompression::NoDecompression>() {
+        retry.ext_mut().insert(marker);
+    }
+
+    retry
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::executor::block_on;
+    use http_types::{auth::AuthenticationScheme, Method};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct State {
+        issued: usize,
+        expires_in: u64,
+        refresh_tokens: Vec<String>,
+    }
+
+    /// A token endpoint at `/token`, and a resource at `/resource` echoing the request body if
+    /// the request carries the most recently issued token.
+    #[derive(Clone, Default)]
+    struct TokenServer(Arc<Mutex<State>>);
+
+    impl Service for TokenServer {
+        type Context = ();
+        type Error = http_types::Error;
+    }
+
+    impl Handler<Request> for TokenServer {
+        type Response = Response;
+
+        async fn call(&mut self, mut request: Request, _: &mut ()) -> http_types::Result<Response> {
+            let mut response = Response::new(StatusCode::Ok);
+
+            match request.url().path() {
+                "/token" => {
+                    let form: Vec<(String, String)> = request.body_form().await?;
+                    let mut state = self.0.lock().unwrap();
+                    if let Some((_, token)) = form.iter().find(|(k, _)| k == "refresh_token") {
+                        state.refresh_tokens.push(token.clone());
+                    } else {
+                        let auth = BasicAuth::from_headers(&request)?.unwrap();
+                        assert_eq!((auth.username(), auth.password()), ("id", "secret"));
+                    }
+
+                    state.issued += 1;
+                    response.set_body(format!(
+                        r#"{{"access_token":"token-{0}","token_type":"Bearer","expires_in":{1},"refresh_token":"refresh-{0}"}}"#,
+                        state.issued, state.expires_in,
+                    ));
+                }
+                "/resource" => {
+                    let expected = format!("token-{}", self.0.lock().unwrap().issued);
+                    match Authorization::from_headers(&request)? {
+                        Some(auth)
+                            if auth.scheme() == AuthenticationScheme::Bearer
+                                && auth.credentials() == expected =>
+                        {
+                            response.set_body(request.take_body());
+                        }
+                        _ => response.set_status(StatusCode::Unauthorized),
+                    }
+                }
+                _ => response.set_status(StatusCode::NotFound),
+            }
+
+            Ok(response)
+        }
+    }
+
+    fn resource(body: &str) -> Request {
+        let mut request = Request::new(
+            Method::Post,
+            Url::parse("http://localhost/resource").unwrap(),
+        );
+        request.set_body(body);
+        request
+    }
+
+    fn client_credentials(server: &TokenServer) -> Auth<TokenServer, OAuth2> {
+        Auth::new(
+            server.clone(),
+            OAuth2::client_credentials(
+                Url::parse("http://localhost/token").unwrap(),
+                "id",
+                "secret",
+            ),
+        )
+    }
+
+    #[test]
+    fn caches_token() {
+        block_on(async {
+            let server = TokenServer::default();
+            server.0.lock().unwrap().expires_in = 3600;
+            let mut auth = client_credentials(&server);
+
+            for body in ["a", "b"] {
+                let mut response = auth.call(resource(body), &mut ()).await.unwrap();
+                assert_eq!(response.body_string().await.unwrap(), body);
+            }
+            assert_eq!(server.0.lock().unwrap().issued, 1);
+        })
+    }
+
+    #[test]
+    fn refreshes_before_expiry() {
+        block_on(async {
+            let server = TokenServer::default();
+            server.0.lock().unwrap().expires_in = 10;
+            let mut auth = client_credentials(&server);
+
+            for _ in 0..2 {
+                let response = auth.call(resource(""), &mut ()).await.unwrap();
+                assert_eq!(response.status(), StatusCode::Ok);
+            }
+            assert_eq!(server.0.lock().unwrap().issued, 2);
+        })
+    }
+
+    #[test]
+    fn retries_once_on_unauthorized() {
+        block_on(async {
+            let server = TokenServer::default();
+            server.0.lock().unwrap().expires_in = 3600;
+            let mut auth = client_credentials(&server);
+            auth.call(resource(""), &mut ()).await.unwrap();
+
+            // revoke the cached token
+            server.0.lock().unwrap().issued += 1;
+
+            let mut response = auth.call(resource("retried"), &mut ()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.body_string().await.unwrap(), "retried");
+            assert_eq!(server.0.lock().unwrap().issued, 3);
+        })
+    }
+
+    #[test]
+    fn invalidates_token_of_unretried_requests() {
+        block_on(async {
+            let server = TokenServer::default();
+            server.0.lock().unwrap().expires_in = 3600;
+            let mut auth = client_credentials(&server);
+            auth.call(resource(""), &mut ()).await.unwrap();
+            server.0.lock().unwrap().issued += 1;
+
+            let mut request = resource("");
+            let reader = futures::io::BufReader::new(&b"streamed"[..]);
+            request.set_body(Body::from_reader(reader, None));
+            let response = auth.call(request, &mut ()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Unauthorized);
+            assert_eq!(server.0.lock().unwrap().issued, 2);
+
+            // the rejected token is not reused
+            let response = auth.call(resource(""), &mut ()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(server.0.lock().unwrap().issued, 3);
+
+            server.0.lock().unwrap().issued += 1;
+            let large = "a".repeat(MAX_RETRY_BODY + 1);
+            let response = auth.call(resource(&large), &mut ()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Unauthorized);
+            let response = auth.call(resource(""), &mut ()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(server.0.lock().unwrap().issued, 5);
+        })
+    }
+
+    #[test]
+    fn retries_keep_the_request() {
+        let mut request = resource("");
+        request.insert_header("x-request-id", "1");
+        request.set_version(Some(http_types::Version::Http1_0));
+        #[cfg(feature = "compression")]
+        request
+            .ext_mut()
+            .insert(crate::http::decompression::NoDecompression);
+
+        let retry = retry_of(&request);
+        assert_eq!(retry.method(), Method::Post);
+        assert_eq!(retry.url(), request.url());
+        assert_eq!(retry.header("x-request-id").unwrap(), "1");
+        assert_eq!(retry.version(), Some(http_types::Version::Http1_0));
+        #[cfg(feature = "compression")]
+        assert!(retry
+            .ext()
+            .get::<crate::http::decompression::NoDecompression>()
+            .is_some());
+    }
+
+    #[test]
+    fn rotates_refresh_token() {
+        block_on(async {
+            let server = TokenServer::default();
+            server.0.lock().unwrap().expires_in = 3600;
+            let mut auth = Auth::new(
+                server.clone(),
+                OAuth2::refresh_token(
+                    Url::parse("http://localhost/token").unwrap(),
+                    "id",
+                    None,
+                    "refresh-0",
+                ),
+            );
+
+            auth.call(resource(""), &mut ()).await.unwrap();
+            server.0.lock().unwrap().issued += 1;
+            let response = auth.call(resource(""), &mut ()).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(
+                server.0.lock().unwrap().refresh_tokens,
+                ["refresh-0", "refresh-1"]
+            );
+            assert_eq!(auth.provider().get_refresh_token(), Some("refresh-3"));
+        })
+    }
+
+    #[test]
+    fn static_credentials() {
+        block_on(async {
+            let server = TokenServer::default();
+            let mut request = resource("");
+
+            Bearer(String::from("token"))
+                .authorize(&mut request, &mut server.clone())
+                .await
+                .unwrap();
+            assert_eq!(request.header("authorization").unwrap(), "Bearer token");
+
+            Basic::new("user", "pass")
+                .authorize(&mut request, &mut server.clone())
+                .await
+                .unwrap();
+            assert_eq!(
+                request.header("authorization").unwrap(),
+                "Basic dXNlcjpwYXNz"
+            );
+
+            ApiKey::header("X-Api-Key", "key")
+                .authorize(&mut request, &mut server.clone())
+                .await
+                .unwrap();
+            ApiKey::query("api_key", "key")
+                .authorize(&mut request, &mut server.clone())
+                .await
+                .unwrap();
+            assert_eq!(request.header("x-api-key").unwrap(), "key");
+            assert_eq!(request.url().query(), Some("api_key=key"));
+        })
+    }
+}
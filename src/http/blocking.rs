@@ -0,0 +1,196 @@
+//! A blocking client, for programs which do not run an async runtime.
+//!
+//! [`HttpClient`] wraps a client, usually an async [`client::HttpClient`], and runs its endpoints
+//! to completion on the current thread. The endpoints are the same, so they serve async and
+//! blocking callers alike:
+//!
+//! ```ignore
+//! let mut client = blocking::HttpClient::new().with_base_url(base_url);
+//! let order = client.call(GetOrder { id: 1 })?;
+//! ```
+//!
+//! The client must not be used from async code, where it would block the executor.
+
+use std::{
+    future::Future,
+    ops::{Deref, DerefMut},
+};
+
+use http_types::Url;
+
+use super::{
+    client::{self, ClientEndpoint, DefaultMiddleware, HttpClientContext, Middleware},
+    paginate::{self, PaginatedEndpoint},
+    *,
+};
+
+/// Run `future` to completion on the current thread. This is the executor of [`HttpClient`] and
+/// of the `execute_blocking` methods generated by `#[with_builder(method, blocking)]`.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    futures::executor::block_on(future)
+}
+
+/// A client running endpoints and requests to completion, wrapping the client `C`.
+///
+/// It dereferences to `C`, so the methods generated by `#[with_builder]` on `C` are available.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct HttpClient<C = client::HttpClient<DefaultMiddleware>> {
+    inner: C,
+}
+
+impl HttpClient {
+    pub fn new() -> Self {
+        Self {
+            inner: client::HttpClient::new(),
+        }
+    }
+}
+
+impl<M: Middleware> HttpClient<client::HttpClient<M>> {
+    pub fn new_with(middleware: M) -> Self {
+        Self {
+            inner: client::HttpClient::new_with(middleware),
+        }
+    }
+
+    pub fn with_base_url(self, base_url: Url) -> Self {
+        Self {
+            inner: self.inner.with_base_url(base_url),
+        }
+    }
+}
+
+impl<C: HttpClientContext> HttpClient<C> {
+    /// Wrap the async client `inner`.
+    pub fn from_async(inner: C) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    pub fn call<E: Service<Context = C> + ClientEndpoint>(
+        &mut self,
+        endpoint: E,
+    ) -> Result<E::Output, E::Error> {
+        block_on(endpoint.run(&mut self.inner))
+    }
+
+    pub fn execute(&mut self, request: Request) -> Result<Response, C::Error> {
+        block_on(self.inner.run_request(request))
+    }
+
+    /// Iterate over the items of all pages of `endpoint`. Pages are fetched when the items of the
+    /// previous page have been consumed, and the iterator ends after the first error.
+    pub fn paginate<'a, E: PaginatedEndpoint<Context = C> + 'a>(
+        &'a mut self,
+        endpoint: E,
+    ) -> impl Iterator<Item = Result<E::Item, E::Error>> + 'a {
+        futures::executor::block_on_stream(Box::pin(paginate::items(&mut self.inner, endpoint)))
+    }
+}
+
+impl<C: HttpClientContext> From<C> for HttpClient<C> {
+    fn from(inner: C) -> Self {
+        Self::from_async(inner)
+    }
+}
+
+impl<C> Deref for HttpClient<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C> DerefMut for HttpClient<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_types::Method;
+
+    use super::*;
+    use crate::http::{
+        mock::{Mock, MockMiddleware, MockResponse},
+        paginate::Page,
+    };
+
+    type Client = client::HttpClient<MockMiddleware>;
+
+    #[derive(Clone)]
+    struct ListNumbers {
+        page: u32,
+    }
+
+    impl Service for ListNumbers {
+        type Context = Client;
+        type Error = http_types::Error;
+    }
+
+    impl ClientEndpoint for ListNumbers {
+        type Output = Vec<u32>;
+
+        async fn run(&self, client: &mut Client) -> Result<Vec<u32>, http_types::Error> {
+            let url = format!("/numbers?page={}", self.page);
+            let request = client.new_request(Method::Get, &url);
+            client.run_request(request).await?.body_json().await
+        }
+    }
+
+    impl PaginatedEndpoint for ListNumbers {
+        type Item = u32;
+        type Cursor = u32;
+
+        async fn fetch_page(
+            &self,
+            client: &mut Client,
+            cursor: Option<&u32>,
+        ) -> Result<Page<u32, u32>, http_types::Error> {
+            let page = cursor.copied().unwrap_or(self.page);
+            let items = ListNumbers { page }.run(client).await?;
+            let next = (!items.is_empty()).then_some(page + 1);
+            Ok(Page { items, next })
+        }
+    }
+
+    #[test]
+    fn runs_endpoints_to_completion() {
+        let mock = MockMiddleware::new();
+        mock.mock(
+            Mock::new(Method::Get, "/numbers")
+                .query("page", "1")
+                .respond_with(MockResponse::new(StatusCode::Ok).json(&[1, 2])),
+        )
+        .mock(
+            Mock::new(Method::Get, "/numbers")
+                .query("page", "2")
+                .respond_with(MockResponse::new(StatusCode::Ok).json(&[3])),
+        )
+        .mock(
+            Mock::new(Method::Get, "/numbers")
+                .query("page", "3")
+                .respond_with(MockResponse::new(StatusCode::NotFound)),
+        );
+        let mut client = HttpClient::new_with(mock.clone())
+            .with_base_url(Url::parse("http://api.test").unwrap());
+
+        assert_eq!(client.call(ListNumbers { page: 2 }).unwrap(), [3]);
+        let response = client
+            .execute(client.new_request(Method::Get, "/numbers?page=3"))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NotFound);
+
+        let mut numbers = client.paginate(ListNumbers { page: 1 });
+        assert_eq!(numbers.next().unwrap().unwrap(), 1);
+        assert_eq!(numbers.next().unwrap().unwrap(), 2);
+        assert_eq!(numbers.next().unwrap().unwrap(), 3);
+        assert!(numbers.next().unwrap().is_err());
+        assert!(numbers.next().is_none());
+    }
+}
@@ -0,0 +1,599 @@
+//! A middleware recording HTTP interactions to a JSON file (a cassette), and replaying them
+//! later without sending any requests.
+//!
+//! ```ignore
+//! let layer = CassetteLayer::auto("tests/cassettes/orders.json")?
+//!     .match_on([MatchRule::Method, MatchRule::Url, MatchRule::Body])
+//!     .redact_header("authorization");
+//! let client = HttpClient::new_with(layer.wrap(DefaultMiddleware::default()));
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use http_types::{Body, Mime, Request, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use super::{client::Middleware, limit::limit_body};
+use crate::{Handler, Layer, Service};
+
+/// The value redacted headers are recorded with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// The default size limit of the response bodies recorded by a [`Cassette`], in bytes.
+pub const DEFAULT_RECORD_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// A recorded body, stored as text when it is valid UTF-8.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedBody {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl RecordedBody {
+    fn new(bytes: Vec<u8>) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+
+        Some(match String::from_utf8(bytes) {
+            Ok(text) => Self::Text(text),
+            Err(e) => Self::Bytes(e.into_bytes()),
+        })
+    }
+
+    /// The contents of the body.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Text(text) => text.as_bytes(),
+            Self::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+/// A recorded request.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<RecordedBody>,
+}
+
+impl RecordedRequest {
+    fn header(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+}
+
+/// A recorded response.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<RecordedBody>,
+}
+
+impl RecordedResponse {
+    fn to_response(&self) -> http_types::Result<Response> {
+        let status = StatusCode::try_from(self.status)?;
+        let mut response = Response::new(status);
+        for (name, value) in &self.headers {
+            response.append_header(name.as_str(), value.as_str());
+        }
+        if let Some(body) = &self.body {
+            response.set_body(body.as_bytes());
+        }
+        Ok(response)
+    }
+}
+
+/// A request and the response it received.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// The recorded interactions, as stored in a cassette file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Recording {
+    /// Load a recording previously written with [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write this recording to `path` as pretty-printed JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::from)?;
+        fs::write(path, json)
+    }
+}
+
+/// Whether a cassette records or replays interactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send requests with the inner middleware, recording each interaction to the file.
+    Record,
+    /// Answer requests from the file, without using the inner middleware.
+    Replay,
+}
+
+/// What has to be equal between a request and a recorded request for it to be replayed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MatchRule {
+    Method,
+    /// The whole URL, including the query.
+    Url,
+    /// The path of the URL.
+    Path,
+    /// The query parameters of the URL, in any order.
+    Query,
+    Body,
+    /// The values of a header, after redaction.
+    Header(String),
+}
+
+impl MatchRule {
+    fn matches(&self, request: &RecordedRequest, recorded: &RecordedRequest) -> bool {
+        match self {
+            Self::Method => request.method.eq_ignore_ascii_case(&recorded.method),
+            Self::Url => request.url == recorded.url,
+            Self::Path => match (Url::parse(&request.url), Url::parse(&recorded.url)) {
+                (Ok(a), Ok(b)) => a.path() == b.path(),
+                _ => false,
+            },
+            Self::Query => match (Url::parse(&request.url), Url::parse(&recorded.url)) {
+                (Ok(a), Ok(b)) => {
+                    let mut a: Vec<_> = a.query_pairs().collect();
+                    let mut b: Vec<_> = b.query_pairs().collect();
+                    a.sort();
+                    b.sort();
+                    a == b
+                }
+                _ => false,
+            },
+            Self::Body => {
+                request.body.as_ref().map(RecordedBody::as_bytes)
+                    == recorded.body.as_ref().map(RecordedBody::as_bytes)
+            }
+            Self::Header(name) => request.header(name) == recorded.header(name),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CassetteState {
+    path: PathBuf,
+    mode: CassetteMode,
+    recording: Recording,
+    /// Whether each interaction of the recording has been replayed.
+    played: Vec<bool>,
+    rules: Vec<MatchRule>,
+    redacted: Vec<String>,
+    limit: u64,
+}
+
+impl CassetteState {
+    fn redact(&self, headers: &mut [(String, String)]) {
+        for (name, value) in headers {
+            if self.redacted.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+                *value = String::from(REDACTED);
+            }
+        }
+    }
+}
+
+/// A [`Layer`] wrapping middlewares in a [`Cassette`]. All middlewares wrapped by a layer (and
+/// its clones) share the same cassette.
+#[derive(Clone, Debug)]
+pub struct CassetteLayer {
+    state: Arc<Mutex<CassetteState>>,
+}
+
+impl CassetteLayer {
+    /// Record interactions to `path`, replacing the interactions already in it.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self::with_recording(path.into(), CassetteMode::Record, Recording::default())
+    }
+
+    /// Replay the interactions recorded in `path`.
+    pub fn replay(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let recording = Recording::load(&path)?;
+        Ok(Self::with_recording(path, CassetteMode::Replay, recording))
+    }
+
+    /// Replay the interactions recorded in `path` if it exists, or record them otherwise.
+    pub fn auto(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if path.exists() {
+            Self::replay(path)
+        } else {
+            Ok(Self::record(path))
+        }
+    }
+
+    fn with_recording(path: PathBuf, mode: CassetteMode, recording: Recording) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CassetteState {
+                path,
+                mode,
+                played: vec![false; recording.interactions.len()],
+                recording,
+                rules: vec![MatchRule::Method, MatchRule::Url],
+                redacted: Vec::new(),
+                limit: DEFAULT_RECORD_LIMIT,
+            })),
+        }
+    }
+
+    /// Replace the rules a request has to match to replay an interaction. By default, the method
+    /// and the URL have to match.
+    pub fn match_on(self, rules: impl IntoIterator<Item = MatchRule>) -> Self {
+        self.state.lock().unwrap().rules = rules.into_iter().collect();
+        self
+    }
+
+    /// Record the values of the header `name`, in requests and responses, as [`REDACTED`].
+    pub fn redact_header(self, name: impl Into<String>) -> Self {
+        self.state.lock().unwrap().redacted.push(name.into());
+        self
+    }
+
+    /// Limit the bodies of recorded responses to `limit` bytes, instead of
+    /// [`DEFAULT_RECORD_LIMIT`]. Larger responses fail with
+    /// [`BodyTooLarge`](super::limit::BodyTooLarge), and are not recorded.
+    pub fn limit(self, limit: u64) -> Self {
+        self.state.lock().unwrap().limit = limit;
+        self
+    }
+
+    /// Whether the cassette records or replays interactions.
+    pub fn mode(&self) -> CassetteMode {
+        self.state.lock().unwrap().mode
+    }
+
+    /// The interactions recorded so far, or loaded to be replayed.
+    pub fn recording(&self) -> Recording {
+        self.state.lock().unwrap().recording.clone()
+    }
+}
+
+impl<M> Layer<M> for CassetteLayer {
+    type Service = Cassette<M>;
+
+    fn wrap(&self, inner: M) -> Self::Service {
+        Cassette {
+            inner,
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// A middleware recording or replaying interactions, created by a [`CassetteLayer`].
+///
+/// In record mode, the file is rewritten after every interaction. In replay mode, every recorded
+/// interaction is replayed at most once, in the order they were recorded; requests that do not
+/// match an interaction which has not been replayed yet fail with a `404 Not Found` error.
+///
+/// Request and response bodies are read into memory to be recorded, so a streamed response is
+/// only returned once it has ended, and endless streams like server-sent events cannot be
+/// recorded. Response bodies are limited to [`DEFAULT_RECORD_LIMIT`] bytes, or the limit set
+/// with [`CassetteLayer::limit`].
+#[derive(Clone, Debug)]
+pub struct Cassette<M> {
+    inner: M,
+    state: Arc<Mutex<CassetteState>>,
+}
+
+impl<M> Cassette<M> {
+    /// The wrapped middleware.
+    pub fn get_inner(&self) -> &M {
+        &self.inner
+    }
+}
+
+impl<M: Middleware> Service for Cassette<M> {
+    type Context = ();
+    type Error = M::Error;
+}
+
+impl<M> Handler<Request> for Cassette<M>
+where
+    M: Middleware,
+    M::Error: From<http_types::Error>,
+{
+    type Response = Response;
+
+    async fn call(
+        &mut self,
+        mut request: Request,
+        cx: &mut Self::Context,
+    ) -> Result<Self::Response, Self::Error> {
+        let (body, mime) = take_bytes(request.take_body()).await?;
+        let mut recorded_request = RecordedRequest {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: headers(request.iter()),
+            body: RecordedBody::new(body.clone()),
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .redact(&mut recorded_request.headers);
+
+        let mode = self.state.lock().unwrap().mode;
+        match mode {
+            CassetteMode::Replay => {
+                let mut state = self.state.lock().unwrap();
+                let state = &mut *state;
+                let found = state
+                    .recording
+                    .interactions
+                    .iter()
+                    .zip(&mut state.played)
+                    .find(|(interaction, played)| {
+                        !**played
+                            && state
+                                .rules
+                                .iter()
+                                .all(|rule| rule.matches(&recorded_request, &interaction.request))
+                    });
+
+                match found {
+                    Some((interaction, played)) => {
+                        *played = true;
+                        Ok(interaction.response.to_response()?)
+                    }
+                    None => Err(http_types::Error::from_str(
+                        StatusCode::NotFound,
+                        format!(
+                            "no recorded interaction matches {} {}",
+                            request.method(),
+                            request.url()
+                        ),
+                    )
+                    .into()),
+                }
+            }
+            CassetteMode::Record => {
+                if !body.is_empty() {
+                    request.set_body(with_mime(body, mime));
+                }
+                let mut response = self.inner.call(request, cx).await?;
+
+                let limit = self.state.lock().unwrap().limit;
+                limit_body(&mut response, limit);
+                let (body, mime) = take_bytes(response.take_body()).await?;
+                let mut recorded_response = RecordedResponse {
+                    status: response.status().into(),
+                    headers: headers(response.iter()),
+                    body: RecordedBody::new(body.clone()),
+                };
+                response.set_body(with_mime(body, mime));
+
+                let mut state = self.state.lock().unwrap();
+                state.redact(&mut recorded_response.headers);
+                state.recording.interactions.push(Interaction {
+                    request: recorded_request,
+                    response: recorded_response,
+                });
+                state
+                    .recording
+                    .save(&state.path)
+                    .map_err(http_types::Error::from)?;
+
+                Ok(response)
+            }
+        }
+    }
+}
+
+fn headers<'a>(
+    iter: impl Iterator<
+        Item = (
+            &'a http_types::headers::HeaderName,
+            &'a http_types::headers::HeaderValues,
+        ),
+    >,
+) -> Vec<(String, String)> {
+    let mut headers: Vec<_> = iter
+        .flat_map(|(name, values)| {
+            values
+                .iter()
+                .map(move |value| (name.as_str().to_owned(), value.as_str().to_owned()))
+        })
+        .collect();
+    // headers are stored in a hash map, so sort them to keep cassettes stable
+    headers.sort();
+    headers
+}
+
+async fn take_bytes(body: Body) -> http_types::Result<(Vec<u8>, Mime)> {
+    let mime = body.mime().clone();
+    Ok((body.into_bytes().await?, mime))
+}
+
+fn with_mime(bytes: Vec<u8>, mime: Mime) -> Body {
+    let mut body = Body::from(bytes);
+    body.set_mime(mime);
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use http_types::Method;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::http::{
+        limit::BodyTooLarge,
+        mock::{Mock, MockMiddleware, MockResponse},
+    };
+
+    /// A server answering `/a` with the `n` of the JSON body, and `/b`.
+    fn server() -> MockMiddleware {
+        let server = MockMiddleware::new();
+        for n in ["one", "two"] {
+            server.mock(
+                Mock::new(Method::Post, "/a")
+                    .json_body(&json!({ "n": n }))
+                    .respond_with(
+                        MockResponse::new(StatusCode::Ok)
+                            .header("set-cookie", "session=secret")
+                            .text(format!("/a: {n}")),
+                    ),
+            );
+        }
+        server.mock(Mock::new(Method::Post, "/b").respond_with(MockResponse::new(StatusCode::Ok)));
+        server
+    }
+
+    fn request(method: Method, path: &str, body: &str) -> Request {
+        let mut request = Request::new(
+            method,
+            Url::parse("http://api.test").unwrap().join(path).unwrap(),
+        );
+        request.insert_header("authorization", "Bearer secret");
+        if !body.is_empty() {
+            request.set_body(body);
+        }
+        request
+    }
+
+    fn cassette_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("acril-cassette-{}-{name}.json", std::process::id()))
+    }
+
+    #[test]
+    fn records_and_replays() {
+        block_on(async {
+            let path = cassette_path("roundtrip");
+
+            let layer = CassetteLayer::record(&path)
+                .redact_header("authorization")
+                .redact_header("set-cookie");
+            let mut recorder = layer.wrap(server());
+            for (path, body, expected) in [
+                ("/a", r#"{"n":"one"}"#, "/a: one"),
+                ("/a", r#"{"n":"two"}"#, "/a: two"),
+                ("/b", "", ""),
+            ] {
+                let mut response = recorder
+                    .call(request(Method::Post, path, body), &mut ())
+                    .await
+                    .unwrap();
+                assert_eq!(response.body_string().await.unwrap(), expected);
+            }
+
+            let saved = fs::read_to_string(&path).unwrap();
+            assert!(!saved.contains("secret"));
+            assert!(saved.contains(REDACTED));
+
+            let layer = CassetteLayer::replay(&path).unwrap().match_on([
+                MatchRule::Method,
+                MatchRule::Path,
+                MatchRule::Body,
+            ]);
+            let server = server();
+            let mut player = layer.wrap(server.clone());
+
+            let mut response = player
+                .call(request(Method::Post, "/a", r#"{"n":"two"}"#), &mut ())
+                .await
+                .unwrap();
+            assert_eq!(response.body_string().await.unwrap(), "/a: two");
+            assert_eq!(response.header("set-cookie").unwrap(), REDACTED);
+
+            let mut response = player
+                .call(
+                    request(Method::Post, "/a?page=2", r#"{"n":"one"}"#),
+                    &mut (),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.body_string().await.unwrap(), "/a: one");
+
+            // every interaction is only replayed once
+            let error = player
+                .call(request(Method::Post, "/a", r#"{"n":"one"}"#), &mut ())
+                .await
+                .unwrap_err();
+            assert_eq!(error.status(), StatusCode::NotFound);
+            // replaying never reaches the inner middleware
+            assert!(server.received().is_empty());
+
+            fs::remove_file(path).unwrap();
+        })
+    }
+
+    #[test]
+    fn auto_mode() {
+        let path = cassette_path("auto");
+        assert_eq!(
+            CassetteLayer::auto(&path).unwrap().mode(),
+            CassetteMode::Record
+        );
+
+        Recording::default().save(&path).unwrap();
+        assert_eq!(
+            CassetteLayer::auto(&path).unwrap().mode(),
+            CassetteMode::Replay
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn limits_recorded_bodies() {
+        block_on(async {
+            let path = cassette_path("limit");
+            let layer = CassetteLayer::record(&path).limit(4);
+            let mut cassette = layer.wrap(server());
+
+            let error = cassette
+                .call(request(Method::Post, "/a", r#"{"n":"one"}"#), &mut ())
+                .await
+                .unwrap_err();
+            assert_eq!(BodyTooLarge::find(&error), Some(&BodyTooLarge { limit: 4 }));
+            assert!(layer.recording().interactions.is_empty());
+
+            let response = cassette
+                .call(request(Method::Post, "/b", ""), &mut ())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(layer.recording().interactions.len(), 1);
+
+            fs::remove_file(&path).unwrap();
+        })
+    }
+
+    #[test]
+    fn binary_bodies() {
+        let body = RecordedBody::new(vec![0xff, 0x00]).unwrap();
+        assert_eq!(body, RecordedBody::Bytes(vec![0xff, 0x00]));
+        assert_eq!(
+            RecordedBody::new(b"ok".to_vec()),
+            Some(RecordedBody::Text("ok".into()))
+        );
+        assert_eq!(RecordedBody::new(Vec::new()), None);
+    }
+}
@@ -1,17 +1,74 @@
 use crate::Handler;
 
-use super::*;
+use std::{
+    fmt::{self, Debug, Display},
+    ops::{Deref, DerefMut},
+};
+
+use super::{
+    paginate::{self, PaginatedEndpoint},
+    *,
+};
+#[cfg(unix)]
+pub use acril_http::client::{unix_url, UnixConnector};
+pub use acril_http::client::{
+    CachingResolver, Connector, DefaultConnector, Resolver, StaticResolver, SystemResolver,
+};
 pub use acril_macros::{with_builder, ClientEndpoint};
-use http_types::Url;
+use futures::Stream;
+use http_types::{
+    headers::{HeaderName, HeaderValue, HeaderValues, Headers},
+    Url,
+};
+
+/// The middleware sending requests, over connections opened by the connector `C`.
+///
+/// The default connector dials TCP, with TLS for `https:` URLs, and Unix domain sockets for
+/// `unix:` URLs. Other connectors can send requests through a proxy, or over in-memory streams in
+/// tests:
+///
+/// ```ignore
+/// let client = HttpClient::new_with(DefaultMiddleware::new(UnixConnector::new("/run/agent.sock")));
+/// ```
+///
+/// With [`with_max_body_size`](Self::with_max_body_size), response bodies are limited as they
+/// are read from the connection, failing with a
+/// [`BodyTooLarge`](super::limit::BodyTooLarge) error past the limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DefaultMiddleware<C = DefaultConnector> {
+    connector: C,
+    max_body_size: Option<u64>,
+}
+
+impl<C: Connector> DefaultMiddleware<C> {
+    pub fn new(connector: C) -> Self {
+        Self {
+            connector,
+            max_body_size: None,
+        }
+    }
+
+    /// Limit response bodies to `max_body_size` bytes.
+    pub fn with_max_body_size(mut self, max_body_size: Option<u64>) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    pub fn get_connector(&self) -> &C {
+        &self.connector
+    }
 
-pub struct DefaultMiddleware;
+    pub fn max_body_size(&self) -> Option<u64> {
+        self.max_body_size
+    }
+}
 
-impl Service for DefaultMiddleware {
+impl<C: Connector> Service for DefaultMiddleware<C> {
     type Context = ();
     type Error = http_types::Error;
 }
 
-impl Handler<Request> for DefaultMiddleware {
+impl<C: Connector> Handler<Request> for DefaultMiddleware<C> {
     type Response = Response;
 
     async fn call(
@@ -19,12 +76,14 @@ impl Handler<Request> for DefaultMiddleware {
         request: Request,
         _cx: &mut Self::Context,
     ) -> Result<Self::Response, Self::Error> {
-        acril_http::client::connect(request).await
+        acril_http::client::connect_with_limit(&self.connector, request, self.max_body_size).await
     }
 }
 
 pub trait Middleware: Handler<Request, Response = Response, Context = ()> {}
 
+impl<T: Handler<Request, Response = Response, Context = ()>> Middleware for T {}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct HttpClient<M = DefaultMiddleware> {
     middleware: M,
@@ -61,12 +120,21 @@ impl<M: Middleware> HttpClient<M> {
     pub async fn execute(&mut self, request: Request) -> Result<Response, M::Error> {
         self.middleware.call(request, &mut ()).await
     }
+
+    /// Stream the items of all pages of `endpoint`. Pages are fetched when the items of the
+    /// previous page have been consumed, and the stream ends after the first error.
+    pub fn paginate<'a, E: PaginatedEndpoint<Context = Self> + 'a>(
+        &'a mut self,
+        endpoint: E,
+    ) -> impl Stream<Item = Result<E::Item, E::Error>> + 'a {
+        paginate::items(self, endpoint)
+    }
 }
 
 impl HttpClient<DefaultMiddleware> {
     pub fn new() -> Self {
         Self {
-            middleware: DefaultMiddleware,
+            middleware: DefaultMiddleware::default(),
             base_url: None,
         }
     }
@@ -106,3 +174,195 @@ pub trait ClientEndpoint: Service {
 
     async fn run(&self, context: &mut Self::Context) -> Result<Self::Output, Self::Error>;
 }
+
+/// The state of a `#[required]` field of a `#[with_builder]` builder that has not been set yet.
+/// Setting the field changes its state to its type, and the builder can only be executed once no
+/// field is `Unset`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Unset;
+
+/// The value of a `#[path_param]` field in the path, percent-encoded as a single path segment. The
+/// value is formatted with the spec of its placeholder first, so `{id:04}` is still padded. In
+/// parenthesized path expressions, the fields are formatted with `{}` and format specs are
+/// ignored.
+#[doc(hidden)]
+pub struct PathSegment<T>(pub T);
+
+impl<T: Display> Display for PathSegment<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.to_string().bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    write!(f, "{}", byte as char)?
+                }
+                _ => write!(f, "%{byte:02X}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The value of a `#[header(...)]` field: scalars are formatted, sequences are joined with commas,
+/// and `None` omits the header.
+#[doc(hidden)]
+pub fn header_value<T: serde::Serialize + ?Sized>(
+    value: &T,
+) -> http_types::Result<Option<HeaderValue>> {
+    fn scalar(value: serde_json::Value) -> http_types::Result<String> {
+        Ok(match value {
+            serde_json::Value::String(s) => s,
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Bool(b) => b.to_string(),
+            _ => http_types::bail!("header fields must be scalars or sequences of scalars"),
+        })
+    }
+
+    let value = match serde_json::to_value(value)? {
+        serde_json::Value::Null => return Ok(None),
+        serde_json::Value::Array(values) => values
+            .into_iter()
+            .map(scalar)
+            .collect::<http_types::Result<Vec<_>>>()?
+            .join(", "),
+        value => scalar(value)?,
+    };
+    http_types::ensure!(
+        !value.bytes().any(|b| b.is_ascii_control()),
+        "header values cannot contain control characters"
+    );
+
+    Ok(Some(value.parse()?))
+}
+
+/// The value of the header `name` of `response`, for the `headers(...)` option of endpoints. The
+/// last value of the header is parsed like a query parameter, so a missing header is `None` for
+/// `Option` fields and an error otherwise.
+#[doc(hidden)]
+#[cfg(feature = "macros")]
+pub fn header_field<T: serde::de::DeserializeOwned>(
+    response: &Response,
+    name: &str,
+) -> http_types::Result<T> {
+    #[derive(serde::Deserialize)]
+    struct Field<T> {
+        value: T,
+    }
+
+    let values = response.header(name);
+    let query = match values {
+        Some(values) => serde_urlencoded::to_string([("value", values.last().as_str())])?,
+        None => String::new(),
+    };
+    serde_urlencoded::from_str::<Field<T>>(&query)
+        .map(|field| field.value)
+        .map_err(|e| match values {
+            Some(_) => http_types::format_err!("invalid header `{}`: {}", name, e),
+            None => http_types::format_err!("missing header `{}`", name),
+        })
+}
+
+/// The output of an endpoint with the status and headers of its response, returned by endpoints
+/// with the `with_meta` option.
+///
+/// Headers can also be parsed into fields of the output with the `headers(...)` option, usually
+/// skipped by serde:
+///
+/// ```ignore
+/// #[derive(ClientEndpoint)]
+/// #[endpoint(Get "/orders/{id}" in MyClient -> Order, headers(etag = "ETag"), with_meta)]
+/// struct GetOrder {
+///     id: u64,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Order {
+///     #[serde(skip)]
+///     etag: Option<String>,
+///     // ...
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct WithMeta<T> {
+    pub status: StatusCode,
+    pub headers: Headers,
+    pub output: T,
+}
+
+impl<T> WithMeta<T> {
+    /// The values of the header `name` of the response, if it has one.
+    pub fn header(&self, name: impl Into<HeaderName>) -> Option<&HeaderValues> {
+        self.headers.get(name)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.output
+    }
+}
+
+impl<T> Deref for WithMeta<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.output
+    }
+}
+
+impl<T> DerefMut for WithMeta<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.output
+    }
+}
+
+/// The error of endpoints declaring an `api_error` type, telling apart the ways a call can fail.
+#[derive(Debug)]
+pub enum EndpointError<A, E = http_types::Error> {
+    /// The request could not be built from the endpoint.
+    Encode(http_types::Error),
+    /// The request could not be sent, or the response could not be received.
+    Transport(E),
+    /// The response, or the body of an error response, could not be decoded. In the latter case,
+    /// the status of the error is the status of the response.
+    Decode(http_types::Error),
+    /// The API responded with a client or server error status.
+    Api(ApiError<A>),
+}
+
+/// An error response, with its body decoded from JSON.
+#[derive(Debug)]
+pub struct ApiError<A> {
+    pub status: StatusCode,
+    pub headers: Headers,
+    pub body: A,
+}
+
+impl<A, E> EndpointError<A, E> {
+    /// The error returned by the API, if any.
+    pub fn api_error(&self) -> Option<&ApiError<A>> {
+        match self {
+            Self::Api(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// The status of the response the error occurred for, if one was received.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Api(e) => Some(e.status),
+            Self::Decode(e) => Some(e.status()),
+            Self::Encode(_) | Self::Transport(_) => None,
+        }
+    }
+}
+
+impl<A: Debug, E: Display> Display for EndpointError<A, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Encode(e) => write!(f, "failed to encode request: {e}"),
+            Self::Transport(e) => write!(f, "{e}"),
+            Self::Decode(e) => write!(f, "failed to decode response: {e}"),
+            Self::Api(e) => write!(f, "API error ({}): {:?}", e.status, e.body),
+        }
+    }
+}
+
+impl<A: Debug, E: Debug + Display> std::error::Error for EndpointError<A, E> {}
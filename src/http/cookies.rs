@@ -0,0 +1,761 @@
+//! A cookie jar middleware, storing cookies from `Set-Cookie` response headers and sending them
+//! back in `Cookie` request headers, following [RFC 6265](https://www.rfc-editor.org/rfc/rfc6265).
+
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use http_types::{
+    headers::{COOKIE, ORIGIN, REFERER, SET_COOKIE},
+    url::Host,
+    Method, Request, Response, Url,
+};
+
+use super::client::Middleware;
+use crate::{Handler, Layer, Service};
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    /// Only sent with same-site requests.
+    Strict,
+    /// Sent with same-site requests, and with cross-site requests using a safe method.
+    Lax,
+    /// Sent with all requests. Requires the cookie to be `Secure`.
+    None,
+}
+
+impl FromStr for SameSite {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("strict") {
+            Ok(Self::Strict)
+        } else if s.eq_ignore_ascii_case("lax") {
+            Ok(Self::Lax)
+        } else if s.eq_ignore_ascii_case("none") {
+            Ok(Self::None)
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        })
+    }
+}
+
+/// A cookie, as stored in a [`CookieStore`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header value received in a response to a request to `url`.
+    ///
+    /// Returns `None` if the cookie is malformed, or if `url` is not allowed to set it. Control
+    /// characters, including tabs in the name, value or path, make a cookie malformed.
+    pub fn parse(set_cookie: &str, url: &Url) -> Option<Self> {
+        if set_cookie
+            .chars()
+            .any(|c| c.is_ascii_control() && c != '\t')
+        {
+            return None;
+        }
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut parts = set_cookie.split(';');
+
+        let (name, value) = parts.next()?.split_once('=')?;
+        let (name, value) = (name.trim(), value.trim());
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Self {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            secure: false,
+            http_only: false,
+            same_site: None,
+            expires: None,
+        };
+
+        let mut max_age = None;
+        let mut expires = None;
+
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+
+            if key.eq_ignore_ascii_case("expires") {
+                // an unparseable date means the attribute is ignored
+                expires = acril_http::parse_http_date(value).ok().or(expires);
+            } else if key.eq_ignore_ascii_case("max-age") {
+                max_age = parse_max_age(value).or(max_age);
+            } else if key.eq_ignore_ascii_case("domain") {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if domain.is_empty() {
+                    continue;
+                }
+                if !domain_match(&host, &domain) {
+                    return None;
+                }
+                // Without a public suffix list, refuse cookies for bare top-level domains
+                // unless they are exactly the request host.
+                if domain != host {
+                    if !domain.contains('.') || url.host().is_some_and(|h| !is_domain(&h)) {
+                        return None;
+                    }
+                    cookie.host_only = false;
+                }
+                cookie.domain = domain;
+            } else if key.eq_ignore_ascii_case("path") {
+                if value.starts_with('/') {
+                    cookie.path = value.to_owned();
+                }
+            } else if key.eq_ignore_ascii_case("secure") {
+                cookie.secure = true;
+            } else if key.eq_ignore_ascii_case("httponly") {
+                cookie.http_only = true;
+            } else if key.eq_ignore_ascii_case("samesite") {
+                cookie.same_site = value.parse().ok();
+            }
+        }
+
+        // Max-Age takes precedence over Expires.
+        cookie.expires = match max_age {
+            Some(secs) if secs <= 0 => Some(UNIX_EPOCH),
+            Some(secs) => Some(
+                SystemTime::now()
+                    .checked_add(Duration::from_secs(secs as u64))
+                    .map_or_else(latest_expiry, |at| at.min(latest_expiry())),
+            ),
+            None => expires,
+        };
+
+        // Tabs separate the fields of a persisted store.
+        if [&cookie.name, &cookie.value, &cookie.path]
+            .iter()
+            .any(|field| field.contains('\t'))
+        {
+            return None;
+        }
+
+        // Insecure origins may not set secure cookies, and `SameSite=None` requires `Secure`.
+        if cookie.secure && !is_secure(url) {
+            return None;
+        }
+        if cookie.same_site == Some(SameSite::None) && !cookie.secure {
+            return None;
+        }
+
+        Some(cookie)
+    }
+
+    /// The name of this cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of this cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The domain this cookie is sent to.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Whether this cookie is only sent to [`domain`](Self::domain) exactly, and not its subdomains.
+    pub fn host_only(&self) -> bool {
+        self.host_only
+    }
+
+    /// The path this cookie is sent to, including subpaths.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Whether this cookie is only sent over secure connections.
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Whether this cookie has the `HttpOnly` attribute.
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    /// The `SameSite` attribute of this cookie, if any.
+    pub fn same_site(&self) -> Option<SameSite> {
+        self.same_site
+    }
+
+    /// When this cookie expires. Cookies without an expiry time live as long as their store.
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.expires
+    }
+
+    /// Whether this cookie has expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Whether this cookie should be sent with a request to `url`.
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+
+        domain_matches && path_match(url.path(), &self.path) && (!self.secure || is_secure(url))
+    }
+
+    fn same_identity(&self, other: &Self) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+
+/// A collection of cookies.
+///
+/// The store can be persisted with [`save`](CookieStore::save) and restored with
+/// [`load`](CookieStore::load). The file format is the same as the one used by the [`Display`]
+/// and [`FromStr`] implementations: a line per cookie, with tab-separated fields.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CookieStore {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieStore {
+    /// Create an empty cookie store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a cookie store previously written with [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e: ParseCookieStoreError| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write this store to `path`, so it can later be [`load`](Self::load)ed.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Insert a cookie, replacing a cookie with the same name, domain and path.
+    ///
+    /// An expired cookie removes the cookie it replaces.
+    pub fn insert(&mut self, cookie: Cookie) {
+        self.cookies.retain(|c| !c.same_identity(&cookie));
+        if !cookie.is_expired(SystemTime::now()) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// Remove all cookies.
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+
+    /// Remove all expired cookies.
+    pub fn remove_expired(&mut self) {
+        let now = SystemTime::now();
+        self.cookies.retain(|c| !c.is_expired(now));
+    }
+
+    /// Iterate over all cookies in the store, including expired ones.
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie> {
+        self.cookies.iter()
+    }
+
+    /// Store the cookies from the `Set-Cookie` headers of a response to a request to `url`.
+    pub fn store_response(&mut self, url: &Url, response: &Response) {
+        for value in response.header(SET_COOKIE).into_iter().flatten() {
+            if let Some(cookie) = Cookie::parse(value.as_str(), url) {
+                self.insert(cookie);
+            }
+        }
+    }
+
+    /// The cookies to send with a request to `url`, ordered by path length, longest first.
+    pub fn matches(&self, url: &Url) -> Vec<&Cookie> {
+        let now = SystemTime::now();
+        let mut cookies = self
+            .cookies
+            .iter()
+            .filter(|c| !c.is_expired(now) && c.matches(url))
+            .collect::<Vec<_>>();
+        // stable, so cookies with equal paths keep their insertion order
+        cookies.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        cookies
+    }
+
+    /// The value of the `Cookie` header for `request`, if any cookies should be sent with it.
+    ///
+    /// The `Origin` header, or the `Referer` header if there is no `Origin`, is used to decide
+    /// whether the request is cross-site, withholding `SameSite` cookies accordingly. Requests
+    /// without either header are treated as same-site.
+    pub fn cookie_header(&self, request: &Request) -> Option<String> {
+        let url = request.url();
+        let cross_site = request
+            .header(ORIGIN)
+            .or_else(|| request.header(REFERER))
+            .and_then(|site| Url::parse(site.last().as_str()).ok())
+            .is_some_and(|site| !same_site(&site, url));
+        let safe_method = matches!(
+            request.method(),
+            Method::Get | Method::Head | Method::Options | Method::Trace
+        );
+
+        let header = self
+            .matches(url)
+            .into_iter()
+            .filter(|c| match c.same_site {
+                Some(SameSite::Strict) => !cross_site,
+                Some(SameSite::Lax) => !cross_site || safe_method,
+                Some(SameSite::None) | None => true,
+            })
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        (!header.is_empty()).then_some(header)
+    }
+}
+
+impl Display for CookieStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "# domain\thost_only\tpath\tsecure\thttp_only\tsame_site\texpires\tname\tvalue"
+        )?;
+        for cookie in &self.cookies {
+            writeln!(
+                f,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                cookie.domain,
+                cookie.host_only,
+                cookie.path,
+                cookie.secure,
+                cookie.http_only,
+                cookie
+                    .same_site
+                    .map_or_else(|| String::from("-"), |s| s.to_string()),
+                cookie.expires.map_or_else(
+                    || String::from("-"),
+                    |e| e
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs()
+                        .to_string()
+                ),
+                cookie.name,
+                cookie.value,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when parsing a persisted [`CookieStore`] fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseCookieStoreError {
+    line: usize,
+}
+
+impl Display for ParseCookieStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed cookie on line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseCookieStoreError {}
+
+impl FromStr for CookieStore {
+    type Err = ParseCookieStoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut store = Self::new();
+
+        for (idx, line) in s.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = ParseCookieStoreError { line: idx + 1 };
+            let fields = line.splitn(9, '\t').collect::<Vec<_>>();
+            let [domain, host_only, path, secure, http_only, same_site, expires, name, value] =
+                fields[..]
+            else {
+                return Err(error);
+            };
+
+            store.cookies.push(Cookie {
+                name: name.to_owned(),
+                value: value.to_owned(),
+                domain: domain.to_owned(),
+                host_only: host_only.parse().map_err(|_| error.clone())?,
+                path: path.to_owned(),
+                secure: secure.parse().map_err(|_| error.clone())?,
+                http_only: http_only.parse().map_err(|_| error.clone())?,
+                same_site: match same_site {
+                    "-" => None,
+                    s => Some(s.parse().map_err(|_| error.clone())?),
+                },
+                expires: match expires {
+                    "-" => None,
+                    e => {
+                        let secs = Duration::from_secs(e.parse().map_err(|_| error.clone())?);
+                        Some(UNIX_EPOCH.checked_add(secs).ok_or(error)?)
+                    }
+                },
+            });
+        }
+
+        Ok(store)
+    }
+}
+
+/// A [`Layer`] wrapping a middleware in [`Cookies`], all sharing the same store.
+#[derive(Clone, Debug, Default)]
+pub struct CookieLayer {
+    store: Arc<Mutex<CookieStore>>,
+}
+
+impl CookieLayer {
+    /// Create a layer sharing `store` between all middlewares it wraps.
+    pub fn new(store: Arc<Mutex<CookieStore>>) -> Self {
+        Self { store }
+    }
+
+    /// The store shared by the middlewares.
+    pub fn store(&self) -> &Arc<Mutex<CookieStore>> {
+        &self.store
+    }
+}
+
+impl<M> Layer<M> for CookieLayer {
+    type Service = Cookies<M>;
+
+    fn wrap(&self, inner: M) -> Self::Service {
+        Cookies::with_store(inner, self.store.clone())
+    }
+}
+
+/// A middleware that keeps cookies set by responses in a [`CookieStore`], and adds the matching
+/// ones to every request.
+#[derive(Debug)]
+pub struct Cookies<M> {
+    inner: M,
+    store: Arc<Mutex<CookieStore>>,
+}
+
+impl<M> Cookies<M> {
+    /// Wrap `inner` with an empty cookie store.
+    pub fn new(inner: M) -> Self {
+        Self::with_store(inner, Arc::default())
+    }
+
+    /// Wrap `inner`, using `store` for cookies.
+    pub fn with_store(inner: M, store: Arc<Mutex<CookieStore>>) -> Self {
+        Self { inner, store }
+    }
+
+    /// The cookie store of this middleware.
+    pub fn store(&self) -> &Arc<Mutex<CookieStore>> {
+        &self.store
+    }
+
+    /// The wrapped middleware.
+    pub fn get_inner(&self) -> &M {
+        &self.inner
+    }
+}
+
+impl<M: Middleware> Service for Cookies<M> {
+    type Context = ();
+    type Error = M::Error;
+}
+
+impl<M: Middleware> Handler<Request> for Cookies<M> {
+    type Response = Response;
+
+    async fn call(
+        &mut self,
+        mut request: Request,
+        cx: &mut Self::Context,
+    ) -> Result<Self::Response, Self::Error> {
+        let url = request.url().clone();
+
+        if request.header(COOKIE).is_none() {
+            let header = self.store.lock().unwrap().cookie_header(&request);
+            if let Some(header) = header {
+                request.insert_header(COOKIE, header);
+            }
+        }
+
+        let response = self.inner.call(request, cx).await?;
+        self.store.lock().unwrap().store_response(&url, &response);
+
+        Ok(response)
+    }
+}
+
+/// The latest expiry time of cookies, 9999-12-31 23:59:59 UTC, which larger `Max-Age` values are
+/// clamped to.
+fn latest_expiry() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(253_402_300_799)
+}
+
+/// Parse a `Max-Age` value, saturating values too large for an `i64`.
+fn parse_max_age(value: &str) -> Option<i64> {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(value.parse().unwrap_or(if digits.len() < value.len() {
+        i64::MIN
+    } else {
+        i64::MAX
+    }))
+}
+
+fn is_secure(url: &Url) -> bool {
+    url.scheme() == "https"
+        || url.scheme() == "wss"
+        || matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"))
+}
+
+fn is_domain(host: &Host<&str>) -> bool {
+    matches!(host, Host::Domain(_))
+}
+
+/// RFC 6265, section 5.1.3.
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+            && host.parse::<std::net::IpAddr>().is_err())
+}
+
+/// RFC 6265, section 5.1.4.
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(idx) => path[..idx].to_owned(),
+    }
+}
+
+/// RFC 6265, section 5.1.4.
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+/// Approximates the registrable domain with the last two labels of the host, as there is no
+/// public suffix list to consult.
+fn same_site(a: &Url, b: &Url) -> bool {
+    fn site(url: &Url) -> Option<String> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        if url.host().is_some_and(|h| !is_domain(&h)) {
+            return Some(host);
+        }
+        let labels = host.rsplit('.').take(2).collect::<Vec<_>>();
+        Some(labels.into_iter().rev().collect::<Vec<_>>().join("."))
+    }
+
+    site(a).is_some() && site(a) == site(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse_attributes() {
+        let cookie = Cookie::parse(
+            "sid=abc; Domain=.example.com; Path=/api; Secure; HttpOnly; SameSite=Strict; Expires=Sun, 02 Oct 2016 14:44:11 GMT",
+            &url("https://www.example.com/login"),
+        )
+        .unwrap();
+
+        assert_eq!(cookie.name(), "sid");
+        assert_eq!(cookie.value(), "abc");
+        assert_eq!(cookie.domain(), "example.com");
+        assert!(!cookie.host_only());
+        assert_eq!(cookie.path(), "/api");
+        assert!(cookie.secure());
+        assert!(cookie.http_only());
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_eq!(
+            cookie.expires(),
+            Some(UNIX_EPOCH + Duration::from_secs(1475419451))
+        );
+    }
+
+    #[test]
+    fn max_age_overrides_expires() {
+        let cookie = Cookie::parse(
+            "a=b; Max-Age=0; Expires=Fri, 31 Dec 9998 23:59:59 GMT",
+            &url("http://example.com/"),
+        )
+        .unwrap();
+        assert!(cookie.is_expired(SystemTime::now()));
+
+        let origin = url("http://example.com/");
+        let cookie = Cookie::parse("a=b; Max-Age=99999999999999999999", &origin).unwrap();
+        assert_eq!(cookie.expires(), Some(latest_expiry()));
+        let cookie = Cookie::parse("a=b; Max-Age=9223372036854775807", &origin).unwrap();
+        assert_eq!(cookie.expires(), Some(latest_expiry()));
+        let cookie = Cookie::parse("a=b; Max-Age=-99999999999999999999", &origin).unwrap();
+        assert!(cookie.is_expired(SystemTime::now()));
+        let cookie = Cookie::parse("a=b; Max-Age=+1", &origin).unwrap();
+        assert_eq!(cookie.expires(), None);
+    }
+
+    #[test]
+    fn reject_control_characters() {
+        let origin = url("http://example.com/");
+        assert!(Cookie::parse("a\tb=c", &origin).is_none());
+        assert!(Cookie::parse("a=b\tc", &origin).is_none());
+        assert!(Cookie::parse("a=b; Path=/x\ty", &origin).is_none());
+        assert!(Cookie::parse("a=b\nc", &origin).is_none());
+        assert!(Cookie::parse("a=b\r; Path=/", &origin).is_none());
+
+        // tabs around names, values and attributes are whitespace
+        let cookie = Cookie::parse("\ta=b\t;\tPath=/x\t", &origin).unwrap();
+        assert_eq!(
+            (cookie.name(), cookie.value(), cookie.path()),
+            ("a", "b", "/x")
+        );
+    }
+
+    #[test]
+    fn reject_foreign_domain() {
+        assert!(Cookie::parse("a=b; Domain=evil.com", &url("http://example.com/")).is_none());
+        assert!(Cookie::parse("a=b; Domain=com", &url("http://example.com/")).is_none());
+        assert!(Cookie::parse("a=b; Secure", &url("http://example.com/")).is_none());
+        assert!(Cookie::parse("a=b; SameSite=None", &url("https://example.com/")).is_none());
+    }
+
+    #[test]
+    fn default_path_and_matching() {
+        let cookie = Cookie::parse("a=b", &url("http://example.com/v1/orders")).unwrap();
+        assert_eq!(cookie.path(), "/v1");
+        assert!(cookie.host_only());
+
+        assert!(cookie.matches(&url("http://example.com/v1")));
+        assert!(cookie.matches(&url("http://example.com/v1/accounts")));
+        assert!(!cookie.matches(&url("http://example.com/v10")));
+        assert!(!cookie.matches(&url("http://api.example.com/v1")));
+    }
+
+    #[test]
+    fn cookie_header() {
+        let mut store = CookieStore::new();
+        let origin = url("https://api.example.com/v1/login");
+        store.insert(Cookie::parse("root=1; Path=/", &origin).unwrap());
+        store.insert(Cookie::parse("v1=2", &origin).unwrap());
+        store.insert(Cookie::parse("strict=3; Path=/; SameSite=Strict", &origin).unwrap());
+        store.insert(Cookie::parse("lax=4; Path=/; SameSite=Lax", &origin).unwrap());
+
+        let request = Request::new(Method::Get, url("https://api.example.com/v1/orders"));
+        assert_eq!(
+            store.cookie_header(&request).unwrap(),
+            "v1=2; root=1; strict=3; lax=4"
+        );
+
+        let mut request = Request::new(Method::Post, url("https://api.example.com/v1/orders"));
+        request.insert_header(ORIGIN, "https://other.org");
+        assert_eq!(store.cookie_header(&request).unwrap(), "v1=2; root=1");
+
+        let mut request = Request::new(Method::Get, url("https://api.example.com/"));
+        request.insert_header(ORIGIN, "https://app.example.com");
+        assert_eq!(
+            store.cookie_header(&request).unwrap(),
+            "root=1; strict=3; lax=4"
+        );
+    }
+
+    #[test]
+    fn replace_and_delete() {
+        let mut store = CookieStore::new();
+        let origin = url("http://example.com/");
+        store.insert(Cookie::parse("a=1", &origin).unwrap());
+        store.insert(Cookie::parse("a=2", &origin).unwrap());
+        assert_eq!(store.iter().map(Cookie::value).collect::<Vec<_>>(), ["2"]);
+
+        store.insert(Cookie::parse("a=; Max-Age=-1", &origin).unwrap());
+        assert_eq!(store.iter().count(), 0);
+    }
+
+    #[test]
+    fn persist_roundtrip() {
+        let mut store = CookieStore::new();
+        let origin = url("https://example.com/");
+        store.insert(Cookie::parse("a=1; Domain=example.com; SameSite=Lax", &origin).unwrap());
+        store.insert(Cookie::parse("b=x=y; Max-Age=3600; Secure", &origin).unwrap());
+
+        let mut loaded = store.to_string().parse::<CookieStore>().unwrap();
+        // persisted expiry times are truncated to seconds
+        for (loaded, original) in loaded.cookies.iter_mut().zip(&store.cookies) {
+            assert_eq!(
+                loaded
+                    .expires
+                    .map(|e| e.duration_since(UNIX_EPOCH).unwrap().as_secs()),
+                original
+                    .expires
+                    .map(|e| e.duration_since(UNIX_EPOCH).unwrap().as_secs()),
+            );
+            loaded.expires = original.expires;
+        }
+        assert_eq!(loaded, store);
+
+        assert!("example.com\ttrue".parse::<CookieStore>().is_err());
+        let overflowing = "example.com\ttrue\t/\tfalse\tfalse\t-\t18446744073709551615\ta\t1";
+        assert_eq!(
+            overflowing.parse::<CookieStore>(),
+            Err(ParseCookieStoreError { line: 1 })
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! A middleware that transparently decompresses response bodies sent with a `Content-Encoding`.
+
+use async_compression::futures::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder};
+use futures::io::{AsyncBufRead, BufReader};
+use http_types::{
+    headers::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH},
+    Body, Method, Request, Response, StatusCode,
+};
+
+use super::client::Middleware;
+use crate::{Handler, Layer, Service};
+
+/// The value of the `Accept-Encoding` header added to requests.
+const ACCEPT_ENCODING_VALUE: &str = "gzip, deflate, br";
+
+/// Insert this into the [extensions](Request::ext_mut) of a request to receive its response body
+/// as it was sent by the server.
+///
+/// ```ignore
+/// request.ext_mut().insert(NoDecompression);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NoDecompression;
+
+/// A [`Layer`] wrapping a middleware in [`Decompression`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecompressionLayer;
+
+impl<M> Layer<M> for DecompressionLayer {
+    type Service = Decompression<M>;
+
+    fn wrap(&self, inner: M) -> Self::Service {
+        Decompression::new(inner)
+    }
+}
+
+/// A middleware that advertises `gzip`, `deflate` and `br` support in `Accept-Encoding`, and
+/// decodes response bodies compressed with them as they are read.
+///
+/// A decoded response has its `Content-Encoding` and `Content-Length` headers removed. Responses
+/// using an encoding this middleware does not know are left untouched.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Decompression<M> {
+    inner: M,
+}
+
+impl<M> Decompression<M> {
+    /// Wrap `inner`.
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+
+    /// The wrapped middleware.
+    pub fn get_inner(&self) -> &M {
+        &self.inner
+    }
+}
+
+impl<M: Middleware> Service for Decompression<M> {
+    type Context = ();
+    type Error = M::Error;
+}
+
+impl<M: Middleware> Handler<Request> for Decompression<M> {
+    type Response = Response;
+
+    async fn call(
+        &mut self,
+        mut request: Request,
+        cx: &mut Self::Context,
+    ) -> Result<Self::Response, Self::Error> {
+        if request.ext().get::<NoDecompression>().is_some() {
+            return self.inner.call(request, cx).await;
+        }
+
+        if request.header(ACCEPT_ENCODING).is_none() {
+            request.insert_header(ACCEPT_ENCODING, ACCEPT_ENCODING_VALUE);
+        }
+
+        let method = request.method();
+        let mut response = self.inner.call(request, cx).await?;
+        if has_body(method, &response) {
+            decompress(&mut response);
+        }
+        Ok(response)
+    }
+}
+
+enum Encoding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+/// Whether `response` to a `method` request has a body to decode. Responses to `HEAD` requests,
+/// informational, `204 No Content` and `304 Not Modified` responses have none even when they
+/// carry the `Content-Encoding` of the representation.
+fn has_body(method: Method, response: &Response) -> bool {
+    let empty = response
+        .header(CONTENT_LENGTH)
+        .is_some_and(|length| length.as_str().trim() == "0");
+    method != Method::Head
+        && !response.status().is_informational()
+        && !matches!(
+            response.status(),
+            StatusCode::NoContent | StatusCode::NotModified
+        )
+        && !empty
+}
+
+/// Replace the body of `response` with a decoding reader, if all of its encodings are supported.
+fn decompress(response: &mut Response) {
+    let Some(header) = response.header(CONTENT_ENCODING) else {
+        return;
+    };
+
+    // Encodings are listed in the order they were applied.
+    let mut encodings = Vec::new();
+    for encoding in header
+        .iter()
+        .flat_map(|value| value.as_str().split(','))
+        .map(str::trim)
+    {
+        encodings.push(match encoding.to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Encoding::Gzip,
+            "deflate" => Encoding::Deflate,
+            "br" => Encoding::Brotli,
+            "identity" | "" => continue,
+            _ => return,
+        });
+    }
+
+    let body = response.take_body();
+    let mime = body.mime().clone();
+
+    let mut reader: Box<dyn AsyncBufRead + Unpin + Send + Sync> = Box::new(body);
+    for encoding in encodings.into_iter().rev() {
+        reader = match encoding {
+            Encoding::Gzip => Box::new(BufReader::new(GzipDecoder::new(reader))),
+            Encoding::Deflate => Box::new(BufReader::new(ZlibDecoder::new(reader))),
+            Encoding::Brotli => Box::new(BufReader::new(BrotliDecoder::new(reader))),
+        };
+    }
+
+    let mut body = Body::from_reader(reader, None);
+    body.set_mime(mime);
+
+    response.remove_header(CONTENT_ENCODING);
+    response.remove_header(CONTENT_LENGTH);
+    response.set_body(body);
+}
+
+#[cfg(test)]
+mod tests {
+    use async_compression::futures::bufread::{BrotliEncoder, GzipEncoder, ZlibEncoder};
+    use futures::{executor::block_on, io::AsyncReadExt};
+    use http_types::Url;
+
+    use super::*;
+    use crate::http::mock::{Mock, MockMiddleware, MockResponse};
+
+    struct Compressed(&'static str);
+
+    impl Service for Compressed {
+        type Context = ();
+        type Error = http_types::Error;
+    }
+
+    impl Handler<Request> for Compressed {
+        type Response = Response;
+
+        async fn call(&mut self, request: Request, _: &mut ()) -> http_types::Result<Response> {
+            let accepted = request
+                .header(ACCEPT_ENCODING)
+                .map(|h| h.as_str().to_owned());
+            let mut response = Response::new(StatusCode::Ok);
+            response.insert_header("accepted", accepted.unwrap_or_default());
+
+            let mut body = Vec::new();
+            match self.0 {
+                "gzip" => {
+                    GzipEncoder::new(&b"hello gzip"[..])
+                        .read_to_end(&mut body)
+                        .await?
+                }
+                "deflate" => {
+                    ZlibEncoder::new(&b"hello deflate"[..])
+                        .read_to_end(&mut body)
+                        .await?
+                }
+                "gzip, br" => {
+                    BrotliEncoder::new(BufReader::new(GzipEncoder::new(
+                        &b"hello gzip and brotli"[..],
+                    )))
+                    .read_to_end(&mut body)
+                    .await?
+                }
+                _ => {
+                    body.extend_from_slice(b"unknown");
+                    0
+                }
+            };
+            response.insert_header(CONTENT_ENCODING, self.0);
+            response.set_body(body);
+            Ok(response)
+        }
+    }
+
+    fn request() -> Request {
+        Request::new(Method::Get, Url::parse("http://example.com/").unwrap())
+    }
+
+    #[test]
+    fn decodes_gzip() {
+        block_on(async {
+            let mut middleware = Decompression::new(Compressed("gzip"));
+            let mut response = middleware.call(request(), &mut ()).await.unwrap();
+
+            assert_eq!(response.header("accepted").unwrap(), ACCEPT_ENCODING_VALUE);
+            assert!(response.header(CONTENT_ENCODING).is_none());
+            assert!(response.header(CONTENT_LENGTH).is_none());
+            assert_eq!(response.body_string().await.unwrap(), "hello gzip");
+        })
+    }
+
+    #[test]
+    fn decodes_deflate() {
+        block_on(async {
+            let mut middleware = Decompression::new(Compressed("deflate"));
+            let mut response = middleware.call(request(), &mut ()).await.unwrap();
+            assert!(response.header(CONTENT_ENCODING).is_none());
+            assert_eq!(response.body_string().await.unwrap(), "hello deflate");
+        })
+    }
+
+    #[test]
+    fn leaves_responses_without_body() {
+        let mock = MockMiddleware::new();
+        mock.mock(Mock::new(Method::Get, "/none").respond_with(
+            MockResponse::new(StatusCode::NoContent).header("content-encoding", "gzip"),
+        ));
+        mock.mock(
+            Mock::new(Method::Get, "/empty").respond_with(
+                MockResponse::new(StatusCode::Ok)
+                    .header("content-encoding", "gzip")
+                    .header("content-length", "0"),
+            ),
+        );
+        mock.mock(
+            Mock::new(Method::Head, "/")
+                .respond_with(MockResponse::new(StatusCode::Ok).header("content-encoding", "gzip")),
+        );
+
+        block_on(async {
+            let mut middleware = Decompression::new(mock);
+            for (method, path) in [
+                (Method::Get, "/none"),
+                (Method::Get, "/empty"),
+                (Method::Head, "/"),
+            ] {
+                let url = Url::parse("http://example.com/")
+                    .unwrap()
+                    .join(path)
+                    .unwrap();
+                let request = Request::new(method, url);
+                let mut response = middleware.call(request, &mut ()).await.unwrap();
+                assert_eq!(response.header(CONTENT_ENCODING).unwrap(), "gzip");
+                assert_eq!(response.body_string().await.unwrap(), "");
+            }
+        })
+    }
+
+    #[test]
+    fn decodes_stacked_encodings() {
+        block_on(async {
+            let mut middleware = Decompression::new(Compressed("gzip, br"));
+            let mut response = middleware.call(request(), &mut ()).await.unwrap();
+            assert_eq!(
+                response.body_string().await.unwrap(),
+                "hello gzip and brotli"
+            );
+        })
+    }
+
+    #[test]
+    fn leaves_unknown_encodings() {
+        block_on(async {
+            let mut middleware = Decompression::new(Compressed("zstd"));
+            let mut response = middleware.call(request(), &mut ()).await.unwrap();
+            assert_eq!(response.header(CONTENT_ENCODING).unwrap(), "zstd");
+            assert_eq!(response.body_string().await.unwrap(), "unknown");
+        })
+    }
+
+    #[test]
+    fn skipped_per_request() {
+        block_on(async {
+            let mut middleware = Decompression::new(Compressed("gzip"));
+            let mut request = request();
+            request.ext_mut().insert(NoDecompression);
+            let response = middleware.call(request, &mut ()).await.unwrap();
+
+            assert_eq!(response.header("accepted").unwrap(), "");
+            assert_eq!(response.header(CONTENT_ENCODING).unwrap(), "gzip");
+        })
+    }
+}
@@ -0,0 +1,128 @@
+//! A middleware limiting the size of response bodies.
+//!
+//! Bodies are checked as they are read, so a response is returned as usual and reading its body,
+//! with `body_json()` or otherwise, fails with [`BodyTooLarge`] once it goes past the limit. A body
+//! whose `Content-Length` exceeds the limit fails before anything is read from the connection.
+
+use futures::io::BufReader;
+use http_types::{Body, Request, Response};
+
+pub use acril_http::limit::BodyTooLarge;
+use acril_http::limit::Limited;
+
+use super::client::Middleware;
+use crate::{Handler, Layer, Service};
+
+/// A [`Layer`] wrapping a middleware in [`BodyLimit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyLimitLayer {
+    limit: u64,
+}
+
+impl BodyLimitLayer {
+    /// Limit response bodies to `limit` bytes.
+    pub fn new(limit: u64) -> Self {
+        Self { limit }
+    }
+}
+
+impl<M> Layer<M> for BodyLimitLayer {
+    type Service = BodyLimit<M>;
+
+    fn wrap(&self, inner: M) -> Self::Service {
+        BodyLimit::new(inner, self.limit)
+    }
+}
+
+/// A middleware limiting the bodies of the responses of its inner middleware to `limit` bytes.
+///
+/// Endpoints can set a lower limit of their own with the `limit = N` option of `#[endpoint]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BodyLimit<M> {
+    inner: M,
+    limit: u64,
+}
+
+impl<M> BodyLimit<M> {
+    /// Wrap `inner`, limiting response bodies to `limit` bytes.
+    pub fn new(inner: M, limit: u64) -> Self {
+        Self { inner, limit }
+    }
+
+    /// The wrapped middleware.
+    pub fn get_inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// The maximum size of response bodies, in bytes.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl<M: Middleware> Service for BodyLimit<M> {
+    type Context = ();
+    type Error = M::Error;
+}
+
+impl<M: Middleware> Handler<Request> for BodyLimit<M> {
+    type Response = Response;
+
+    async fn call(
+        &mut self,
+        request: Request,
+        cx: &mut Self::Context,
+    ) -> Result<Self::Response, Self::Error> {
+        let mut response = self.inner.call(request, cx).await?;
+        limit_body(&mut response, self.limit);
+        Ok(response)
+    }
+}
+
+/// Replace the body of `response` with one failing with [`BodyTooLarge`] past `limit` bytes.
+pub fn limit_body(response: &mut Response, limit: u64) {
+    let body = response.take_body();
+    let mime = body.mime().clone();
+    let length = body.len().map(|len| len as u64);
+
+    let reader = Limited::with_length(body, length, Some(limit));
+    let length = length.filter(|_| !reader.exceeded());
+    let mut body = Body::from_reader(BufReader::new(reader), length.map(|len| len as usize));
+    body.set_mime(mime);
+    response.set_body(body);
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use http_types::{Method, StatusCode, Url};
+
+    use super::*;
+    use crate::http::mock::{Mock, MockMiddleware, MockResponse};
+
+    fn respond(body: &str, limit: u64) -> http_types::Result<String> {
+        let mock = MockMiddleware::new();
+        mock.mock(Mock::any().respond_with(MockResponse::new(StatusCode::Ok).text(body)));
+        let request = Request::new(Method::Get, Url::parse("http://example.com/").unwrap());
+        let mut middleware = BodyLimitLayer::new(limit).wrap(mock);
+        block_on(async {
+            let mut response = middleware.call(request, &mut ()).await?;
+            response.body_string().await
+        })
+    }
+
+    #[test]
+    fn fails_reading_past_the_limit() {
+        assert_eq!(respond("abcd", 4).unwrap(), "abcd");
+
+        let error = respond("abcde", 4).unwrap_err();
+        assert_eq!(BodyTooLarge::find(&error), Some(&BodyTooLarge { limit: 4 }));
+
+        // a streamed body of unknown length
+        let mut response = Response::new(StatusCode::Ok);
+        response.set_body(Body::from_reader(BufReader::new(&b"abcde"[..]), None));
+        limit_body(&mut response, 4);
+        let error = block_on(response.body_string()).unwrap_err();
+        assert_eq!(BodyTooLarge::find(&error), Some(&BodyTooLarge { limit: 4 }));
+    }
+}
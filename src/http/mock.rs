@@ -0,0 +1,513 @@
+//! An in-process transport returning canned responses, for testing code built on
+//! [`HttpClient`](super::client::HttpClient) without a server.
+//!
+//! ```ignore
+//! let mock = MockMiddleware::new();
+//! mock.mock(
+//!     Mock::new(Method::Get, "/users/1")
+//!         .header("authorization", "Bearer token")
+//!         .respond_with(MockResponse::new(StatusCode::Ok).json(&User { id: 1 }))
+//!         .times(1),
+//! );
+//!
+//! let mut client = HttpClient::new_with(mock.clone())
+//!     .with_base_url(Url::parse("http://api.test").unwrap());
+//! client.call(GetUser { id: 1 }).await?;
+//!
+//! mock.verify();
+//! ```
+
+use std::{
+    fmt::{self, Write},
+    io,
+    sync::{Arc, Mutex},
+};
+
+use futures::{stream, TryStreamExt};
+
+use http_types::{mime, Body, Method, Mime, Request, Response, StatusCode};
+use serde::Serialize;
+
+use crate::{Handler, Service};
+
+/// A canned response, returned each time its [`Mock`] matches.
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: Option<(MockBody, Mime)>,
+}
+
+#[derive(Clone, Debug)]
+enum MockBody {
+    Bytes(Vec<u8>),
+    /// Read one chunk at a time, without a length.
+    Chunked(Vec<Vec<u8>>),
+}
+
+impl MockResponse {
+    /// Create an empty response with `status`.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Add a header.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set an `application/octet-stream` body.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some((MockBody::Bytes(body.into()), mime::BYTE_STREAM));
+        self
+    }
+
+    /// Set a `text/plain` body.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.body = Some((MockBody::Bytes(text.into().into_bytes()), mime::PLAIN));
+        self
+    }
+
+    /// Set an `application/json` body.
+    ///
+    /// # Panics
+    ///
+    /// If `value` fails to serialize.
+    pub fn json(mut self, value: &impl Serialize) -> Self {
+        let body = serde_json::to_vec(value).expect("mock response body failed to serialize");
+        self.body = Some((MockBody::Bytes(body), mime::JSON));
+        self
+    }
+
+    /// Set a body of type `mime`, streamed without a length in `chunks`, each returned by a
+    /// separate read, like a `Transfer-Encoding: chunked` body.
+    pub fn chunked<T: Into<Vec<u8>>>(
+        mut self,
+        chunks: impl IntoIterator<Item = T>,
+        mime: impl Into<Mime>,
+    ) -> Self {
+        let chunks = chunks.into_iter().map(Into::into).collect();
+        self.body = Some((MockBody::Chunked(chunks), mime.into()));
+        self
+    }
+
+    fn to_response(&self) -> Response {
+        let mut response = Response::new(self.status);
+        if let Some((body, mime)) = &self.body {
+            let mut body = match body {
+                MockBody::Bytes(bytes) => Body::from(bytes.clone()),
+                MockBody::Chunked(chunks) => {
+                    let chunks = chunks.clone().into_iter().map(Ok::<_, io::Error>);
+                    Body::from_reader(stream::iter(chunks).into_async_read(), None)
+                }
+            };
+            body.set_mime(mime.clone());
+            response.set_body(body);
+        }
+        for (name, value) in &self.headers {
+            response.append_header(name.as_str(), value.as_str());
+        }
+        response
+    }
+}
+
+/// Matches requests and responds to them with a [`MockResponse`].
+///
+/// All matchers that are set must match. By default, a mock matches any request, and responds
+/// with an empty `200 OK`.
+#[derive(Clone, Debug)]
+pub struct Mock {
+    method: Option<Method>,
+    path: Option<String>,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    json: Option<serde_json::Value>,
+    predicates: Vec<Predicate>,
+    response: MockResponse,
+    expected: Option<usize>,
+    hits: usize,
+}
+
+impl Default for Mock {
+    fn default() -> Self {
+        Self::any()
+    }
+}
+
+impl Mock {
+    /// Match requests with `method` to `path`.
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        Self {
+            method: Some(method),
+            path: Some(path.into()),
+            ..Self::any()
+        }
+    }
+
+    /// Match any request.
+    pub fn any() -> Self {
+        Self {
+            method: None,
+            path: None,
+            query: Vec::new(),
+            headers: Vec::new(),
+            json: None,
+            predicates: Vec::new(),
+            response: MockResponse::new(StatusCode::Ok),
+            expected: None,
+            hits: 0,
+        }
+    }
+
+    /// Only match requests with `method`.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Only match requests to `path`, which does not include the query.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Only match requests with the query parameter `name` set to `value`. Other parameters are
+    /// ignored.
+    pub fn query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    /// Only match requests with the header `name` set to `value`.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Only match requests whose body is JSON equal to `value`.
+    ///
+    /// # Panics
+    ///
+    /// If `value` fails to serialize.
+    pub fn json_body(mut self, value: &impl Serialize) -> Self {
+        self.json = Some(serde_json::to_value(value).expect("mock JSON body failed to serialize"));
+        self
+    }
+
+    /// Only match requests for which `predicate`, given the request and its body, returns `true`.
+    /// This checks what the other matchers cannot, such as form or multipart bodies.
+    pub fn matching(
+        mut self,
+        predicate: impl Fn(&Request, &[u8]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.predicates.push(Predicate(Arc::new(predicate)));
+        self
+    }
+
+    /// Respond to matched requests with `response`.
+    pub fn respond_with(mut self, response: MockResponse) -> Self {
+        self.response = response;
+        self
+    }
+
+    /// Expect this mock to match exactly `times` requests, checked by
+    /// [`MockMiddleware::verify`].
+    pub fn times(mut self, times: usize) -> Self {
+        self.expected = Some(times);
+        self
+    }
+
+    /// The number of requests this mock matched.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    fn matches(&self, request: &Request, body: &[u8]) -> bool {
+        if self.method.is_some_and(|method| method != request.method()) {
+            return false;
+        }
+        if self
+            .path
+            .as_ref()
+            .is_some_and(|path| path != request.url().path())
+        {
+            return false;
+        }
+        if !self.query.iter().all(|(name, value)| {
+            request
+                .url()
+                .query_pairs()
+                .any(|(n, v)| n == name.as_str() && v == value.as_str())
+        }) {
+            return false;
+        }
+        if !self.headers.iter().all(|(name, value)| {
+            request
+                .header(name.as_str())
+                .is_some_and(|values| values.iter().any(|v| v == value.as_str()))
+        }) {
+            return false;
+        }
+        if let Some(json) = &self.json {
+            if !serde_json::from_slice::<serde_json::Value>(body).is_ok_and(|body| &body == json) {
+                return false;
+            }
+        }
+        self.predicates
+            .iter()
+            .all(|Predicate(predicate)| predicate(request, body))
+    }
+
+    fn describe(&self) -> String {
+        let mut description = match (&self.method, &self.path) {
+            (Some(method), Some(path)) => format!("{method} {path}"),
+            (Some(method), None) => format!("{method} *"),
+            (None, Some(path)) => format!("* {path}"),
+            (None, None) => String::from("* *"),
+        };
+        for (name, value) in &self.query {
+            let _ = write!(description, " ?{name}={value}");
+        }
+        for (name, value) in &self.headers {
+            let _ = write!(description, " [{name}: {value}]");
+        }
+        if let Some(json) = &self.json {
+            let _ = write!(description, " {json}");
+        }
+        if !self.predicates.is_empty() {
+            let _ = write!(description, " ({} predicates)", self.predicates.len());
+        }
+        description
+    }
+}
+
+/// A matcher set with [`Mock::matching`].
+#[derive(Clone)]
+struct Predicate(Arc<PredicateFn>);
+
+type PredicateFn = dyn Fn(&Request, &[u8]) -> bool + Send + Sync;
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Predicate")
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    mocks: Vec<Mock>,
+    received: Vec<(Request, Vec<u8>)>,
+}
+
+/// A middleware answering requests with the first registered [`Mock`] matching them, instead of
+/// sending them. Requests no mock matches fail with a `404 Not Found` error.
+///
+/// Clones of a `MockMiddleware` share their mocks and received requests, so a clone can be kept
+/// for assertions after moving the middleware into a client.
+#[derive(Clone, Debug, Default)]
+pub struct MockMiddleware {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockMiddleware {
+    /// Create a middleware without any mocks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `mock`. Mocks are tried in the order they were registered.
+    pub fn mock(&self, mock: Mock) -> &Self {
+        self.state.lock().unwrap().mocks.push(mock);
+        self
+    }
+
+    /// The registered mocks, with their [hits](Mock::hits).
+    pub fn mocks(&self) -> Vec<Mock> {
+        self.state.lock().unwrap().mocks.clone()
+    }
+
+    /// All requests received so far, including the ones no mock matched, with their bodies.
+    pub fn received(&self) -> Vec<Request> {
+        self.state
+            .lock()
+            .unwrap()
+            .received
+            .iter()
+            .map(|(request, body)| {
+                let mut request = request.clone();
+                if !body.is_empty() {
+                    request.set_body(body.clone());
+                }
+                request
+            })
+            .collect()
+    }
+
+    /// Forget the requests received so far, and reset the hits of all mocks.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.received.clear();
+        for mock in &mut state.mocks {
+            mock.hits = 0;
+        }
+    }
+
+    /// Check that every mock with an expectation set by [`Mock::times`] matched the expected
+    /// number of requests.
+    ///
+    /// # Panics
+    ///
+    /// If an expectation is not met, listing all unmet expectations.
+    pub fn verify(&self) {
+        let state = self.state.lock().unwrap();
+        let unmet: Vec<_> = state
+            .mocks
+            .iter()
+            .filter_map(|mock| {
+                let expected = mock.expected?;
+                (mock.hits != expected).then(|| {
+                    format!(
+                        "  {}: expected {expected} requests, received {}",
+                        mock.describe(),
+                        mock.hits
+                    )
+                })
+            })
+            .collect();
+
+        if !unmet.is_empty() {
+            panic!("mock expectations were not met:\n{}", unmet.join("\n"));
+        }
+    }
+}
+
+impl Service for MockMiddleware {
+    type Context = ();
+    type Error = http_types::Error;
+}
+
+impl Handler<Request> for MockMiddleware {
+    type Response = Response;
+
+    async fn call(
+        &mut self,
+        mut request: Request,
+        _cx: &mut Self::Context,
+    ) -> Result<Self::Response, Self::Error> {
+        let body = request.take_body().into_bytes().await?;
+
+        let mut state = self.state.lock().unwrap();
+        state.received.push((request.clone(), body.clone()));
+
+        match state
+            .mocks
+            .iter_mut()
+            .find(|mock| mock.matches(&request, &body))
+        {
+            Some(mock) => {
+                mock.hits += 1;
+                Ok(mock.response.to_response())
+            }
+            None => Err(http_types::Error::from_str(
+                StatusCode::NotFound,
+                format!("no mock matches {} {}", request.method(), request.url()),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use http_types::Url;
+
+    use super::*;
+
+    fn request(method: Method, url: &str) -> Request {
+        Request::new(method, Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn matches_and_records() {
+        block_on(async {
+            let mock = MockMiddleware::new();
+            mock.mock(
+                Mock::new(Method::Post, "/users")
+                    .query("dry_run", "true")
+                    .header("x-api-key", "secret")
+                    .json_body(&serde_json::json!({ "name": "ferris" }))
+                    .respond_with(
+                        MockResponse::new(StatusCode::Created)
+                            .header("location", "/users/1")
+                            .json(&serde_json::json!({ "id": 1 })),
+                    )
+                    .times(1),
+            )
+            .mock(Mock::any().respond_with(MockResponse::new(StatusCode::Accepted)));
+
+            let mut matching = request(Method::Post, "http://api.test/users?dry_run=true&x=1");
+            matching.insert_header("x-api-key", "secret");
+            matching.set_body(Body::from_json(&serde_json::json!({ "name": "ferris" })).unwrap());
+
+            let mut response = mock.clone().call(matching, &mut ()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Created);
+            assert_eq!(response.header("location").unwrap(), "/users/1");
+            assert_eq!(response.body_string().await.unwrap(), "{\"id\":1}");
+
+            // the header is missing, so only the catch-all mock matches
+            let mut other = request(Method::Post, "http://api.test/users?dry_run=true");
+            other.set_body("{\"name\":\"ferris\"}");
+            let response = mock.clone().call(other, &mut ()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Accepted);
+
+            let received = mock.received();
+            assert_eq!(received.len(), 2);
+            let mut first = received.into_iter().next().unwrap();
+            assert_eq!(first.url().path(), "/users");
+            assert_eq!(first.body_string().await.unwrap(), "{\"name\":\"ferris\"}");
+
+            mock.verify();
+        })
+    }
+
+    #[test]
+    fn unmatched_requests_fail() {
+        block_on(async {
+            let mock = MockMiddleware::new();
+            mock.mock(Mock::new(Method::Get, "/a"));
+
+            let error = mock
+                .clone()
+                .call(request(Method::Get, "http://api.test/b"), &mut ())
+                .await
+                .unwrap_err();
+            assert_eq!(error.status(), StatusCode::NotFound);
+            assert_eq!(mock.received().len(), 1);
+        })
+    }
+
+    #[test]
+    #[should_panic(expected = "GET /a: expected 2 requests, received 1")]
+    fn verify_reports_unmet_expectations() {
+        let mock = MockMiddleware::new();
+        mock.mock(Mock::new(Method::Get, "/a").times(2));
+        block_on(
+            mock.clone()
+                .call(request(Method::Get, "http://api.test/a"), &mut ()),
+        )
+        .unwrap();
+
+        mock.reset();
+        block_on(
+            mock.clone()
+                .call(request(Method::Get, "http://api.test/a"), &mut ()),
+        )
+        .unwrap();
+        mock.verify();
+    }
+}
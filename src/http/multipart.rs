@@ -0,0 +1,438 @@
+//! Support for the `multipart` input mode of [`ClientEndpoint`](super::client::ClientEndpoint).
+//!
+//! Fields of a multipart endpoint are sent as text parts, serialized with serde. Fields marked
+//! with `#[file]` are sent as file parts instead, using their [`FilePart`] implementation:
+//!
+//! ```ignore
+//! #[derive(ClientEndpoint, Serialize)]
+//! #[endpoint(Post(multipart) "/avatar" in MyClient)]
+//! struct UploadAvatar {
+//!     user: u64,
+//!     #[file]
+//!     #[serde(skip)]
+//!     avatar: PathBuf,
+//! }
+//! ```
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+pub use acril_http::multipart::{Multipart, Part};
+use http_types::Mime;
+use serde::{ser, Serialize};
+
+/// A value that can be sent as a file part.
+pub trait FilePart {
+    /// Create the part named `name`, or `None` to send nothing.
+    async fn to_part(&self, name: &str) -> http_types::Result<Option<Part>>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FilePart for Path {
+    async fn to_part(&self, name: &str) -> http_types::Result<Option<Part>> {
+        Ok(Some(Part::file(name, self).await?))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FilePart for PathBuf {
+    async fn to_part(&self, name: &str) -> http_types::Result<Option<Part>> {
+        self.as_path().to_part(name).await
+    }
+}
+
+impl FilePart for Vec<u8> {
+    async fn to_part(&self, name: &str) -> http_types::Result<Option<Part>> {
+        Ok(Some(Part::bytes(name, self.clone())))
+    }
+}
+
+impl<T: FilePart> FilePart for Option<T> {
+    async fn to_part(&self, name: &str) -> http_types::Result<Option<Part>> {
+        match self {
+            Some(value) => value.to_part(name).await,
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T: FilePart + ?Sized> FilePart for &T {
+    async fn to_part(&self, name: &str) -> http_types::Result<Option<Part>> {
+        (**self).to_part(name).await
+    }
+}
+
+/// An in-memory file, with a file name and optionally a mime type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct File {
+    pub file_name: String,
+    pub mime: Option<Mime>,
+    pub contents: Vec<u8>,
+}
+
+impl File {
+    /// Create a file named `file_name`, sent as `application/octet-stream`.
+    pub fn new(file_name: impl Into<String>, contents: impl Into<Vec<u8>>) -> Self {
+        Self {
+            file_name: file_name.into(),
+            mime: None,
+            contents: contents.into(),
+        }
+    }
+
+    /// Set the mime type of the file.
+    pub fn mime(mut self, mime: impl Into<Mime>) -> Self {
+        self.mime = Some(mime.into());
+        self
+    }
+}
+
+impl FilePart for File {
+    async fn to_part(&self, name: &str) -> http_types::Result<Option<Part>> {
+        let part = Part::bytes(name, self.contents.clone()).file_name(&self.file_name);
+        Ok(Some(match &self.mime {
+            Some(mime) => part.mime(mime.clone()),
+            None => part,
+        }))
+    }
+}
+
+/// Append `value` to `multipart` as text parts named `name`.
+///
+/// Scalars are sent as a single part, `None` and unit values are omitted, sequences are sent
+/// as one part per element and bytes are sent as an `application/octet-stream` part. Maps and
+/// structs cannot be sent.
+pub fn append_field<T: Serialize + ?Sized>(
+    multipart: &mut Multipart,
+    name: &str,
+    value: &T,
+) -> http_types::Result<()> {
+    value.serialize(FieldSerializer { multipart, name })?;
+    Ok(())
+}
+
+/// The error of serializing a field that cannot be sent as a text part.
+#[derive(Debug)]
+pub struct FieldError(String);
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+impl ser::Error for FieldError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+struct FieldSerializer<'a> {
+    multipart: &'a mut Multipart,
+    name: &'a str,
+}
+
+impl FieldSerializer<'_> {
+    fn text(self, value: impl ToString) -> Result<(), FieldError> {
+        self.multipart
+            .push(Part::text(self.name, value.to_string()));
+        Ok(())
+    }
+
+    fn unsupported(self, kind: &str) -> FieldError {
+        FieldError(format!(
+            "field `{}` is a {kind}, which cannot be sent as a multipart text part",
+            self.name
+        ))
+    }
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = FieldError;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = ser::Impossible<(), FieldError>;
+    type SerializeMap = ser::Impossible<(), FieldError>;
+    type SerializeStruct = ser::Impossible<(), FieldError>;
+    type SerializeStructVariant = ser::Impossible<(), FieldError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), FieldError> {
+        self.text(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), FieldError> {
+        self.multipart.push(Part::bytes(self.name, v));
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), FieldError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), FieldError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), FieldError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), FieldError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), FieldError> {
+        self.text(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), FieldError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), FieldError> {
+        Err(self.unsupported("enum variant with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, FieldError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, FieldError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, FieldError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, FieldError> {
+        Err(self.unsupported("enum variant with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, FieldError> {
+        Err(self.unsupported("map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, FieldError> {
+        Err(self.unsupported("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, FieldError> {
+        Err(self.unsupported("enum variant with data"))
+    }
+}
+
+impl FieldSerializer<'_> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), FieldError> {
+        value.serialize(FieldSerializer {
+            multipart: self.multipart,
+            name: self.name,
+        })
+    }
+}
+
+impl ser::SerializeSeq for FieldSerializer<'_> {
+    type Ok = ();
+    type Error = FieldError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), FieldError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), FieldError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for FieldSerializer<'_> {
+    type Ok = ();
+    type Error = FieldError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), FieldError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), FieldError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for FieldSerializer<'_> {
+    type Ok = ();
+    type Error = FieldError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), FieldError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), FieldError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Visibility {
+        Public,
+    }
+
+    fn encode(fill: impl FnOnce(&mut Multipart) -> http_types::Result<()>) -> String {
+        let mut multipart = Multipart::with_boundary("B");
+        fill(&mut multipart).unwrap();
+        block_on(multipart.into_body().into_string()).unwrap()
+    }
+
+    #[test]
+    fn text_fields() {
+        let body = encode(|m| {
+            append_field(m, "n", &3)?;
+            append_field(m, "tags", &["a", "b"])?;
+            append_field(m, "none", &None::<u8>)?;
+            append_field(m, "visibility", &Visibility::Public)
+        });
+        let values: Vec<_> = body
+            .split("--B")
+            .filter_map(|part| part.split_once("\r\n\r\n"))
+            .map(|(head, value)| (head.trim(), value.trim_end()))
+            .collect();
+
+        assert_eq!(
+            values,
+            [
+                ("Content-Disposition: form-data; name=\"n\"", "3"),
+                ("Content-Disposition: form-data; name=\"tags\"", "a"),
+                ("Content-Disposition: form-data; name=\"tags\"", "b"),
+                (
+                    "Content-Disposition: form-data; name=\"visibility\"",
+                    "public"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_structs() {
+        #[derive(Serialize)]
+        struct Nested {
+            a: u8,
+        }
+
+        let mut multipart = Multipart::new();
+        assert!(append_field(&mut multipart, "nested", &Nested { a: 1 }).is_err());
+    }
+
+    #[test]
+    fn files() {
+        let file = File::new("notes.txt", "hi").mime(http_types::mime::PLAIN);
+        let body = encode(|m| {
+            m.push(block_on(file.to_part("notes"))?.unwrap());
+            assert!(block_on(None::<File>.to_part("missing"))?.is_none());
+            Ok(())
+        });
+
+        assert!(body.contains(
+            "name=\"notes\"; filename=\"notes.txt\"\r\n\
+             Content-Type: text/plain;charset=utf-8\r\n\r\nhi\r\n"
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+//! Paginated endpoints, whose items are streamed by [`HttpClient::paginate`].
+//!
+//! The `paginate(...)` option of `#[endpoint]` implements [`PaginatedEndpoint`] for an endpoint,
+//! given the type of the items (`item = Type`), the path of the items in the output
+//! (`items = path.to.items`, the output itself if omitted), and one of these strategies:
+//!
+//! - `cursor = field, next = path.to.cursor`: the next page is requested by setting `field` to
+//!   the cursor (or page token) found at the path in the output, an `Option`, until it is `None`.
+//! - `page = field`: the next page is requested by incrementing `field`, until a page is empty.
+//! - `offset = field`: the next page is requested by adding the number of items received to
+//!   `field`, until a page is empty.
+//! - `link`: the next page is requested from the `next` URL of the `Link` header, until there is
+//!   none.
+//!
+//! All strategies but `link` clone the endpoint to request the next page.
+//!
+//! Finding an empty page takes a request after the last page. With `size = field`, naming the
+//! integer field holding the number of items requested per page, `page` and `offset` pagination
+//! also stop after a page with fewer items, saving that request.
+//!
+//! ```ignore
+//! #[derive(ClientEndpoint, Serialize, Clone)]
+//! #[endpoint(
+//!     Get(query) "/orders" in MyClient -> OrdersPage,
+//!     paginate(cursor = after, next = next_cursor, item = Order, items = orders)
+//! )]
+//! struct ListOrders {
+//!     after: Option<String>,
+//! }
+//!
+//! let mut orders = pin!(client.paginate(ListOrders { after: None }));
+//! while let Some(order) = orders.try_next().await? { /* ... */ }
+//! ```
+//!
+//! [`HttpClient::paginate`]: super::client::HttpClient::paginate
+
+use std::ops::AddAssign;
+
+use futures::{stream, Stream, TryStreamExt};
+use http_types::{headers::HeaderValue, Response, Url};
+
+use super::client::ClientEndpoint;
+
+/// A page of items.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Page<T, C> {
+    pub items: Vec<T>,
+    /// The cursor of the next page, if there is one.
+    pub next: Option<C>,
+}
+
+/// An endpoint returning a page of items.
+pub trait PaginatedEndpoint: ClientEndpoint {
+    /// The type of the items.
+    type Item;
+    /// What locates a page after the first one.
+    type Cursor;
+
+    /// Fetch the page at `cursor`, or the first page if `cursor` is `None`.
+    async fn fetch_page(
+        &self,
+        context: &mut Self::Context,
+        cursor: Option<&Self::Cursor>,
+    ) -> Result<Page<Self::Item, Self::Cursor>, Self::Error>;
+}
+
+#[doc(hidden)]
+pub fn advance<T: TryFrom<usize> + AddAssign>(offset: &mut T, by: usize) {
+    *offset += T::try_from(by)
+        .ok()
+        .expect("the number of items overflows the offset");
+}
+
+/// Whether a page of `len` items is the last one, when `size` items were requested.
+#[doc(hidden)]
+pub fn is_last_page<T: TryInto<usize>>(len: usize, size: T) -> bool {
+    size.try_into().is_ok_and(|size| len < size)
+}
+
+/// Stream the items of all pages of `endpoint`, fetching them with `context`. This is the stream
+/// of the async and blocking `HttpClient::paginate`.
+pub(crate) fn items<'a, C, E: PaginatedEndpoint<Context = C> + 'a>(
+    context: &'a mut C,
+    endpoint: E,
+) -> impl Stream<Item = Result<E::Item, E::Error>> + 'a {
+    // `None` once there are no more pages, `Some(None)` for the first page
+    let cursor: Option<Option<E::Cursor>> = Some(None);
+
+    stream::try_unfold(
+        (context, endpoint, cursor),
+        |(context, endpoint, cursor)| async move {
+            let Some(cursor) = cursor else {
+                return Ok(None);
+            };
+
+            let page = endpoint.fetch_page(context, cursor.as_ref()).await?;
+            Ok(Some((page.items, (context, endpoint, page.next.map(Some)))))
+        },
+    )
+    .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+    .try_flatten()
+}
+
+/// The URL with `rel="next"` in the `Link` header of `response`, resolved against `url`.
+pub fn next_link(response: &Response, url: &Url) -> Option<String> {
+    response
+        .header("link")?
+        .iter()
+        .find_map(|value| find_next(value, url))
+}
+
+fn find_next(value: &HeaderValue, base: &Url) -> Option<String> {
+    let mut rest = value.as_str();
+
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..].find('>')? + start;
+        let target = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        // the parameters run until the next link, skipping commas in quoted strings
+        let mut quoted = false;
+        let params_end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                c == ',' && !quoted
+            })
+            .map_or(rest.len(), |(idx, _)| idx);
+        let params = &rest[..params_end];
+        rest = &rest[params_end..];
+
+        let is_next = params.split(';').any(|param| {
+            let Some((name, value)) = param.split_once('=') else {
+                return false;
+            };
+            name.trim().eq_ignore_ascii_case("rel")
+                && value
+                    .trim()
+                    .trim_matches('"')
+                    .split_ascii_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("next"))
+        });
+        if is_next {
+            return base.join(target).ok().map(String::from);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use http_types::StatusCode;
+
+    use super::*;
+
+    #[test]
+    fn parses_link_header() {
+        let base = Url::parse("https://api.test/orders?page=1").unwrap();
+        let mut response = Response::new(StatusCode::Ok);
+
+        assert_eq!(next_link(&response, &base), None);
+
+        response.insert_header(
+            "Link",
+            "<https://api.test/orders?page=1,2>; rel=\"prev first\"; title=\"a, b\", \
+             </orders?page=2>; rel=\"next\", <https://api.test/orders?page=9>; rel=last",
+        );
+        assert_eq!(
+            next_link(&response, &base).as_deref(),
+            Some("https://api.test/orders?page=2")
+        );
+
+        response.insert_header("Link", "<https://api.test/orders?page=9>; rel=last");
+        assert_eq!(next_link(&response, &base), None);
+    }
+}
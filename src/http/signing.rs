@@ -0,0 +1,677 @@
+//! A request signing middleware, with HMAC-SHA256 and AWS Signature Version 4 signers.
+
+use std::{
+    fmt::{self, Display, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use http_types::{
+    headers::{CONTENT_TYPE, HOST},
+    Body, Method, Request, Response,
+};
+use sha2::{Digest, Sha256};
+
+use super::client::Middleware;
+use crate::{Handler, Layer, Service};
+
+/// The payload hash used in canonical requests when the body is not hashed.
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// A request in canonical form, as described by the
+/// [AWS Signature Version 4](https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html)
+/// specification, which other signing schemes can reuse.
+///
+/// The [`Display`] implementation writes the full canonical request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanonicalRequest {
+    /// The request method.
+    pub method: Method,
+    /// The URI-encoded path.
+    pub path: String,
+    /// The URI-encoded query parameters, sorted by name and value and joined with `&`.
+    pub query: String,
+    /// Header names in lowercase and their trimmed values, sorted by name. Repeated headers have
+    /// their values joined with `,`.
+    pub headers: Vec<(String, String)>,
+    /// The lowercase hex SHA-256 hash of the body, if the signer asked for it.
+    pub payload_hash: Option<String>,
+}
+
+impl CanonicalRequest {
+    /// Canonicalize `request`, which should have its `Host` header set.
+    ///
+    /// Each segment of the path is decoded and then encoded once, as S3 expects.
+    pub fn new(request: &Request, payload_hash: Option<String>) -> Self {
+        let url = request.url();
+
+        let path = url
+            .path()
+            .split('/')
+            .map(|segment| uri_encode(&percent_decode(segment)))
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut query = url
+            .query_pairs()
+            .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+            .collect::<Vec<_>>();
+        query.sort();
+        let query = query
+            .into_iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut headers = request
+            .iter()
+            .map(|(name, values)| {
+                let values = values
+                    .iter()
+                    .map(|v| v.as_str().split_whitespace().collect::<Vec<_>>().join(" "))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                (name.as_str().to_ascii_lowercase(), values)
+            })
+            .collect::<Vec<_>>();
+        headers.sort();
+
+        Self {
+            method: request.method(),
+            path: if path.is_empty() {
+                String::from("/")
+            } else {
+                path
+            },
+            query,
+            headers,
+            payload_hash,
+        }
+    }
+
+    /// Encode the canonical path a second time, as all AWS services but S3 expect.
+    pub fn double_encode_path(mut self) -> Self {
+        self.path = self
+            .path
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/");
+        self
+    }
+
+    /// The names of the canonical headers, joined with `;`.
+    pub fn signed_headers(&self) -> String {
+        self.headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    /// The payload hash, or [`UNSIGNED_PAYLOAD`].
+    pub fn payload_hash(&self) -> &str {
+        self.payload_hash.as_deref().unwrap_or(UNSIGNED_PAYLOAD)
+    }
+}
+
+impl Display for CanonicalRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.method)?;
+        writeln!(f, "{}", self.path)?;
+        writeln!(f, "{}", self.query)?;
+        for (name, value) in &self.headers {
+            writeln!(f, "{name}:{value}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{}", self.signed_headers())?;
+        f.write_str(self.payload_hash())
+    }
+}
+
+/// A signing scheme.
+pub trait Signer {
+    /// Whether [`CanonicalRequest::payload_hash`] is needed. Request bodies are only buffered
+    /// for signers that need their hash.
+    fn needs_payload_hash(&self) -> bool {
+        true
+    }
+
+    /// Whether the path of the canonical request is
+    /// [encoded twice](CanonicalRequest::double_encode_path).
+    fn double_encode_path(&self) -> bool {
+        false
+    }
+
+    /// Add headers that have to be part of the signature, like a timestamp, before the request is
+    /// canonicalized.
+    fn prepare(&mut self, _request: &mut Request, _payload_hash: Option<&str>) {}
+
+    /// Sign the canonicalized request, adding the signature to `request`.
+    fn sign(&mut self, canonical: &CanonicalRequest, request: &mut Request);
+}
+
+/// Sign `request` with `signer`.
+///
+/// If the request has no `Host` header, one is added from its URL, since most schemes sign it.
+pub async fn sign_request<S: Signer>(
+    signer: &mut S,
+    request: &mut Request,
+) -> http_types::Result<()> {
+    if request.header(HOST).is_none() {
+        let url = request.url();
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => return Err(http_types::format_err!("Missing hostname")),
+        };
+        request.insert_header(HOST, host);
+    }
+
+    let payload_hash = if signer.needs_payload_hash() {
+        let body = replace_body(request, Body::empty());
+        let mime = body.mime().clone();
+        let bytes = body.into_bytes().await?;
+        let hash = hex(&Sha256::digest(&bytes));
+
+        let mut body = Body::from_bytes(bytes);
+        body.set_mime(mime);
+        replace_body(request, body);
+        Some(hash)
+    } else {
+        None
+    };
+
+    signer.prepare(request, payload_hash.as_deref());
+    let mut canonical = CanonicalRequest::new(request, payload_hash);
+    if signer.double_encode_path() {
+        canonical = canonical.double_encode_path();
+    }
+    signer.sign(&canonical, request);
+
+    Ok(())
+}
+
+/// Replace the body of `request`, without adding the `Content-Type` header `http_types` adds for
+/// bodies when it is missing, which would otherwise end up in the signature.
+fn replace_body(request: &mut Request, body: Body) -> Body {
+    let had_content_type = request.header(CONTENT_TYPE).is_some();
+    let body = request.replace_body(body);
+    if !had_content_type {
+        request.remove_header(CONTENT_TYPE);
+    }
+    body
+}
+
+/// Signs requests with HMAC-SHA256 over a timestamp and the canonical request.
+///
+/// The string to sign is made of the timestamp (in milliseconds since the Unix epoch), the
+/// method, the canonical path, the canonical query and the payload hash, separated by newlines.
+/// The timestamp and the hex signature are sent in the `X-Timestamp` and `X-Signature` headers by
+/// default.
+#[derive(Clone, Debug)]
+pub struct HmacSha256 {
+    key: Vec<u8>,
+    signature_header: String,
+    timestamp_header: String,
+    payload: bool,
+    time: Option<SystemTime>,
+    timestamp: String,
+}
+
+impl HmacSha256 {
+    /// Sign with `key`.
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            signature_header: String::from("X-Signature"),
+            timestamp_header: String::from("X-Timestamp"),
+            payload: true,
+            time: None,
+            timestamp: String::new(),
+        }
+    }
+
+    /// The header to send the signature in.
+    pub fn signature_header(mut self, name: impl Into<String>) -> Self {
+        self.signature_header = name.into();
+        self
+    }
+
+    /// The header to send the timestamp in.
+    pub fn timestamp_header(mut self, name: impl Into<String>) -> Self {
+        self.timestamp_header = name.into();
+        self
+    }
+
+    /// Whether to sign the hash of the body. Defaults to `true`.
+    pub fn payload(mut self, payload: bool) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Sign with a fixed time instead of the current one.
+    pub fn at(mut self, time: SystemTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+impl Signer for HmacSha256 {
+    fn needs_payload_hash(&self) -> bool {
+        self.payload
+    }
+
+    fn prepare(&mut self, request: &mut Request, _payload_hash: Option<&str>) {
+        let time = self.time.unwrap_or_else(SystemTime::now);
+        self.timestamp = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+            .to_string();
+        request.insert_header(self.timestamp_header.as_str(), self.timestamp.as_str());
+    }
+
+    fn sign(&mut self, canonical: &CanonicalRequest, request: &mut Request) {
+        let string_to_sign = format!(
+            "{}\n{}\n{}\n{}\n{}",
+            self.timestamp,
+            canonical.method,
+            canonical.path,
+            canonical.query,
+            canonical.payload_hash(),
+        );
+        let signature = hex(&hmac(&self.key, string_to_sign.as_bytes()));
+        request.insert_header(self.signature_header.as_str(), signature);
+    }
+}
+
+/// Signs requests with [AWS Signature Version 4](https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_aws-signing.html),
+/// sending the signature in the `Authorization` header.
+///
+/// All headers present on the request when it is signed are part of the signature.
+#[derive(Clone, Debug)]
+pub struct SigV4 {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    region: String,
+    service: String,
+    payload: bool,
+    content_sha256: bool,
+    double_encode_path: bool,
+    time: Option<SystemTime>,
+    date: (String, String),
+}
+
+impl SigV4 {
+    /// Sign for `service` in `region` with the given credentials.
+    pub fn new(
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+        region: impl Into<String>,
+        service: impl Into<String>,
+    ) -> Self {
+        Self {
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            session_token: None,
+            region: region.into(),
+            service: service.into(),
+            payload: true,
+            content_sha256: false,
+            double_encode_path: true,
+            time: None,
+            date: Default::default(),
+        }
+    }
+
+    /// Send temporary credentials' session token in `X-Amz-Security-Token`.
+    pub fn session_token(mut self, token: impl Into<String>) -> Self {
+        self.session_token = Some(token.into());
+        self
+    }
+
+    /// Whether to sign the hash of the body, or use `UNSIGNED-PAYLOAD`. Defaults to `true`.
+    pub fn payload(mut self, payload: bool) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Whether to send the payload hash in `X-Amz-Content-Sha256`, as S3 requires. Defaults to
+    /// `false`.
+    pub fn content_sha256_header(mut self, content_sha256: bool) -> Self {
+        self.content_sha256 = content_sha256;
+        self
+    }
+
+    /// Whether to encode the path twice in the canonical request, as all services but S3 expect.
+    /// Defaults to `true`, set it to `false` for S3.
+    pub fn double_encode_path(mut self, double_encode_path: bool) -> Self {
+        self.double_encode_path = double_encode_path;
+        self
+    }
+
+    /// Sign with a fixed time instead of the current one.
+    pub fn at(mut self, time: SystemTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+impl Signer for SigV4 {
+    fn needs_payload_hash(&self) -> bool {
+        self.payload
+    }
+
+    fn double_encode_path(&self) -> bool {
+        self.double_encode_path
+    }
+
+    fn prepare(&mut self, request: &mut Request, payload_hash: Option<&str>) {
+        self.date = amz_date(self.time.unwrap_or_else(SystemTime::now));
+        request.insert_header("x-amz-date", self.date.1.as_str());
+        if let Some(token) = &self.session_token {
+            request.insert_header("x-amz-security-token", token.as_str());
+        }
+        if self.content_sha256 {
+            request.insert_header(
+                "x-amz-content-sha256",
+                payload_hash.unwrap_or(UNSIGNED_PAYLOAD),
+            );
+        }
+    }
+
+    fn sign(&mut self, canonical: &CanonicalRequest, request: &mut Request) {
+        let (date, datetime) = &self.date;
+        let scope = format!("{date}/{}/{}/aws4_request", self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{datetime}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical.to_string().as_bytes())),
+        );
+
+        let key = hmac(
+            format!("AWS4{}", self.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let key = hmac(&key, self.region.as_bytes());
+        let key = hmac(&key, self.service.as_bytes());
+        let key = hmac(&key, b"aws4_request");
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+        request.insert_header(
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={}, Signature={signature}",
+                self.access_key,
+                canonical.signed_headers(),
+            ),
+        );
+    }
+}
+
+/// A [`Layer`] wrapping a middleware in [`Signing`], each with a clone of the signer.
+#[derive(Clone, Debug)]
+pub struct SigningLayer<S>(pub S);
+
+impl<M, S: Clone> Layer<M> for SigningLayer<S> {
+    type Service = Signing<M, S>;
+
+    fn wrap(&self, inner: M) -> Self::Service {
+        Signing::new(inner, self.0.clone())
+    }
+}
+
+/// A middleware that signs every request with `S`.
+#[derive(Clone, Debug)]
+pub struct Signing<M, S> {
+    inner: M,
+    signer: S,
+}
+
+impl<M, S> Signing<M, S> {
+    /// Wrap `inner`, signing requests with `signer`.
+    pub fn new(inner: M, signer: S) -> Self {
+        Self { inner, signer }
+    }
+
+    /// The wrapped middleware.
+    pub fn get_inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// The signer.
+    pub fn signer(&self) -> &S {
+        &self.signer
+    }
+}
+
+impl<M: Middleware, S> Service for Signing<M, S> {
+    type Context = ();
+    type Error = M::Error;
+}
+
+impl<M, S> Handler<Request> for Signing<M, S>
+where
+    M: Middleware,
+    M::Error: From<http_types::Error>,
+    S: Signer,
+{
+    type Response = Response;
+
+    async fn call(
+        &mut self,
+        mut request: Request,
+        cx: &mut Self::Context,
+    ) -> Result<Self::Response, Self::Error> {
+        sign_request(&mut self.signer, &mut request).await?;
+        self.inner.call(request, cx).await
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+/// Percent-encode everything but unreserved characters, as specified for SigV4.
+fn uri_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escaped = (bytes[idx] == b'%')
+            .then(|| bytes.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            None => {
+                decoded.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The date (`YYYYMMDD`) and date-time (`YYYYMMDD'T'HHMMSS'Z'`) of `time` in UTC.
+fn amz_date(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let date = format!("{year:04}{month:02}{day:02}");
+    let datetime = format!(
+        "{date}T{:02}{:02}{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    );
+    (date, datetime)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+    use http_types::Url;
+
+    use super::*;
+
+    /// 2015-08-30T12:36:00Z, the time used by the AWS SigV4 test suite.
+    fn test_time() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1440938160)
+    }
+
+    fn aws() -> SigV4 {
+        SigV4::new(
+            "AKIDEXAMPLE",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "us-east-1",
+            "service",
+        )
+        .at(test_time())
+    }
+
+    #[test]
+    fn amz_dates() {
+        assert_eq!(
+            amz_date(test_time()),
+            (String::from("20150830"), String::from("20150830T123600Z"))
+        );
+        assert_eq!(amz_date(UNIX_EPOCH).1, "19700101T000000Z");
+        assert_eq!(
+            amz_date(UNIX_EPOCH + Duration::from_secs(951782400)).1,
+            "20000229T000000Z"
+        );
+    }
+
+    #[test]
+    fn sigv4_get_vanilla() {
+        let mut request = Request::new(
+            Method::Get,
+            Url::parse("https://example.amazonaws.com/").unwrap(),
+        );
+        block_on(sign_request(&mut aws(), &mut request)).unwrap();
+
+        assert_eq!(
+            request.header("authorization").unwrap(),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn sigv4_encodes_the_path_twice() {
+        let url = Url::parse("https://example.amazonaws.com/documents%20and%20settings/").unwrap();
+        let mut request = Request::new(Method::Get, url.clone());
+        block_on(sign_request(&mut aws(), &mut request)).unwrap();
+        assert!(request.header("authorization").unwrap().as_str().ends_with(
+            "Signature=23c9727f014f850a592311a0323b422f9c1e3ad2d406c610f00d64ab3272c75a"
+        ));
+
+        let request = Request::new(Method::Get, url);
+        assert_eq!(
+            CanonicalRequest::new(&request, None).path,
+            "/documents%20and%20settings/"
+        );
+        assert_eq!(
+            CanonicalRequest::new(&request, None)
+                .double_encode_path()
+                .path,
+            "/documents%2520and%2520settings/"
+        );
+    }
+
+    #[test]
+    fn canonical_request() {
+        let mut request = Request::new(
+            Method::Post,
+            Url::parse("https://example.com/a%20b/c?b=2&a=3&a=1&c=x+y").unwrap(),
+        );
+        request.insert_header(HOST, "example.com");
+        request.append_header("X-Multi", "  one   two ");
+        request.append_header("X-Multi", "three");
+
+        let canonical = CanonicalRequest::new(&request, None);
+        assert_eq!(
+            canonical.to_string(),
+            "POST\n/a%20b/c\na=1&a=3&b=2&c=x%20y\nhost:example.com\nx-multi:one two,three\n\nhost;x-multi\nUNSIGNED-PAYLOAD"
+        );
+    }
+
+    #[test]
+    fn hmac_buffers_only_when_hashing() {
+        let mut request = Request::new(
+            Method::Post,
+            Url::parse("https://example.com/orders").unwrap(),
+        );
+        request.set_body(Body::from_reader(
+            futures::io::Cursor::new(b"{}".to_vec()),
+            None,
+        ));
+
+        let mut signer = HmacSha256::new("secret").at(test_time()).payload(false);
+        block_on(sign_request(&mut signer, &mut request)).unwrap();
+        assert_eq!(request.len(), None);
+        assert_eq!(request.header("x-timestamp").unwrap(), "1440938160000");
+
+        let mut signer = HmacSha256::new("secret").at(test_time());
+        block_on(sign_request(&mut signer, &mut request)).unwrap();
+        assert_eq!(request.len(), Some(2));
+
+        let string_to_sign = format!(
+            "1440938160000\nPOST\n/orders\n\n{}",
+            hex(&Sha256::digest(b"{}"))
+        );
+        assert_eq!(
+            request.header("x-signature").unwrap(),
+            hex(&hmac(b"secret", string_to_sign.as_bytes())).as_str()
+        );
+        assert_eq!(block_on(request.body_string()).unwrap(), "{}");
+    }
+}
@@ -0,0 +1,225 @@
+//! Incremental parsers for streamed response bodies, used by the `ndjson` and `sse` output modes
+//! of [`ClientEndpoint`](super::client::ClientEndpoint).
+
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{
+    io::{AsyncBufRead, AsyncBufReadExt, Lines},
+    ready, Stream,
+};
+use http_types::Body;
+use serde::de::DeserializeOwned;
+
+/// A stream of newline-delimited JSON values. Blank lines are skipped.
+#[derive(Debug)]
+pub struct NdJson<T, R = Body> {
+    lines: Lines<R>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, R: AsyncBufRead> NdJson<T, R> {
+    /// Parse the lines read from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned, R: AsyncBufRead + Unpin> Stream for NdJson<T, R> {
+    type Item = http_types::Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            return Poll::Ready(match ready!(Pin::new(&mut self.lines).poll_next(cx)) {
+                None => None,
+                Some(Err(e)) => Some(Err(e.into())),
+                Some(Ok(line)) if line.trim().is_empty() => continue,
+                Some(Ok(line)) => Some(serde_json::from_str(&line).map_err(Into::into)),
+            });
+        }
+    }
+}
+
+/// A [server-sent event](https://html.spec.whatwg.org/multipage/server-sent-events.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The event type, `message` if the server did not specify one.
+    pub event: String,
+    /// The event data. Multiple `data` lines are joined with newlines.
+    pub data: String,
+    /// The last event ID sent by the server, as of this event.
+    pub id: Option<String>,
+    /// The reconnection time requested by the server with this event, if any.
+    pub retry: Option<Duration>,
+}
+
+impl Event {
+    /// Deserialize [`data`](Event::data) as JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.data)
+    }
+}
+
+/// A stream of [server-sent events](Event) parsed from an `text/event-stream` body.
+#[derive(Debug)]
+pub struct EventStream<R = Body> {
+    lines: Lines<R>,
+    event: String,
+    data: String,
+    last_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl<R: AsyncBufRead> EventStream<R> {
+    /// Parse the events read from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            event: String::new(),
+            data: String::new(),
+            last_id: None,
+            retry: None,
+        }
+    }
+
+    /// The ID of the last event received, to send in `Last-Event-ID` when reconnecting.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_id.as_deref()
+    }
+
+    /// Process a line, returning an event if it completed one.
+    fn process(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            let event = std::mem::take(&mut self.event);
+            let mut data = std::mem::take(&mut self.data);
+            let retry = self.retry.take();
+            if data.is_empty() {
+                return None;
+            }
+            data.pop();
+
+            return Some(Event {
+                event: if event.is_empty() {
+                    String::from("message")
+                } else {
+                    event
+                },
+                data,
+                id: self.last_id.clone(),
+                retry,
+            });
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some(("", _)) => return None,
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event = value.to_owned(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_owned()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry = value.parse().ok().map(Duration::from_millis);
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for EventStream<R> {
+    type Item = http_types::Result<Event>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match ready!(Pin::new(&mut self.lines).poll_next(cx)) {
+                // an event that isn't terminated by a blank line is discarded
+                None => return Poll::Ready(None),
+                Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                Some(Ok(line)) => {
+                    if let Some(event) = self.process(&line) {
+                        return Poll::Ready(Some(Ok(event)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, StreamExt, TryStreamExt};
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Trade {
+        price: u32,
+    }
+
+    #[test]
+    fn ndjson() {
+        let body = Body::from("{\"price\":1}\n\n{\"price\":2}\r\n{\"price\":3}");
+        let trades = block_on(NdJson::<Trade>::new(body).try_collect::<Vec<_>>()).unwrap();
+        assert_eq!(
+            trades,
+            [Trade { price: 1 }, Trade { price: 2 }, Trade { price: 3 }]
+        );
+
+        let mut items = NdJson::<Trade>::new(Body::from("{\"price\":1}\nnot json\n"));
+        assert!(block_on(items.next()).unwrap().is_ok());
+        assert!(block_on(items.next()).unwrap().is_err());
+    }
+
+    #[test]
+    fn sse() {
+        let body = Body::from(
+            ": comment\n\
+             data: first\n\
+             data:  line\n\
+             \n\
+             event: trade\n\
+             id: 7\n\
+             retry: 3000\n\
+             data: {\"price\":4}\n\
+             \n\
+             id\n\
+             \n\
+             data: unterminated",
+        );
+        let events = block_on(EventStream::new(body).try_collect::<Vec<_>>()).unwrap();
+
+        assert_eq!(
+            events,
+            [
+                Event {
+                    event: String::from("message"),
+                    data: String::from("first\n line"),
+                    id: None,
+                    retry: None,
+                },
+                Event {
+                    event: String::from("trade"),
+                    data: String::from("{\"price\":4}"),
+                    id: Some(String::from("7")),
+                    retry: Some(Duration::from_secs(3)),
+                },
+            ]
+        );
+        assert_eq!(events[1].json::<Trade>().unwrap(), Trade { price: 4 });
+    }
+}
@@ -2,6 +2,12 @@
 #![allow(async_fn_in_trait, incomplete_features)]
 #![feature(associated_type_bounds, return_type_notation)]
 
+#[cfg(feature = "http")]
+#[doc(hidden)]
+pub use serde;
+#[cfg(feature = "http")]
+#[doc(hidden)]
+pub use serde_json;
 #[cfg(feature = "macros")]
 #[doc(hidden)]
 pub use serde_urlencoded;
@@ -0,0 +1,88 @@
+//! Endpoints run to completion on the current thread, outside of any executor.
+
+use acril::{http::blocking, prelude::http::*};
+use serde::{Deserialize, Serialize};
+
+use super::{json_mock, mock, Api};
+
+endpoint_error!(http_types::Error);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Pet {
+    name: String,
+}
+
+#[with_builder(count_pets, blocking)]
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/pets/count" in Api -> u64)]
+struct CountPets;
+
+#[with_builder(fetch_pet, blocking)]
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/pets/{id}" in Api -> Pet)]
+struct FetchPet {
+    #[required]
+    id: u64,
+}
+
+#[with_builder(find_pets, blocking)]
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/pets" in Api -> Vec<Pet>)]
+struct FindPets {
+    #[query]
+    tag: Option<String>,
+}
+
+#[derive(ClientEndpoint, Serialize, Clone)]
+#[endpoint(
+    Get(query) "/numbers" in HttpClient<acril::http::mock::MockMiddleware> -> Vec<u32>,
+    paginate(page = page, item = u32)
+)]
+struct ListNumbers {
+    page: u32,
+}
+
+fn rex() -> Pet {
+    Pet {
+        name: String::from("Rex"),
+    }
+}
+
+#[test]
+fn blocking_methods() {
+    let mock = mock([
+        json_mock(Method::Get, "/pets/count", 1).times(1),
+        json_mock(Method::Get, "/pets/1", rex()).times(1),
+        json_mock(Method::Get, "/pets", [rex()])
+            .query("tag", "good")
+            .times(1),
+    ]);
+    let mut api = Api::new(&mock);
+
+    assert_eq!(api.count_pets_blocking().unwrap(), 1);
+    assert_eq!(api.fetch_pet_blocking(1).unwrap(), rex());
+    let pets = api.find_pets().tag("good").execute_blocking();
+    assert_eq!(pets.unwrap(), [rex()]);
+    mock.verify();
+}
+
+#[test]
+fn blocking_pagination() {
+    let mock = mock([
+        json_mock(Method::Get, "/numbers", [1, 2])
+            .query("page", "1")
+            .times(1),
+        json_mock(Method::Get, "/numbers", [3])
+            .query("page", "2")
+            .times(1),
+        json_mock(Method::Get, "/numbers", [0; 0])
+            .query("page", "3")
+            .times(1),
+    ]);
+    let mut client = blocking::HttpClient::new_with(mock.clone())
+        .with_base_url(Url::parse("http://api.test").unwrap());
+
+    let numbers = client.paginate(ListNumbers { page: 1 });
+    assert_eq!(numbers.collect::<Result<Vec<_>, _>>().unwrap(), [1, 2, 3]);
+    mock.verify();
+}
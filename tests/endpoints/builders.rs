@@ -0,0 +1,79 @@
+use acril::{
+    http::mock::{Mock, MockResponse},
+    prelude::http::*,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{json_mock, mock, run, Api};
+
+endpoint_error!(http_types::Error);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Pet {
+    name: String,
+    age: u32,
+    tag: Option<String>,
+}
+
+#[with_builder(add_pet)]
+#[derive(ClientEndpoint, Serialize)]
+#[endpoint(Post(json) "/pets" in Api -> Pet)]
+struct AddPet {
+    #[required]
+    name: String,
+    #[default = 3]
+    age: u32,
+    tag: Option<String>,
+}
+
+#[with_builder(get_pet)]
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/pets/{_0}" in Api -> Pet)]
+struct GetPet(#[required] u64);
+
+fn rex(tag: Option<&str>) -> Pet {
+    Pet {
+        name: String::from("Rex"),
+        age: 3,
+        tag: tag.map(String::from),
+    }
+}
+
+#[test]
+fn builders_set_defaults() {
+    let mock = mock([
+        Mock::new(Method::Post, "/pets")
+            .json_body(&json!({ "name": "Rex", "age": 3, "tag": null }))
+            .respond_with(MockResponse::new(StatusCode::Ok).json(&rex(None)))
+            .times(1),
+        Mock::new(Method::Post, "/pets")
+            .json_body(&json!({ "name": "Rex", "age": 3, "tag": "good" }))
+            .respond_with(MockResponse::new(StatusCode::Ok).json(&rex(Some("good"))))
+            .times(1),
+    ]);
+
+    run(&mock, async {
+        let mut api = Api::new(&mock);
+        let pet = api.add_pet().name("Rex").execute().await.unwrap();
+        assert_eq!(pet, rex(None));
+
+        // `Option` fields are set with their inner type, in any order
+        let pet = api.add_pet().tag("good").name("Rex").execute().await;
+        assert_eq!(pet.unwrap(), rex(Some("good")));
+    });
+}
+
+#[test]
+fn required_fields_are_arguments() {
+    let mock = mock([json_mock(Method::Get, "/pets/1", rex(None)).times(2)]);
+
+    run(&mock, async {
+        let mut api = Api::new(&mock);
+        assert_eq!(api.get_pet(1).await.unwrap(), rex(None));
+
+        // the builder of a tuple struct is still available
+        let pet = GetPetBuilder::new(&mut api)._0(1u64).execute().await;
+        assert_eq!(pet.unwrap(), rex(None));
+    });
+}
@@ -0,0 +1,86 @@
+use acril::{
+    http::mock::{Mock, MockResponse},
+    prelude::http::*,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{client, json_mock, mock, run, Client};
+
+endpoint_error!(http_types::Error);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Pet {
+    id: u64,
+    name: String,
+}
+
+#[derive(ClientEndpoint)]
+enum Pets {
+    #[endpoint(Get "/pets/{id}" in Client -> Pet)]
+    Get { id: u64 },
+    #[endpoint(Post(json) "/pets" in Client -> Pet)]
+    Create { name: String },
+}
+
+#[derive(ClientEndpoint)]
+enum Store {
+    #[endpoint(Get "/pets/{_0}" in Client -> Pet)]
+    Pet(u64),
+    #[endpoint(Delete(empty, empty) "/pets/{_0}" in Client)]
+    Delete(u64),
+    #[endpoint(Get "/count" in Client -> u64)]
+    Count,
+}
+
+fn rex() -> Pet {
+    Pet {
+        id: 1,
+        name: String::from("Rex"),
+    }
+}
+
+#[test]
+fn variants_are_separate_endpoints() {
+    let mock = mock([
+        json_mock(Method::Get, "/pets/1", rex()).times(1),
+        Mock::new(Method::Post, "/pets")
+            .json_body(&json!({ "name": "Rex" }))
+            .respond_with(MockResponse::new(StatusCode::Created).json(&rex()))
+            .times(1),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        assert_eq!(client.call(Pets::Get { id: 1 }).await.unwrap(), rex());
+        let created = client.call(Pets::Create {
+            name: String::from("Rex"),
+        });
+        assert_eq!(created.await.unwrap(), rex());
+    });
+}
+
+#[test]
+fn variants_with_different_outputs() {
+    let mock = mock([
+        json_mock(Method::Get, "/pets/1", rex()),
+        Mock::new(Method::Delete, "/pets/1").respond_with(MockResponse::new(StatusCode::NoContent)),
+        json_mock(Method::Get, "/count", 3),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        assert!(matches!(
+            client.call(Store::Pet(1)).await.unwrap(),
+            StoreOutput::Pet(pet) if pet == rex()
+        ));
+        assert!(matches!(
+            client.call(Store::Delete(1)).await.unwrap(),
+            StoreOutput::Delete(())
+        ));
+        assert!(matches!(
+            client.call(Store::Count).await.unwrap(),
+            StoreOutput::Count(3)
+        ));
+    });
+}
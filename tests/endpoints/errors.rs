@@ -0,0 +1,132 @@
+//! Errors of endpoints with `api_error` or their own error types, in a module without
+//! `endpoint_error!`.
+
+use acril::{
+    http::mock::{Mock, MockResponse},
+    prelude::http::*,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{client, json_mock, mock, run, Client};
+
+#[derive(Debug)]
+enum PetError {
+    Http(http_types::Error),
+    NotFound(String),
+}
+
+impl From<http_types::Error> for PetError {
+    fn from(e: http_types::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+impl From<EndpointError<Message>> for PetError {
+    fn from(e: EndpointError<Message>) -> Self {
+        match e {
+            EndpointError::Api(e) if e.status == StatusCode::NotFound => {
+                Self::NotFound(e.body.message)
+            }
+            EndpointError::Api(e) => Self::Http(http_types::format_err!("{}", e.body.message)),
+            EndpointError::Encode(e) | EndpointError::Transport(e) | EndpointError::Decode(e) => {
+                Self::Http(e)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    message: String,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/pets/{id}" in Client -> String, error = PetError)]
+struct GetPet {
+    id: u64,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/pets/{id}" in Client -> String, api_error = Message, error = PetError)]
+struct FindPet {
+    id: u64,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/pets/{id}" in Client -> String, api_error = Message)]
+struct LookUpPet {
+    id: u64,
+}
+
+#[test]
+fn errors_convert_with_from() {
+    // `1` is not a string
+    let mock = mock([json_mock(Method::Get, "/pets/1", 1)]);
+
+    run(&mock, async {
+        let error = client(&mock).call(GetPet { id: 1 }).await.unwrap_err();
+        assert!(
+            matches!(error, PetError::Http(e) if e.status() == StatusCode::UnprocessableEntity)
+        );
+    });
+}
+
+#[test]
+fn api_errors_convert_with_from() {
+    let mock = mock([Mock::new(Method::Get, "/pets/2").respond_with(
+        MockResponse::new(StatusCode::NotFound).json(&json!({ "message": "no pet 2" })),
+    )]);
+
+    run(&mock, async {
+        let error = client(&mock).call(FindPet { id: 2 }).await.unwrap_err();
+        assert!(matches!(error, PetError::NotFound(message) if message == "no pet 2"));
+    });
+}
+
+#[test]
+fn api_errors_without_error_types() {
+    let mock = mock([
+        Mock::new(Method::Get, "/pets/1").respond_with(
+            MockResponse::new(StatusCode::Conflict)
+                .header("Retry-After", "1")
+                .json(&json!({ "message": "busy" })),
+        ),
+        // neither the output nor the error body decode
+        json_mock(Method::Get, "/pets/2", 2),
+        Mock::new(Method::Get, "/pets/3")
+            .respond_with(MockResponse::new(StatusCode::BadGateway).text("upstream down")),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+
+        let error = client.call(LookUpPet { id: 1 }).await.unwrap_err();
+        let EndpointError::Api(ApiError {
+            status,
+            headers,
+            body,
+        }) = error
+        else {
+            panic!("expected an API error, got {error:?}");
+        };
+        assert_eq!(status, StatusCode::Conflict);
+        assert_eq!(headers["Retry-After"], "1");
+        assert_eq!(body.message, "busy");
+
+        let error = client.call(LookUpPet { id: 2 }).await.unwrap_err();
+        assert!(matches!(&error, EndpointError::Decode(_)));
+        assert_eq!(error.status(), Some(StatusCode::UnprocessableEntity));
+
+        let error = client.call(LookUpPet { id: 3 }).await.unwrap_err();
+        assert!(matches!(&error, EndpointError::Decode(_)));
+        assert_eq!(error.status(), Some(StatusCode::BadGateway));
+
+        // no mock matches
+        let error = client.call(LookUpPet { id: 4 }).await.unwrap_err();
+        assert!(
+            matches!(&error, EndpointError::Transport(e) if e.status() == StatusCode::NotFound)
+        );
+        assert_eq!(error.status(), None);
+    });
+}
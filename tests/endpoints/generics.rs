@@ -0,0 +1,71 @@
+use std::marker::PhantomData;
+
+use acril::prelude::http::*;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use super::{client, json_mock, mock, run, Api, Client};
+
+endpoint_error!(http_types::Error);
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Bar {
+    close: f64,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/{kind}" in Client -> Page<T>)]
+struct List<T: DeserializeOwned> {
+    #[path_param]
+    kind: &'static str,
+    #[serde(skip)]
+    _item: PhantomData<T>,
+}
+
+#[with_builder(get_bars)]
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/bars" in Api -> Vec<Bar>)]
+struct GetBars<'a> {
+    #[required]
+    #[query]
+    symbol: &'a str,
+    #[query]
+    limit: Option<u32>,
+}
+
+#[test]
+fn generic_outputs() {
+    let mock = mock([json_mock(
+        Method::Get,
+        "/names",
+        json!({ "items": ["a", "b"] }),
+    )]);
+
+    run(&mock, async {
+        let endpoint = List::<String> {
+            kind: "names",
+            _item: PhantomData,
+        };
+        let page = client(&mock).call(endpoint).await.unwrap();
+        assert_eq!(page.items, ["a", "b"]);
+    });
+}
+
+#[test]
+fn borrowed_fields() {
+    let mock = mock([json_mock(Method::Get, "/bars", json!([{ "close": 1.5 }]))
+        .query("symbol", "AAPL")
+        .query("limit", "1")]);
+    let symbol = String::from("AAPL");
+
+    run(&mock, async {
+        let mut api = Api::new(&mock);
+        let bars = api.get_bars().symbol(&*symbol).limit(1u32).execute().await;
+        assert_eq!(bars.unwrap(), [Bar { close: 1.5 }]);
+    });
+}
@@ -0,0 +1,113 @@
+//! Input modes encoding the endpoint as a form or multipart body.
+
+use acril::{
+    http::{
+        mock::{Mock, MockResponse},
+        multipart::File,
+    },
+    prelude::http::*,
+};
+use http_types::mime;
+use serde::Serialize;
+
+use super::{client, mock, run, Client};
+
+endpoint_error!(http_types::Error);
+
+#[derive(ClientEndpoint, Serialize)]
+#[endpoint(Post(form, display) "/login" in Client -> String)]
+struct Login {
+    user: String,
+    password: String,
+    remember: bool,
+}
+
+#[derive(ClientEndpoint, Serialize)]
+#[endpoint(Post(multipart, display) "/pets/{id}/photos" in Client -> String)]
+struct UploadPhoto {
+    #[path_param]
+    #[serde(skip)]
+    id: u64,
+    caption: String,
+    tags: Vec<String>,
+    note: Option<String>,
+    #[file]
+    #[serde(skip)]
+    photo: File,
+    #[file]
+    #[serde(skip)]
+    thumbnail: Vec<u8>,
+    #[file]
+    #[serde(skip)]
+    original: Option<File>,
+}
+
+#[test]
+fn form_bodies() {
+    let mock = mock([Mock::new(Method::Post, "/login")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .matching(|_, body| body == b"user=ada&password=a+%26+b&remember=true")
+        .respond_with(MockResponse::new(StatusCode::Ok).text("welcome"))
+        .times(1)]);
+
+    run(&mock, async {
+        let login = Login {
+            user: String::from("ada"),
+            password: String::from("a & b"),
+            remember: true,
+        };
+        assert_eq!(client(&mock).call(login).await.unwrap(), "welcome");
+    });
+}
+
+#[test]
+fn multipart_bodies() {
+    let mock = mock([Mock::new(Method::Post, "/pets/1/photos")
+        .matching(|request, body| {
+            let content_type = request.header("Content-Type").unwrap().as_str();
+            let Some(boundary) = content_type.strip_prefix("multipart/form-data;boundary=") else {
+                return false;
+            };
+            let expected = format!(
+                "--{boundary}\r\n\
+                 Content-Disposition: form-data; name=\"caption\"\r\n\
+                 \r\n\
+                 Rex \"asleep\"\r\n\
+                 --{boundary}\r\n\
+                 Content-Disposition: form-data; name=\"tags\"\r\n\
+                 \r\n\
+                 good\r\n\
+                 --{boundary}\r\n\
+                 Content-Disposition: form-data; name=\"tags\"\r\n\
+                 \r\n\
+                 boy\r\n\
+                 --{boundary}\r\n\
+                 Content-Disposition: form-data; name=\"photo\"; filename=\"rex.png\"\r\n\
+                 Content-Type: image/png\r\n\
+                 \r\n\
+                 \u{1}png\r\n\
+                 --{boundary}\r\n\
+                 Content-Disposition: form-data; name=\"thumbnail\"\r\n\
+                 Content-Type: application/octet-stream\r\n\
+                 \r\n\
+                 thumb\r\n\
+                 --{boundary}--\r\n"
+            );
+            body == expected.as_bytes()
+        })
+        .respond_with(MockResponse::new(StatusCode::Created).text("uploaded"))
+        .times(1)]);
+
+    run(&mock, async {
+        let upload = UploadPhoto {
+            id: 1,
+            caption: String::from("Rex \"asleep\""),
+            tags: vec![String::from("good"), String::from("boy")],
+            note: None,
+            photo: File::new("rex.png", *b"\x01png").mime(mime::PNG),
+            thumbnail: b"thumb".to_vec(),
+            original: None,
+        };
+        assert_eq!(client(&mock).call(upload).await.unwrap(), "uploaded");
+    });
+}
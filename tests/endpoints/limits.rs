@@ -0,0 +1,36 @@
+use acril::{
+    http::{
+        limit::BodyTooLarge,
+        mock::{Mock, MockResponse},
+    },
+    prelude::http::*,
+};
+
+use super::{client, mock, run, Client};
+
+endpoint_error!(http_types::Error);
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get(empty, display) "/{name}" in Client -> String, limit = 4)]
+struct Download {
+    name: &'static str,
+}
+
+#[test]
+fn limits_response_bodies() {
+    let mock = mock([
+        Mock::new(Method::Get, "/small")
+            .respond_with(MockResponse::new(StatusCode::Ok).text("1234")),
+        Mock::new(Method::Get, "/large")
+            .respond_with(MockResponse::new(StatusCode::Ok).text("12345")),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        let small = client.call(Download { name: "small" }).await.unwrap();
+        assert_eq!(small, "1234");
+
+        let error = client.call(Download { name: "large" }).await.unwrap_err();
+        assert_eq!(BodyTooLarge::find(&error), Some(&BodyTooLarge { limit: 4 }));
+    });
+}
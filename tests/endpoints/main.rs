@@ -0,0 +1,73 @@
+//! Endpoints derived with `#[derive(ClientEndpoint)]`, called through a [`MockMiddleware`].
+
+mod blocking;
+mod builders;
+mod enums;
+mod errors;
+mod generics;
+mod inputs;
+mod limits;
+mod meta;
+mod openapi;
+mod outputs;
+mod paginate;
+mod placement;
+
+use std::future::Future;
+
+use acril::{
+    http::mock::{Mock, MockMiddleware, MockResponse},
+    prelude::http::*,
+};
+use futures::executor::block_on;
+use serde::Serialize;
+
+/// The client of the endpoints of the tests.
+type Client = HttpClient<MockMiddleware>;
+
+/// A client sending its requests to `mock`.
+fn client(mock: &MockMiddleware) -> Client {
+    HttpClient::new_with(mock.clone()).with_base_url(Url::parse("http://api.test").unwrap())
+}
+
+/// A middleware answering with the first of `mocks` matching each request.
+fn mock(mocks: impl IntoIterator<Item = Mock>) -> MockMiddleware {
+    let middleware = MockMiddleware::new();
+    for mock in mocks {
+        middleware.mock(mock);
+    }
+    middleware
+}
+
+/// A mock of `method path`, answering `200 OK` with `body` as JSON.
+fn json_mock(method: Method, path: &str, body: impl Serialize) -> Mock {
+    Mock::new(method, path).respond_with(MockResponse::new(StatusCode::Ok).json(&body))
+}
+
+/// Run `test`, then check the expectations of `mock`.
+fn run<T>(mock: &MockMiddleware, test: impl Future<Output = T>) -> T {
+    let output = block_on(test);
+    mock.verify();
+    output
+}
+
+/// A client of the crate, which `#[with_builder]` can add methods to.
+pub struct Api(Client);
+
+impl Api {
+    fn new(mock: &MockMiddleware) -> Self {
+        Self(client(mock))
+    }
+}
+
+impl HttpClientContext for Api {
+    type Error = http_types::Error;
+
+    fn new_request(&self, method: Method, url: &str) -> Request {
+        self.0.new_request(method, url)
+    }
+
+    async fn run_request(&mut self, request: Request) -> Result<Response, Self::Error> {
+        self.0.run_request(request).await
+    }
+}
@@ -0,0 +1,82 @@
+use acril::{
+    http::mock::{Mock, MockMiddleware, MockResponse},
+    prelude::http::*,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{client, json_mock, mock, run, Client};
+
+endpoint_error!(http_types::Error);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Order {
+    id: u64,
+    #[serde(skip)]
+    etag: Option<String>,
+    #[serde(skip)]
+    remaining: u32,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/orders/{id}" in Client -> Order, with_meta)]
+struct GetOrderWithMeta {
+    id: u64,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(
+    Get "/orders/{id}" in Client -> Order,
+    headers(etag = "ETag", remaining = "X-RateLimit-Remaining"),
+)]
+struct GetOrder {
+    id: u64,
+}
+
+/// Order 1 is sent with an `ETag` and rate limit headers, order 2 without them.
+fn orders() -> MockMiddleware {
+    mock([
+        Mock::new(Method::Get, "/orders/1").respond_with(
+            MockResponse::new(StatusCode::Ok)
+                .header("ETag", "\"v1\"")
+                .header("X-RateLimit-Remaining", "59")
+                .json(&json!({ "id": 1 })),
+        ),
+        json_mock(Method::Get, "/orders/2", json!({ "id": 2 })),
+    ])
+}
+
+#[test]
+fn outputs_with_meta() {
+    let mock = orders();
+    run(&mock, async {
+        let order = client(&mock)
+            .call(GetOrderWithMeta { id: 1 })
+            .await
+            .unwrap();
+        assert_eq!(order.status, StatusCode::Ok);
+        assert_eq!(order.header("ETag").unwrap(), "\"v1\"");
+        assert_eq!(order.id, 1);
+        assert_eq!(order.etag, None);
+    });
+}
+
+#[test]
+fn fields_from_headers() {
+    let mock = orders();
+    run(&mock, async {
+        let order = client(&mock).call(GetOrder { id: 1 }).await.unwrap();
+        assert_eq!(
+            order,
+            Order {
+                id: 1,
+                etag: Some(String::from("\"v1\"")),
+                remaining: 59,
+            }
+        );
+
+        // `remaining` is not optional
+        let error = client(&mock).call(GetOrder { id: 2 }).await.unwrap_err();
+        assert_eq!(error.to_string(), "missing header `X-RateLimit-Remaining`");
+    });
+}
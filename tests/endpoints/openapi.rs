@@ -0,0 +1,72 @@
+//! Endpoints generated from `tests/fixtures/petstore.yaml`.
+
+use acril::{
+    http::mock::{Mock, MockResponse},
+    prelude::http::*,
+};
+use serde_json::json;
+
+use super::{json_mock, mock, run, Api};
+
+#[derive(Debug)]
+pub struct PetStoreError(http_types::Error);
+
+impl From<http_types::Error> for PetStoreError {
+    fn from(e: http_types::Error) -> Self {
+        Self(e)
+    }
+}
+
+openapi!(
+    "tests/fixtures/petstore.yaml",
+    client = Api,
+    error = PetStoreError
+);
+
+#[test]
+fn calls_generated_endpoints() {
+    let pet = json!({ "id": 1, "name": "Rex", "status": "available" });
+    let mock = mock([
+        json_mock(Method::Get, "/pets", [&pet])
+            .query("status", "available")
+            .times(1),
+        Mock::new(Method::Post, "/pets")
+            .header("X-Request-ID", "42")
+            .json_body(&json!({ "name": "Rex" }))
+            .respond_with(MockResponse::new(StatusCode::Created).json(&pet))
+            .times(1),
+        Mock::new(Method::Delete, "/pets/a%2Fb")
+            .respond_with(MockResponse::new(StatusCode::NoContent))
+            .times(1),
+    ]);
+
+    run(&mock, async {
+        let mut api = Api::new(&mock);
+
+        let pets = api.list_pets().status(Status::Available).execute().await;
+        let pets = pets.unwrap();
+        assert_eq!(pets.len(), 1);
+        assert_eq!((pets[0].id, pets[0].status), (1, Some(Status::Available)));
+
+        let new = NewPet {
+            name: String::from("Rex"),
+            status: None,
+        };
+        let pet = api.create_pet(String::from("42"), new).await.unwrap();
+        assert_eq!(pet.name, "Rex");
+
+        // the path parameter stays a single segment
+        api.delete_pet(String::from("a/b")).await.unwrap();
+    });
+}
+
+#[test]
+fn errors_convert_to_the_given_type() {
+    let mock = mock([]);
+    run(&mock, async {
+        // no mock matches
+        let error = Api::new(&mock).delete_pet(String::from("1")).await;
+        let PetStoreError(error) = error.unwrap_err();
+        assert_eq!(error.status(), StatusCode::NotFound);
+    });
+}
@@ -0,0 +1,164 @@
+//! Output modes decoding query strings, or returning raw or streamed bodies.
+
+use acril::{
+    http::{
+        mock::{Mock, MockResponse},
+        stream::{Event, EventStream, NdJson},
+    },
+    prelude::http::*,
+};
+use futures::TryStreamExt;
+use http_types::{mime, Body};
+use serde::Deserialize;
+
+use super::{client, mock, run, Client};
+
+endpoint_error!(http_types::Error);
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Tick {
+    price: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Token {
+    access_token: String,
+    expires_in: u32,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get(empty, query) "/tokens/{name}" in Client -> Token)]
+struct GetToken {
+    name: &'static str,
+}
+
+/// Count the lines of the body.
+async fn count_lines(response: &mut Response) -> http_types::Result<usize> {
+    Ok(response.body_string().await?.lines().count())
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get(empty, with = count_lines) "/report" in Client -> usize)]
+struct CountLines;
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get(empty, bytes) "/report" in Client -> Vec<u8>)]
+struct Report;
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get(empty, body) "/report" in Client -> Body)]
+struct ReportBody;
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get(empty, ndjson) "/ticks" in Client -> NdJson<Tick>)]
+struct Ticks;
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get(empty, sse) "/events" in Client -> EventStream)]
+struct Events;
+
+#[test]
+fn query_strings() {
+    let mock = mock([
+        Mock::new(Method::Get, "/tokens/valid").respond_with(
+            MockResponse::new(StatusCode::Ok).text("access_token=a%2Fb+c&expires_in=3600"),
+        ),
+        Mock::new(Method::Get, "/tokens/invalid")
+            .respond_with(MockResponse::new(StatusCode::Ok).text("expires_in=never")),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        let token = client.call(GetToken { name: "valid" }).await.unwrap();
+        assert_eq!(
+            token,
+            Token {
+                access_token: String::from("a/b c"),
+                expires_in: 3600,
+            }
+        );
+
+        let error = client.call(GetToken { name: "invalid" }).await.unwrap_err();
+        assert_eq!(error.status(), StatusCode::UnprocessableEntity);
+    });
+}
+
+#[test]
+fn custom_decoders() {
+    let mock = mock([Mock::new(Method::Get, "/report")
+        .respond_with(MockResponse::new(StatusCode::Ok).text("a,1\nb,2\nc,3\n"))
+        .times(1)]);
+
+    run(&mock, async {
+        assert_eq!(client(&mock).call(CountLines).await.unwrap(), 3);
+    });
+}
+
+#[test]
+fn raw_bodies() {
+    let mock = mock([Mock::new(Method::Get, "/report")
+        .respond_with(MockResponse::new(StatusCode::Ok).body(*b"\x00\xffreport"))
+        .times(2)]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        assert_eq!(client.call(Report).await.unwrap(), b"\x00\xffreport");
+
+        let body = client.call(ReportBody).await.unwrap();
+        assert_eq!(body.mime(), &mime::BYTE_STREAM);
+        assert_eq!(body.into_bytes().await.unwrap(), b"\x00\xffreport");
+    });
+}
+
+#[test]
+fn ndjson_streams() {
+    // values and lines are split across chunks
+    let chunks = ["{\"price\":1}\n{\"pri", "ce\":2}\n", "\n{\"price\"", ":3}"];
+    let mock = mock([Mock::new(Method::Get, "/ticks")
+        .respond_with(MockResponse::new(StatusCode::Ok).chunked(chunks, "application/x-ndjson"))]);
+
+    run(&mock, async {
+        let ticks = client(&mock).call(Ticks).await.unwrap();
+        assert_eq!(
+            ticks.try_collect::<Vec<_>>().await.unwrap(),
+            [Tick { price: 1 }, Tick { price: 2 }, Tick { price: 3 }]
+        );
+    });
+}
+
+#[test]
+fn sse_streams() {
+    // events and fields are split across chunks
+    let chunks = [
+        "event: tick\nid: 1\nda",
+        "ta: {\"price\":1}\n",
+        "\ndata: closed\n",
+        "\n",
+    ];
+    let mock = mock([Mock::new(Method::Get, "/events")
+        .respond_with(MockResponse::new(StatusCode::Ok).chunked(chunks, mime::SSE))]);
+
+    run(&mock, async {
+        let mut events = client(&mock).call(Events).await.unwrap();
+
+        let tick = events.try_next().await.unwrap().unwrap();
+        assert_eq!(
+            (tick.event.as_str(), tick.id.as_deref()),
+            ("tick", Some("1"))
+        );
+        assert_eq!(tick.json::<Tick>().unwrap(), Tick { price: 1 });
+
+        let closed = events.try_next().await.unwrap().unwrap();
+        assert_eq!(
+            closed,
+            Event {
+                event: String::from("message"),
+                data: String::from("closed"),
+                id: Some(String::from("1")),
+                retry: None,
+            }
+        );
+        assert!(events.try_next().await.unwrap().is_none());
+        assert_eq!(events.last_event_id(), Some("1"));
+    });
+}
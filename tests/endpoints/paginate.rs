@@ -0,0 +1,166 @@
+use acril::{
+    http::mock::{Mock, MockResponse},
+    prelude::http::*,
+};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{client, json_mock, mock, run, Client};
+
+endpoint_error!(http_types::Error);
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Order {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct OrdersPage {
+    orders: Vec<Order>,
+    next_cursor: Option<String>,
+}
+
+#[derive(ClientEndpoint, Serialize, Clone)]
+#[endpoint(
+    Get(query) "/orders" in Client -> OrdersPage,
+    paginate(cursor = after, next = next_cursor, item = Order, items = orders)
+)]
+struct ListOrders {
+    after: Option<String>,
+}
+
+#[derive(ClientEndpoint, Serialize, Clone)]
+#[endpoint(Get(query) "/numbers" in Client -> Vec<u32>, paginate(page = page, item = u32))]
+struct ListNumbers {
+    page: u32,
+}
+
+#[derive(ClientEndpoint, Serialize, Clone)]
+#[endpoint(
+    Get(query) "/numbers" in Client -> Vec<u32>,
+    paginate(offset = offset, size = limit, item = u32)
+)]
+struct ListNumbersAt {
+    offset: usize,
+    limit: usize,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/letters" in Client -> Vec<String>, paginate(link, item = String))]
+struct ListLetters;
+
+#[test]
+fn cursor_pagination() {
+    // later pages first, as the first page matches any cursor
+    let mock = mock([
+        json_mock(
+            Method::Get,
+            "/orders",
+            json!({ "orders": [{ "id": 3 }], "next_cursor": null }),
+        )
+        .query("after", "b")
+        .times(1),
+        json_mock(
+            Method::Get,
+            "/orders",
+            json!({ "orders": [{ "id": 1 }, { "id": 2 }], "next_cursor": "b" }),
+        )
+        .times(1),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        let orders = client.paginate(ListOrders { after: None });
+        let orders: Vec<_> = orders.try_collect().await.unwrap();
+        assert_eq!(orders, [Order { id: 1 }, Order { id: 2 }, Order { id: 3 }]);
+    });
+}
+
+#[test]
+fn page_pagination_ends_with_an_empty_page() {
+    let mock = mock([
+        json_mock(Method::Get, "/numbers", [1, 2])
+            .query("page", "1")
+            .times(1),
+        json_mock(Method::Get, "/numbers", [3])
+            .query("page", "2")
+            .times(1),
+        json_mock(Method::Get, "/numbers", [0; 0])
+            .query("page", "3")
+            .times(1),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        let numbers = client.paginate(ListNumbers { page: 1 });
+        assert_eq!(numbers.try_collect::<Vec<_>>().await.unwrap(), [1, 2, 3]);
+    });
+}
+
+#[test]
+fn offset_pagination_ends_with_a_short_page() {
+    let mock = mock([
+        json_mock(Method::Get, "/numbers", [1, 2])
+            .query("offset", "0")
+            .times(1),
+        json_mock(Method::Get, "/numbers", [3])
+            .query("offset", "2")
+            .times(1),
+        // `size = limit` saves requesting the empty page
+        json_mock(Method::Get, "/numbers", [0; 0])
+            .query("offset", "3")
+            .times(0),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        let numbers = client.paginate(ListNumbersAt {
+            offset: 0,
+            limit: 2,
+        });
+        assert_eq!(numbers.try_collect::<Vec<_>>().await.unwrap(), [1, 2, 3]);
+    });
+    assert!(mock.received().iter().all(|request| {
+        let query = request.url().query().unwrap_or_default();
+        query.contains("limit=2")
+    }));
+}
+
+#[test]
+fn link_pagination() {
+    let mock = mock([
+        json_mock(Method::Get, "/letters", ["c"])
+            .query("page", "2")
+            .times(1),
+        Mock::new(Method::Get, "/letters")
+            .respond_with(
+                MockResponse::new(StatusCode::Ok)
+                    .header("Link", "</letters?page=2>; rel=\"next\"")
+                    .json(&["a", "b"]),
+            )
+            .times(1),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        let letters = client.paginate(ListLetters);
+        assert_eq!(
+            letters.try_collect::<Vec<_>>().await.unwrap(),
+            ["a", "b", "c"]
+        );
+    });
+}
+
+#[test]
+fn pagination_stops_at_the_first_error() {
+    let mock = mock([json_mock(Method::Get, "/numbers", [1, 2]).query("page", "1")]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        let numbers = client.paginate(ListNumbers { page: 1 });
+        // no mock matches page 2
+        let error = numbers.try_collect::<Vec<_>>().await.unwrap_err();
+        assert_eq!(error.status(), StatusCode::NotFound);
+    });
+}
@@ -0,0 +1,105 @@
+use acril::prelude::http::*;
+use serde_json::json;
+
+use super::{client, json_mock, mock, run, Client};
+
+endpoint_error!(http_types::Error);
+
+#[derive(ClientEndpoint)]
+#[endpoint(Put(json) "/owners/{owner}/pets/{id}" in Client -> String)]
+struct RenamePet {
+    #[path_param]
+    owner: String,
+    #[path_param]
+    id: u64,
+    #[query]
+    notify: bool,
+    #[header("X-Request-Id")]
+    request_id: Option<String>,
+    #[body]
+    name: String,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Post(json) "/pets/{id}/tags" in Client -> Vec<String>)]
+struct AddTags {
+    #[path_param]
+    id: u64,
+    #[body(whole)]
+    tags: Vec<String>,
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/items/{id:04}/{name:>5}" in Client -> String)]
+struct GetItem {
+    #[path_param]
+    id: u32,
+    #[path_param]
+    name: &'static str,
+}
+
+#[test]
+fn fields_are_placed_one_by_one() {
+    let mock = mock([
+        json_mock(Method::Put, "/owners/Ada%2FLovelace/pets/1", "Rex")
+            .query("notify", "true")
+            .header("X-Request-Id", "42")
+            .json_body(&json!({ "name": "Rex" }))
+            .times(1),
+    ]);
+
+    run(&mock, async {
+        let mut client = client(&mock);
+        let name = client.call(RenamePet {
+            owner: String::from("Ada/Lovelace"),
+            id: 1,
+            notify: true,
+            request_id: Some(String::from("42")),
+            name: String::from("Rex"),
+        });
+        assert_eq!(name.await.unwrap(), "Rex");
+    });
+}
+
+#[test]
+fn missing_headers_are_omitted() {
+    let mock = mock([json_mock(Method::Put, "/owners/ada/pets/1", "Rex")]);
+
+    run(&mock, async {
+        let endpoint = RenamePet {
+            owner: String::from("ada"),
+            id: 1,
+            notify: false,
+            request_id: None,
+            name: String::from("Rex"),
+        };
+        client(&mock).call(endpoint).await.unwrap();
+    });
+    assert!(mock.received()[0].header("X-Request-Id").is_none());
+}
+
+#[test]
+fn whole_body_fields() {
+    let mock = mock([json_mock(Method::Post, "/pets/1/tags", ["good", "boy"])
+        .json_body(&json!(["good", "boy"]))
+        .times(1)]);
+
+    run(&mock, async {
+        let tags = vec![String::from("good"), String::from("boy")];
+        let added = client(&mock).call(AddTags { id: 1, tags }).await.unwrap();
+        assert_eq!(added, ["good", "boy"]);
+    });
+}
+
+#[test]
+fn path_params_keep_their_format_spec() {
+    let mock = mock([json_mock(Method::Get, "/items/0042/%20%20a%2Fb", "item").times(1)]);
+
+    run(&mock, async {
+        let endpoint = GetItem {
+            id: 42,
+            name: "a/b",
+        };
+        assert_eq!(client(&mock).call(endpoint).await.unwrap(), "item");
+    });
+}
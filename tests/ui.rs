@@ -0,0 +1,6 @@
+//! Endpoints the derive macros reject at compile time.
+
+#[test]
+fn ui() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,19 @@
+use acril::prelude::http::*;
+use std::fmt::{self, Display};
+
+endpoint_error!(http_types::Error);
+
+#[derive(ClientEndpoint)]
+#[endpoint(Post(display) "/say" in HttpClient -> String)]
+struct Say {
+    #[body]
+    message: String,
+}
+
+impl Display for Say {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use acril::prelude::http::*;
+
+endpoint_error!(http_types::Error);
+
+async fn encode(say: &Say, request: &mut Request) -> http_types::Result<()> {
+    request.set_body(say.message.as_str());
+    Ok(())
+}
+
+#[derive(ClientEndpoint)]
+#[endpoint(Post(with = encode) "/say" in HttpClient -> String)]
+struct Say {
+    #[body]
+    message: String,
+}
+
+fn main() {}
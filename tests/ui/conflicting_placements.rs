@@ -0,0 +1,13 @@
+use acril::prelude::http::*;
+
+endpoint_error!(http_types::Error);
+
+#[derive(ClientEndpoint)]
+#[endpoint(Get "/pets/{id}" in HttpClient -> String)]
+struct GetPet {
+    #[path_param]
+    #[query]
+    id: u64,
+}
+
+fn main() {}
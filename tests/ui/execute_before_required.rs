@@ -0,0 +1,33 @@
+use acril::prelude::http::*;
+
+endpoint_error!(http_types::Error);
+
+pub struct Api(HttpClient);
+
+impl HttpClientContext for Api {
+    type Error = http_types::Error;
+
+    fn new_request(&self, method: Method, url: &str) -> Request {
+        self.0.new_request(method, url)
+    }
+
+    async fn run_request(&mut self, request: Request) -> Result<Response, Self::Error> {
+        self.0.run_request(request).await
+    }
+}
+
+#[with_builder(add_pet)]
+#[derive(ClientEndpoint, serde::Serialize)]
+#[endpoint(Post(json) "/pets" in Api -> String)]
+struct AddPet {
+    #[required]
+    name: String,
+    #[required]
+    age: u32,
+    tag: Option<String>,
+}
+
+fn main() {
+    let mut api = Api(HttpClient::new());
+    let _ = api.add_pet().name("Rex").execute();
+}